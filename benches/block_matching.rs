@@ -0,0 +1,53 @@
+//! Block matching on the inputs of the `pyram_lucas_kanade` benches, as a baseline (the
+//! endpoint errors of both are compared in the tests of `BlockMatching`):
+//!
+//! ```sh
+//! cargo bench --bench block_matching
+//! ```
+#![feature(test)]
+
+extern crate image;
+extern crate miro;
+extern crate test;
+
+use fixtures::{grid, texture};
+use miro::core::motion::Flow;
+use miro::modules::motion::{BlockMatching, Search};
+use test::Bencher;
+
+mod fixtures;
+
+fn bench_points(b: &mut Bencher, search: Search) {
+    let image_i = texture(640, 360, 0.0, 0.0);
+    let image_j = texture(640, 360, 3.0, -2.0);
+    let points = grid((100.0, 60.0), (440.0, 240.0), 10);
+
+    let bm = BlockMatching::new(8, 16).search(search);
+
+    b.iter(|| bm.flow(&image_i, &points, &image_j).unwrap());
+}
+
+#[bench]
+fn points_100_exhaustive(b: &mut Bencher) {
+    bench_points(b, Search::Exhaustive);
+}
+
+#[bench]
+fn points_100_three_step(b: &mut Bencher) {
+    bench_points(b, Search::ThreeStep);
+}
+
+#[bench]
+fn points_100_diamond(b: &mut Bencher) {
+    bench_points(b, Search::Diamond);
+}
+
+#[bench]
+fn field_diamond(b: &mut Bencher) {
+    let image_i = texture(640, 360, 0.0, 0.0);
+    let image_j = texture(640, 360, 3.0, -2.0);
+
+    let bm = BlockMatching::new(16, 16);
+
+    b.iter(|| bm.field(&image_i, &image_j).unwrap());
+}
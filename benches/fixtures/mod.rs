@@ -0,0 +1,28 @@
+//! The inputs shared by the benches (the same as the ones of the tests of the library).
+
+use image::{GrayImage, Luma};
+use miro::utility::plane_euclidean::Point;
+
+/// A smooth `width × height` texture, shifted by (`dx`, `dy`).
+pub fn texture(width: u32, height: u32, dx: f32, dy: f32) -> GrayImage {
+    GrayImage::from_fn(width, height, |x, y| {
+        let (x, y) = (x as f32 - dx, y as f32 - dy);
+        let value = 127.5 + 63.0 * (x / 7.0).sin() * (y / 11.0).cos() + 63.0 * ((x + y) / 17.0).sin();
+
+        Luma { data: [value as u8] }
+    })
+}
+
+/// A `λ × λ` grid of points spanning the `width × height` rectangle at (`x`, `y`) (`λ = 10`
+/// sends 100 points, as `MedianFlow` does by default).
+pub fn grid((x, y): (f32, f32), (width, height): (f32, f32), λ: usize) -> Vec<Point<f32>> {
+    let mut points = Vec::with_capacity(λ * λ);
+
+    for m in 0..λ {
+        for n in 0..λ {
+            points.push([x + m as f32 * width / λ as f32, y + n as f32 * height / λ as f32].into());
+        }
+    }
+
+    points
+}
@@ -0,0 +1,38 @@
+//! Run with and without the "parallel" feature to compare:
+//!
+//! ```sh
+//! cargo bench --bench pyram_lucas_kanade
+//! cargo bench --bench pyram_lucas_kanade --features "parallel"
+//! ```
+#![feature(test)]
+
+extern crate image;
+extern crate miro;
+extern crate test;
+
+use fixtures::{grid, texture};
+use miro::core::motion::Flow;
+use miro::modules::motion::PyramLk;
+use test::Bencher;
+
+mod fixtures;
+
+fn bench_points(b: &mut Bencher, λ: usize) {
+    let image_i = texture(640, 360, 0.0, 0.0);
+    let image_j = texture(640, 360, 3.0, -2.0);
+    let points = grid((100.0, 60.0), (440.0, 240.0), λ);
+
+    let lk = PyramLk::default();
+
+    b.iter(|| lk.flow(&image_i, &points, &image_j).unwrap());
+}
+
+#[bench]
+fn points_100(b: &mut Bencher) {
+    bench_points(b, 10);
+}
+
+#[bench]
+fn points_400(b: &mut Bencher) {
+    bench_points(b, 20);
+}
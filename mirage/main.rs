@@ -1,16 +1,16 @@
 use high::{capture, piston};
 use image::ConvertBuffer;
 
-use miro::core::tracking::Track;
+use miro::core::tracking::TrackMut;
 use miro::modules::motion::PyramLk;
-use miro::modules::tracking::MedianFlow;
+use miro::modules::tracking::{MedianFlow, Predictive};
 
 mod util {
 
 	include!(concat!("../main-", "util.rs"));
 }
 
-let mf = MedianFlow::<PyramLk>::default();
+let mut mf = Predictive::new(MedianFlow::<PyramLk>::default());
 
 let color = [0.8125, 0.8125, 0.8125, 0.75];
 let mut pressed = false;
@@ -41,11 +41,14 @@ capture::conn();
 
 					if let [Some(ref imI), Some(ref imJ)] = images {
 
-						if let Ok(re) = mf.track(imI, (*r).into(), imJ) {
+						if let Ok(re) = mf.track_mut(imI, (*r).into(), imJ) {
 
 							*r = re.into();
 						}
 					}
+				} else {
+
+					mf.reset();
 				}
 			},
 
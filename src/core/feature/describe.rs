@@ -0,0 +1,20 @@
+use error::Result;
+use super::Keypoint;
+
+/// Feature description
+///
+/// Summarizes the neighbourhood of keypoints in descriptors that can be compared to match the
+/// keypoints across images.
+pub trait Describe<I> {
+
+    /// The type of the descriptors.
+    type Descriptor;
+
+    /// Describes the `keypoints` of `image`.
+    ///
+    /// # Returns
+    ///
+    /// A descriptor for every keypoint, in the same order, or `None` if the neighbourhood of the
+    /// keypoint isn't entirely inside the image.
+    fn describe(&self, image: &I, keypoints: &[Keypoint]) -> Result<Vec<Option<Self::Descriptor>>>;
+}
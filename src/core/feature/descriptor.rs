@@ -0,0 +1,120 @@
+/// A distance between descriptors.
+pub trait Distance {
+
+    /// Returns the distance to `other`.
+    fn distance(&self, other: &Self) -> f32;
+}
+
+/// A binary descriptor, packed in 64-bit words.
+///
+/// Binary descriptors are compared with the Hamming distance: the number of differing bits.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct BinaryDescriptor {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl BinaryDescriptor {
+
+    /// Constructs a descriptor of `len` bits, all unset.
+    pub fn new(len: usize) -> BinaryDescriptor {
+        BinaryDescriptor { words: vec![0; (len + 63) / 64], len }
+    }
+
+    /// Returns the number of bits.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the descriptor has no bits.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the packed bits. Bit `n` is bit `n % 64` of word `n / 64`.
+    pub fn as_words(&self) -> &[u64] {
+        &self.words
+    }
+
+    /// Returns the bit `n`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is out of bounds.
+    pub fn get(&self, n: usize) -> bool {
+        assert!(n < self.len, "bit {} out of bounds ({} bits)", n, self.len);
+
+        self.words[n / 64] & (1 << (n % 64)) != 0
+    }
+
+    /// Sets the bit `n` to `value`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is out of bounds.
+    pub fn set(&mut self, n: usize, value: bool) {
+        assert!(n < self.len, "bit {} out of bounds ({} bits)", n, self.len);
+
+        if value {
+            self.words[n / 64] |= 1 << (n % 64);
+        } else {
+            self.words[n / 64] &= !(1 << (n % 64));
+        }
+    }
+
+    /// Returns the Hamming distance to `other`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the descriptors don't have the same length.
+    pub fn hamming(&self, other: &BinaryDescriptor) -> u32 {
+        assert_eq!(self.len, other.len, "descriptors of different lengths");
+
+        self.words.iter().zip(&other.words).map(|(a, b)| (a ^ b).count_ones()).sum()
+    }
+}
+
+impl Distance for BinaryDescriptor {
+
+    /// The Hamming distance.
+    fn distance(&self, other: &BinaryDescriptor) -> f32 {
+        self.hamming(other) as f32
+    }
+}
+
+impl Distance for Vec<f32> {
+
+    /// The Euclidean (L2) distance.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the descriptors don't have the same length.
+    fn distance(&self, other: &Vec<f32>) -> f32 {
+        assert_eq!(self.len(), other.len(), "descriptors of different lengths");
+
+        self.iter().zip(other).map(|(a, b)| (a - b) * (a - b)).sum::<f32>().sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BinaryDescriptor;
+
+    #[test]
+    fn hamming_distance() {
+        let mut a = BinaryDescriptor::new(100);
+        let mut b = BinaryDescriptor::new(100);
+
+        a.set(0, true);
+        a.set(70, true);
+        b.set(70, true);
+        b.set(99, true);
+
+        assert!(a.get(70) && !a.get(99));
+        assert_eq!(a.hamming(&b), 2);
+
+        b.set(99, false);
+
+        assert_eq!(a.hamming(&b), 1);
+    }
+}
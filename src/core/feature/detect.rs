@@ -0,0 +1,58 @@
+use error::Result;
+use std::f32;
+use utility::plane_euclidean::Point;
+
+/// Feature detection
+///
+/// Finds distinctive points (e.g., corners or blobs) that can be located again on another view
+/// of the same scene.
+pub trait Detect<I> {
+
+    /// Detects the keypoints of `image`.
+    ///
+    /// # Returns
+    ///
+    /// The keypoints, in the coordinates of `image` (or of the bottom of an image pyramid).
+    fn detect(&self, image: &I) -> Result<Vec<Keypoint>>;
+}
+
+/// A point found by a `Detect` algorithm.
+#[derive(Clone, Copy, Debug)]
+pub struct Keypoint {
+    /// The location of the keypoint, in the coordinates of the image (or of the bottom of the
+    /// image pyramid).
+    pub point: Point<f32>,
+    /// The strength of the keypoint. Its meaning and range depend on the detector, but a
+    /// stronger keypoint always has a larger response.
+    pub response: f32,
+    /// The size of the neighbourhood described by the keypoint, relative to the bottom of the
+    /// image pyramid (`2^level` for detectors that don't refine it).
+    pub scale: f32,
+    /// The level of the image pyramid on which the keypoint was found.
+    pub level: usize,
+    /// The orientation of the keypoint, in radians.
+    ///
+    /// `NaN` if the detector doesn't compute it.
+    pub angle: f32,
+}
+
+impl Keypoint {
+
+    /// Constructs a keypoint found at (`x`, `y`) on the bottom of the image pyramid, without
+    /// orientation.
+    pub fn new(x: f32, y: f32, response: f32) -> Keypoint {
+        Keypoint { point: [x, y].into(), response, scale: 1.0, level: 0, angle: f32::NAN }
+    }
+
+    /// Returns the coordinates of the keypoint.
+    pub fn coordinates(&self) -> (f32, f32) {
+        let [x, y] = self.point.coordinates();
+
+        (x.into(), y.into())
+    }
+
+    /// Returns `true` if the keypoint has an orientation.
+    pub fn has_angle(&self) -> bool {
+        !self.angle.is_nan()
+    }
+}
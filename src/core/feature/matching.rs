@@ -0,0 +1,10 @@
+/// A match between two descriptors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Match {
+    /// The index of the query descriptor.
+    pub query: usize,
+    /// The index of the matched train descriptor.
+    pub train: usize,
+    /// The distance between the descriptors.
+    pub distance: f32,
+}
@@ -0,0 +1,9 @@
+pub use self::describe::Describe;
+pub use self::descriptor::{BinaryDescriptor, Distance};
+pub use self::detect::{Detect, Keypoint};
+pub use self::matching::Match;
+
+mod describe;
+mod descriptor;
+mod detect;
+mod matching;
@@ -0,0 +1,48 @@
+use error::Result;
+
+/// A source of video frames: a camera, a video file or a processing stage.
+pub trait FrameSource {
+    /// The type of the frames.
+    type Frame;
+
+    /// Returns the next frame, or `None` once the source is exhausted.
+    fn next_frame(&mut self) -> Result<Option<Self::Frame>>;
+}
+
+/// A `FrameSource` that yields the frames of an iterator (e.g., images decoded from disk).
+#[derive(Debug)]
+pub struct IterSource<I> { frames: I }
+
+impl<I> IterSource<I> where I: Iterator {
+
+    /// Constructs a `FrameSource` from an iterator over frames.
+    pub fn new<T>(frames: T) -> IterSource<I> where T: IntoIterator<IntoIter = I, Item = I::Item> {
+
+        IterSource { frames: frames.into_iter() }
+    }
+}
+
+impl<I> FrameSource for IterSource<I> where I: Iterator {
+
+    type Frame = I::Item;
+
+    fn next_frame(&mut self) -> Result<Option<I::Item>> {
+        Ok(self.frames.next())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FrameSource, IterSource};
+
+    #[test]
+    fn yields_the_frames_in_order() {
+        let mut source = IterSource::new(vec![1, 2, 3]);
+
+        assert_eq!(source.next_frame().unwrap(), Some(1));
+        assert_eq!(source.next_frame().unwrap(), Some(2));
+        assert_eq!(source.next_frame().unwrap(), Some(3));
+        assert_eq!(source.next_frame().unwrap(), None);
+        assert_eq!(source.next_frame().unwrap(), None);
+    }
+}
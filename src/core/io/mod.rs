@@ -0,0 +1,3 @@
+pub use self::frame_source::{FrameSource, IterSource};
+
+mod frame_source;
@@ -1,4 +1,5 @@
 pub mod classification;
+pub mod feature;
 pub mod io;
 pub mod motion;
-pub mod tracking;
\ No newline at end of file
+pub mod tracking;
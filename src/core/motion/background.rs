@@ -0,0 +1,592 @@
+use error::{Error, ErrorKind, Result};
+use piston_image::GrayImage;
+use std::mem;
+
+/// The value of foreground pixels in the masks.
+const FOREGROUND: u8 = 255;
+
+/// The value of background (and shadow) pixels in the masks.
+const BACKGROUND: u8 = 0;
+
+/// Background subtraction
+///
+/// A model of the background of a fixed camera, updated with every frame.
+pub trait Subtract {
+
+    /// Updates the model with `frame` and returns its foreground mask.
+    ///
+    /// # Returns
+    ///
+    /// A binary mask of the dimensions of `frame`: 255 for foreground pixels, 0 for background
+    /// (and suppressed shadow) pixels. The first frame initializes the model and is entirely
+    /// background. Returns an error if the dimensions of `frame` differ from those of the
+    /// previous frames.
+    fn subtract(&mut self, frame: &GrayImage) -> Result<GrayImage>;
+
+    /// Returns the current estimate of the background, or `None` before the first frame.
+    fn background(&self) -> Option<GrayImage>;
+
+    /// Forgets the model. The next frame initializes a new one.
+    fn reset(&mut self);
+}
+
+/// Shadow suppression
+///
+/// A cast shadow darkens the background without changing its texture. A foreground pixel whose
+/// intensity `i` and background intensity `b` satisfy `lower ≤ i / b ≤ upper` is classified as
+/// a shadow, and reported as background.
+///
+/// ## References
+///
+/// * Prati, A., Mikic, I., Trivedi, M. M., & Cucchiara, R. (2003). *Detecting moving shadows:
+///   algorithms and evaluation*. IEEE Transactions on Pattern Analysis and Machine
+///   Intelligence, 25(7), 918-923.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Shadows {
+    /// The lower bound of the intensity ratio (how dark a shadow can be).
+    pub lower: f32,
+    /// The upper bound of the intensity ratio (how light a shadow can be).
+    pub upper: f32,
+}
+
+impl Shadows {
+
+    fn contains(&self, intensity: f32, background: f32) -> bool {
+
+        if background <= 0.0 {
+            return false;
+        }
+
+        let ratio = intensity / background;
+
+        ratio >= self.lower && ratio <= self.upper
+    }
+}
+
+impl Default for Shadows {
+
+    /// Returns the bounds `[0.5, 0.95]`.
+    fn default() -> Shadows {
+        Shadows { lower: 0.5, upper: 0.95 }
+    }
+}
+
+/// Checks the dimensions of `frame` against those of the model.
+///
+/// Returns `true` if the model is uninitialized.
+fn check(dimensions: Option<(u32, u32)>, frame: &GrayImage) -> Result<bool> {
+
+    match dimensions {
+        None => Ok(true),
+        Some(dimensions) if dimensions == frame.dimensions() => Ok(false),
+        Some((width, height)) => {
+            let (frame_width, frame_height) = frame.dimensions();
+
+            let message = format!("expected a frame of {}×{} pixels, found {}×{}",
+                width, height, frame_width, frame_height);
+
+            Err(Error::new(ErrorKind::Motion, message))
+        }
+    }
+}
+
+fn to_image(dimensions: Option<(u32, u32)>, background: &[f32]) -> Option<GrayImage> {
+
+    dimensions.map(|(width, height)| {
+        let data = background.iter().map(|&b| b.round().max(0.0).min(255.0) as u8).collect();
+
+        GrayImage::from_raw(width, height, data).unwrap()
+    })
+}
+
+/// Running-average background model
+///
+/// The background is an exponential moving average of the frames:
+/// `b ← (1 − α) b + α i`. A pixel is foreground if `|i − b| > threshold`.
+#[derive(Clone, Debug)]
+pub struct RunningAverage {
+    learning_rate: f32,
+    threshold: f32,
+    shadows: Option<Shadows>,
+
+    dimensions: Option<(u32, u32)>,
+    background: Vec<f32>,
+}
+
+impl RunningAverage {
+
+    /// Constructs a model with a learning rate of 0.05, a threshold of 25 intensity levels and
+    /// no shadow suppression.
+    pub fn new() -> RunningAverage {
+
+        RunningAverage {
+            learning_rate: 0.05,
+            threshold: 25.0,
+            shadows: None,
+            dimensions: None,
+            background: Vec::new(),
+        }
+    }
+
+    /// Sets the learning rate `α`, clamped to `[0, 1]`.
+    pub fn learning_rate(mut self, learning_rate: f32) -> Self {
+        self.learning_rate = learning_rate.max(0.0).min(1.0);
+        self
+    }
+
+    /// Sets the foreground threshold, in intensity levels.
+    pub fn threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Enables (or disables, with `None`) shadow suppression.
+    pub fn shadows<S: Into<Option<Shadows>>>(mut self, shadows: S) -> Self {
+        self.shadows = shadows.into();
+        self
+    }
+}
+
+impl Default for RunningAverage {
+
+    fn default() -> RunningAverage {
+        RunningAverage::new()
+    }
+}
+
+impl Subtract for RunningAverage {
+
+    fn subtract(&mut self, frame: &GrayImage) -> Result<GrayImage> {
+
+        if check(self.dimensions, frame)? {
+            self.dimensions = Some(frame.dimensions());
+            self.background = frame.iter().map(|&i| i as f32).collect();
+        }
+
+        let (width, height) = frame.dimensions();
+        let mut mask = vec![BACKGROUND; (width * height) as usize];
+
+        for ((&i, b), m) in frame.iter().zip(self.background.iter_mut()).zip(mask.iter_mut()) {
+            let i = i as f32;
+
+            if (i - *b).abs() > self.threshold && !self.shadows.map_or(false, |s| s.contains(i, *b)) {
+                *m = FOREGROUND;
+            }
+
+            *b += self.learning_rate * (i - *b);
+        }
+
+        Ok(GrayImage::from_raw(width, height, mask).unwrap())
+    }
+
+    fn background(&self) -> Option<GrayImage> {
+        to_image(self.dimensions, &self.background)
+    }
+
+    fn reset(&mut self) {
+        self.dimensions = None;
+        self.background.clear();
+    }
+}
+
+/// Approximate-median background model
+///
+/// Every frame moves the background of each pixel towards its intensity by a fixed step of
+/// `learning_rate × 255` intensity levels, so that the background converges to the median of
+/// the recent intensities. Unlike the running average, it isn't dragged by brief outliers. A
+/// pixel is foreground if `|i − b| > threshold`.
+///
+/// ## References
+///
+/// * McFarlane, N. J., & Schofield, C. P. (1995). *Segmentation and tracking of piglets in
+///   images*. Machine Vision and Applications, 8(3), 187-193.
+#[derive(Clone, Debug)]
+pub struct Median {
+    learning_rate: f32,
+    threshold: f32,
+    shadows: Option<Shadows>,
+
+    dimensions: Option<(u32, u32)>,
+    background: Vec<f32>,
+}
+
+impl Median {
+
+    /// Constructs a model with a step of one intensity level per frame, a threshold of 25
+    /// intensity levels and no shadow suppression.
+    pub fn new() -> Median {
+
+        Median {
+            learning_rate: 1.0 / 255.0,
+            threshold: 25.0,
+            shadows: None,
+            dimensions: None,
+            background: Vec::new(),
+        }
+    }
+
+    /// Sets the learning rate (the step per frame, as a fraction of the intensity range),
+    /// clamped to `[0, 1]`.
+    pub fn learning_rate(mut self, learning_rate: f32) -> Self {
+        self.learning_rate = learning_rate.max(0.0).min(1.0);
+        self
+    }
+
+    /// Sets the foreground threshold, in intensity levels.
+    pub fn threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Enables (or disables, with `None`) shadow suppression.
+    pub fn shadows<S: Into<Option<Shadows>>>(mut self, shadows: S) -> Self {
+        self.shadows = shadows.into();
+        self
+    }
+}
+
+impl Default for Median {
+
+    fn default() -> Median {
+        Median::new()
+    }
+}
+
+impl Subtract for Median {
+
+    fn subtract(&mut self, frame: &GrayImage) -> Result<GrayImage> {
+
+        if check(self.dimensions, frame)? {
+            self.dimensions = Some(frame.dimensions());
+            self.background = frame.iter().map(|&i| i as f32).collect();
+        }
+
+        let (width, height) = frame.dimensions();
+        let mut mask = vec![BACKGROUND; (width * height) as usize];
+
+        let step = self.learning_rate * 255.0;
+
+        for ((&i, b), m) in frame.iter().zip(self.background.iter_mut()).zip(mask.iter_mut()) {
+            let i = i as f32;
+
+            if (i - *b).abs() > self.threshold && !self.shadows.map_or(false, |s| s.contains(i, *b)) {
+                *m = FOREGROUND;
+            }
+
+            // don't overshoot the intensity
+            if i > *b {
+                *b = (*b + step).min(i);
+            } else {
+                *b = (*b - step).max(i);
+            }
+        }
+
+        Ok(GrayImage::from_raw(width, height, mask).unwrap())
+    }
+
+    fn background(&self) -> Option<GrayImage> {
+        to_image(self.dimensions, &self.background)
+    }
+
+    fn reset(&mut self) {
+        self.dimensions = None;
+        self.background.clear();
+    }
+}
+
+/// A component of the mixture of a pixel.
+#[derive(Clone, Copy, Debug, Default)]
+struct Gaussian {
+    weight: f32,
+    mean: f32,
+    variance: f32,
+}
+
+/// Gaussian-mixture background model (MOG2)
+///
+/// The intensity of each pixel is modelled by a mixture of up to `components` Gaussians,
+/// sorted by decreasing weight. The heaviest Gaussians whose cumulated weight reaches
+/// `background_ratio` model the background; the others model transient objects. A pixel is
+/// background if it lies within `√var_threshold` standard deviations of a background Gaussian.
+/// Gaussians are updated with the learning rate `α`, and new ones are created for intensities
+/// that match none. Multi-modal backgrounds (e.g., swaying trees, flickering screens) are
+/// learned as several background Gaussians.
+///
+/// ## References
+///
+/// * Zivkovic, Z. (2004). *Improved adaptive Gaussian mixture model for background
+///   subtraction*. In Proceedings of the 17th International Conference on Pattern
+///   Recognition (Vol. 2, pp. 28-31). IEEE.
+/// * Stauffer, C., & Grimson, W. E. L. (1999). *Adaptive background mixture models for
+///   real-time tracking*. In Proceedings of the IEEE Conference on Computer Vision and Pattern
+///   Recognition (Vol. 2, pp. 246-252). IEEE.
+#[derive(Clone, Debug)]
+pub struct GaussianMixture {
+    learning_rate: f32,
+    components: usize,
+    background_ratio: f32,
+    var_threshold: f32,
+    shadows: Option<Shadows>,
+
+    dimensions: Option<(u32, u32)>,
+    /// `components` Gaussians per pixel.
+    mixtures: Vec<Gaussian>,
+    /// The number of Gaussians used by each pixel.
+    counts: Vec<u8>,
+}
+
+/// The squared number of standard deviations within which an intensity updates a Gaussian.
+const VAR_THRESHOLD_GEN: f32 = 9.0;
+
+/// The variance of new Gaussians.
+const VAR_INIT: f32 = 15.0;
+
+/// The bounds of the variances.
+const VAR_MIN: f32 = 4.0;
+const VAR_MAX: f32 = 75.0;
+
+impl GaussianMixture {
+
+    /// Constructs a model with a learning rate of 1/500, 5 Gaussians per pixel, a background
+    /// ratio of 0.9, a variance threshold of 16 (4σ) and shadow suppression.
+    pub fn new() -> GaussianMixture {
+
+        GaussianMixture {
+            learning_rate: 1.0 / 500.0,
+            components: 5,
+            background_ratio: 0.9,
+            var_threshold: 16.0,
+            shadows: Some(Shadows::default()),
+            dimensions: None,
+            mixtures: Vec::new(),
+            counts: Vec::new(),
+        }
+    }
+
+    /// Sets the learning rate `α`, clamped to `[0, 1]`. A rate of `1 / n` adapts to the last
+    /// `n` frames or so.
+    pub fn learning_rate(mut self, learning_rate: f32) -> Self {
+        self.learning_rate = learning_rate.max(0.0).min(1.0);
+        self
+    }
+
+    /// Sets the maximum number of Gaussians per pixel, clamped to `[1, 255]`. Resets the model.
+    pub fn components(mut self, components: usize) -> Self {
+        self.components = components.max(1).min(255);
+        self.reset();
+        self
+    }
+
+    /// Sets the fraction of the weights that models the background.
+    pub fn background_ratio(mut self, background_ratio: f32) -> Self {
+        self.background_ratio = background_ratio;
+        self
+    }
+
+    /// Sets the threshold on the squared Mahalanobis distance to a background Gaussian.
+    pub fn var_threshold(mut self, var_threshold: f32) -> Self {
+        self.var_threshold = var_threshold;
+        self
+    }
+
+    /// Enables (or disables, with `None`) shadow suppression.
+    pub fn shadows<S: Into<Option<Shadows>>>(mut self, shadows: S) -> Self {
+        self.shadows = shadows.into();
+        self
+    }
+
+    /// Updates the mixture of a pixel with the intensity `i` and classifies it.
+    fn update(&self, mixture: &mut [Gaussian], count: &mut u8, i: f32) -> u8 {
+
+        let α = self.learning_rate;
+
+        let mut total = 0.0;
+        let mut fits = false;
+        let mut background = false;
+
+        let mut k = 0;
+
+        while k < *count as usize {
+            let mut weight = (1.0 - α) * mixture[k].weight;
+
+            if !fits {
+                let Gaussian { mean, variance, .. } = mixture[k];
+                let d2 = (i - mean).powi(2);
+
+                if total < self.background_ratio && d2 < self.var_threshold * variance {
+                    background = true;
+                }
+
+                if d2 < VAR_THRESHOLD_GEN * variance {
+                    fits = true;
+
+                    weight += α;
+
+                    let rate = α / weight;
+
+                    mixture[k].mean = mean + rate * (i - mean);
+                    mixture[k].variance = (variance + rate * (d2 - variance)).max(VAR_MIN).min(VAR_MAX);
+                    mixture[k].weight = weight;
+
+                    // keep the Gaussians sorted by weight
+                    let mut j = k;
+
+                    while j > 0 && mixture[j - 1].weight < weight {
+                        mixture.swap(j - 1, j);
+                        j -= 1;
+                    }
+
+                    total += weight;
+                    k += 1;
+
+                    continue;
+                }
+            }
+
+            mixture[k].weight = weight;
+            total += weight;
+            k += 1;
+        }
+
+        if !fits {
+            // add a Gaussian, or replace the lightest one if the mixture is full
+            let k = if (*count as usize) < self.components {
+                *count += 1;
+                *count as usize - 1
+            } else {
+                total -= mixture[*count as usize - 1].weight;
+                *count as usize - 1
+            };
+
+            total += α;
+            mixture[k] = Gaussian { weight: α, mean: i, variance: VAR_INIT };
+
+            let mut j = k;
+
+            while j > 0 && mixture[j - 1].weight < α {
+                mixture.swap(j - 1, j);
+                j -= 1;
+            }
+        }
+
+        if total > 0.0 {
+            for gaussian in &mut mixture[..*count as usize] {
+                gaussian.weight /= total;
+            }
+        }
+
+        if background {
+            return BACKGROUND;
+        }
+
+        let shadow = self.shadows.map_or(false, |shadows| {
+            let mut total = 0.0;
+
+            mixture[..*count as usize].iter()
+                .take_while(|g| { let bg = total < self.background_ratio; total += g.weight; bg })
+                .any(|g| shadows.contains(i, g.mean))
+        });
+
+        if shadow { BACKGROUND } else { FOREGROUND }
+    }
+}
+
+impl Default for GaussianMixture {
+
+    fn default() -> GaussianMixture {
+        GaussianMixture::new()
+    }
+}
+
+impl Subtract for GaussianMixture {
+
+    fn subtract(&mut self, frame: &GrayImage) -> Result<GrayImage> {
+
+        let (width, height) = frame.dimensions();
+        let n = self.components;
+
+        if check(self.dimensions, frame)? {
+            self.dimensions = Some((width, height));
+            self.mixtures = vec![Gaussian::default(); (width * height) as usize * n];
+            self.counts = vec![1; (width * height) as usize];
+
+            for (mixture, &i) in self.mixtures.chunks_mut(n).zip(frame.iter()) {
+                mixture[0] = Gaussian { weight: 1.0, mean: i as f32, variance: VAR_INIT };
+            }
+        }
+
+        let mut mixtures = mem::replace(&mut self.mixtures, Vec::new());
+        let mut counts = mem::replace(&mut self.counts, Vec::new());
+
+        let mask = {
+            mixtures.chunks_mut(n).zip(counts.iter_mut()).zip(frame.iter())
+                .map(|((mixture, count), &i)| self.update(mixture, count, i as f32))
+                .collect()
+        };
+
+        self.mixtures = mixtures;
+        self.counts = counts;
+
+        Ok(GrayImage::from_raw(width, height, mask).unwrap())
+    }
+
+    /// Returns the mean of the heaviest Gaussian of every pixel.
+    fn background(&self) -> Option<GrayImage> {
+        let means: Vec<f32> = self.mixtures.chunks(self.components).map(|m| m[0].mean).collect();
+
+        to_image(self.dimensions, &means)
+    }
+
+    fn reset(&mut self) {
+        self.dimensions = None;
+        self.mixtures.clear();
+        self.counts.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use piston_image::{GrayImage, Luma};
+    use super::{GaussianMixture, Median, RunningAverage, Shadows, Subtract};
+
+    fn scene(object: Option<(u32, u8)>) -> GrayImage {
+        GrayImage::from_fn(32, 32, |x, y| {
+            match object {
+                Some((at, intensity)) if x >= at && x < at + 8 && y >= 12 && y < 20 => Luma { data: [intensity] },
+                _ => Luma { data: [100 + ((x + y) % 4) as u8] },
+            }
+        })
+    }
+
+    fn foreground(mask: &GrayImage) -> usize {
+        mask.iter().filter(|&&m| m == 255).count()
+    }
+
+    fn detects<S: Subtract>(mut model: S) {
+        for _ in 0..20 {
+            assert_eq!(foreground(&model.subtract(&scene(None)).unwrap()), 0);
+        }
+
+        assert_eq!(foreground(&model.subtract(&scene(Some((4, 220)))).unwrap()), 64);
+
+        // a shadow
+        assert_eq!(foreground(&model.subtract(&scene(Some((20, 70)))).unwrap()), 0);
+
+        assert!(model.subtract(&GrayImage::new(8, 8)).is_err());
+    }
+
+    #[test]
+    fn running_average() {
+        detects(RunningAverage::new().shadows(Shadows::default()));
+    }
+
+    #[test]
+    fn median() {
+        detects(Median::new().shadows(Shadows::default()));
+    }
+
+    #[test]
+    fn gaussian_mixture() {
+        detects(GaussianMixture::new());
+    }
+}
@@ -0,0 +1,420 @@
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use error::{Error, ErrorKind, Result};
+use piston_image::{Rgba, RgbaImage};
+use std::cmp;
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// The tag at the start of every `.flo` file ("PIEH" in ASCII).
+const TAG: f32 = 202021.25;
+
+/// Flow components with an absolute value greater than this threshold are treated as unknown.
+const UNKNOWN_FLOW_THRESHOLD: f32 = 1e9;
+
+/// Sanity limit on the dimensions read from a `.flo` header.
+const MAX_DIMENSION: i32 = 99999;
+
+/// The number of components allocated up front by `FlowField::read`.
+const READ_CAPACITY: usize = 1 << 16;
+
+/// A dense optical flow field: a horizontal and a vertical displacement (`u`, `v`) per pixel.
+///
+/// ## Middlebury `.flo` format
+///
+/// Fields can be read and written using the binary format of the [Middlebury] optical flow
+/// benchmark. All values are stored in little-endian order:
+///
+/// * bytes 0-3: the float `202021.25` (the ASCII string "PIEH"), used as a sanity check.
+/// * bytes 4-7: the width as an integer.
+/// * bytes 8-11: the height as an integer.
+/// * bytes 12-end: the `u` and `v` components as floats, interleaved, in row major order.
+///
+/// Components larger than `1e9` mark the flow at that pixel as unknown. Unknown pixels are
+/// rendered black and are ignored by the error measures.
+///
+/// [Middlebury]: http://vision.middlebury.edu/flow/
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlowField {
+    width: u32,
+    height: u32,
+    /// The interleaved `u` and `v` components.
+    data: Vec<f32>,
+}
+
+impl FlowField {
+
+    /// Constructs a new flow field of the specified dimensions with zero flow at every pixel.
+    pub fn new(width: u32, height: u32) -> FlowField {
+
+        let len = width as usize * height as usize * 2;
+
+        FlowField { width, height, data: vec![0.0; len] }
+    }
+
+    /// Constructs a flow field from interleaved `u` and `v` components.
+    ///
+    /// Returns `None` if the length of `data` doesn't match the dimensions.
+    pub fn from_raw(width: u32, height: u32, data: Vec<f32>) -> Option<FlowField> {
+
+        if data.len() != width as usize * height as usize * 2 {
+            return None;
+        }
+
+        Some(FlowField { width, height, data })
+    }
+
+    /// Returns the width and the height of the field.
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Returns the interleaved `u` and `v` components.
+    pub fn as_raw(&self) -> &[f32] {
+        &self.data
+    }
+
+    /// Returns the flow vector `[u, v]` at (`x`, `y`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if (`x`, `y`) is out of bounds.
+    pub fn get(&self, x: u32, y: u32) -> [f32; 2] {
+        let index = self.index(x, y);
+
+        [self.data[index], self.data[index + 1]]
+    }
+
+    /// Sets the flow vector at (`x`, `y`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if (`x`, `y`) is out of bounds.
+    pub fn set(&mut self, x: u32, y: u32, flow: [f32; 2]) {
+        let index = self.index(x, y);
+
+        self.data[index] = flow[0];
+        self.data[index + 1] = flow[1];
+    }
+
+    /// Returns `true` if the flow at (`x`, `y`) is known.
+    pub fn is_known(&self, x: u32, y: u32) -> bool {
+        let [u, v] = self.get(x, y);
+
+        is_known(u, v)
+    }
+
+    /// Reads a flow field from a `.flo` file.
+    pub fn open<P>(path: P) -> Result<FlowField> where P: AsRef<Path> {
+
+        let file = File::open(path).map_err(|err| Error::new(ErrorKind::Io, err))?;
+
+        FlowField::read(&mut BufReader::new(file))
+    }
+
+    /// Reads a flow field in the `.flo` format from `reader`.
+    pub fn read<R>(reader: &mut R) -> Result<FlowField> where R: Read {
+
+        let tag = reader.read_f32::<LittleEndian>().map_err(|err| Error::new(ErrorKind::Io, err))?;
+
+        if tag != TAG {
+            return Err(Error::new(ErrorKind::Io, format!("wrong `.flo` tag: {}", tag)));
+        }
+
+        let width = reader.read_i32::<LittleEndian>().map_err(|err| Error::new(ErrorKind::Io, err))?;
+        let height = reader.read_i32::<LittleEndian>().map_err(|err| Error::new(ErrorKind::Io, err))?;
+
+        if width < 1 || width > MAX_DIMENSION || height < 1 || height > MAX_DIMENSION {
+            let message = format!("illegal `.flo` dimensions: {}x{}", width, height);
+
+            return Err(Error::new(ErrorKind::Io, message));
+        }
+
+        let (width, height) = (width as u32, height as u32);
+        let row = 2 * width as usize;
+
+        // the buffer grows with the data actually read, so that a truncated file can't force
+        // the allocation of the whole field announced by its header
+        let mut data = Vec::with_capacity(cmp::min(row * height as usize, READ_CAPACITY));
+
+        for _ in 0..height {
+            for _ in 0..row {
+                let component = reader.read_f32::<LittleEndian>()
+                    .map_err(|err| Error::new(ErrorKind::Io, err))?;
+
+                data.push(component);
+            }
+        }
+
+        Ok(FlowField { width, height, data })
+    }
+
+    /// Writes the flow field to a `.flo` file.
+    pub fn save<P>(&self, path: P) -> Result where P: AsRef<Path> {
+
+        let file = File::create(path).map_err(|err| Error::new(ErrorKind::Io, err))?;
+
+        self.write(&mut BufWriter::new(file))
+    }
+
+    /// Writes the flow field in the `.flo` format to `writer`.
+    pub fn write<W>(&self, writer: &mut W) -> Result where W: Write {
+
+        let mut write = || -> ::std::io::Result<()> {
+            writer.write_f32::<LittleEndian>(TAG)?;
+            writer.write_i32::<LittleEndian>(self.width as i32)?;
+            writer.write_i32::<LittleEndian>(self.height as i32)?;
+
+            for &component in &self.data {
+                writer.write_f32::<LittleEndian>(component)?;
+            }
+
+            writer.flush()
+        };
+
+        write().map_err(|err| Error::new(ErrorKind::Io, err))
+    }
+
+    /// Renders the flow field using the colour-wheel encoding of the Middlebury benchmark.
+    ///
+    /// The hue encodes the direction of the flow and the saturation encodes its magnitude. Zero
+    /// flow is white and unknown flow is black.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_magnitude` - The magnitude mapped to a fully saturated colour. If `None`, the
+    ///   largest known magnitude in the field is used.
+    pub fn to_rgba(&self, max_magnitude: Option<f32>) -> RgbaImage {
+
+        let max_magnitude = max_magnitude.unwrap_or_else(|| {
+            self.data.chunks(2)
+                .filter(|flow| is_known(flow[0], flow[1]))
+                .map(|flow| flow[0].hypot(flow[1]))
+                .fold(0.0, f32::max)
+        });
+
+        // avoid dividing by zero when the field contains no motion.
+        let max_magnitude = if max_magnitude > 0.0 { max_magnitude } else { 1.0 };
+
+        let wheel = colour_wheel();
+
+        let mut image = RgbaImage::new(self.width, self.height);
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let [u, v] = self.get(x, y);
+
+                let data = if is_known(u, v) {
+                    colour(&wheel, u / max_magnitude, v / max_magnitude)
+                } else {
+                    [0, 0, 0, 255]
+                };
+
+                image.put_pixel(x, y, Rgba { data: data });
+            }
+        }
+
+        image
+    }
+
+    /// Computes the average endpoint error (EPE) against a ground-truth field.
+    ///
+    /// The endpoint error at a pixel is the Euclidean distance between the estimated and the
+    /// ground-truth flow vectors. Pixels where either flow is unknown are ignored.
+    ///
+    /// Returns an error if the fields don't have the same dimensions.
+    pub fn endpoint_error(&self, truth: &FlowField) -> Result<f32> {
+
+        self.average_error(truth, |u, v, u_t, v_t| (u - u_t).hypot(v - v_t))
+    }
+
+    /// Computes the average angular error (AE), in degrees, against a ground-truth field.
+    ///
+    /// The angular error at a pixel is the angle between the space-time vectors `(u, v, 1)` and
+    /// `(uₜ, vₜ, 1)` (Barron et al.). Pixels where either flow is unknown are ignored.
+    ///
+    /// Returns an error if the fields don't have the same dimensions.
+    pub fn angular_error(&self, truth: &FlowField) -> Result<f32> {
+
+        self.average_error(truth, |u, v, u_t, v_t| {
+            let dot = u * u_t + v * v_t + 1.0;
+            let norm = ((u * u + v * v + 1.0) * (u_t * u_t + v_t * v_t + 1.0)).sqrt();
+
+            // rounding may push the cosine slightly outside of [-1, 1]
+            (dot / norm).min(1.0).max(-1.0).acos().to_degrees()
+        })
+    }
+
+    fn average_error<F>(&self, truth: &FlowField, error: F) -> Result<f32>
+        where F: Fn(f32, f32, f32, f32) -> f32
+    {
+        if self.dimensions() != truth.dimensions() {
+            let message = format!("flow field dimensions differ: {}x{} and {}x{}",
+                self.width, self.height, truth.width, truth.height);
+
+            return Err(Error::new(ErrorKind::Motion, message));
+        }
+
+        let mut sum = 0.0f64;
+        let mut count = 0usize;
+
+        for (flow, flow_t) in self.data.chunks(2).zip(truth.data.chunks(2)) {
+            let (u, v, u_t, v_t) = (flow[0], flow[1], flow_t[0], flow_t[1]);
+
+            if is_known(u, v) && is_known(u_t, v_t) {
+                sum += error(u, v, u_t, v_t) as f64;
+                count += 1;
+            }
+        }
+
+        if count == 0 {
+            return Ok(0.0);
+        }
+
+        Ok((sum / count as f64) as f32)
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        assert!(x < self.width && y < self.height,
+            "({}, {}) is outside of the field bounds {}x{}", x, y, self.width, self.height);
+
+        (y as usize * self.width as usize + x as usize) * 2
+    }
+}
+
+fn is_known(u: f32, v: f32) -> bool {
+    u.is_finite() && v.is_finite()
+        && u.abs() <= UNKNOWN_FLOW_THRESHOLD && v.abs() <= UNKNOWN_FLOW_THRESHOLD
+}
+
+/// Builds the colour wheel of the Middlebury benchmark.
+///
+/// The number of hues between each pair of primary colours is chosen according to the
+/// perceptual similarity of the colours.
+fn colour_wheel() -> Vec<[f32; 3]> {
+    // red-yellow, yellow-green, green-cyan, cyan-blue, blue-magenta, magenta-red
+    const RY: usize = 15;
+    const YG: usize = 6;
+    const GC: usize = 4;
+    const CB: usize = 11;
+    const BM: usize = 13;
+    const MR: usize = 6;
+
+    let ramp = |i: usize, n: usize| (255 * i / n) as f32;
+
+    let mut wheel = Vec::with_capacity(RY + YG + GC + CB + BM + MR);
+
+    wheel.extend((0..RY).map(|i| [255.0, ramp(i, RY), 0.0]));
+    wheel.extend((0..YG).map(|i| [255.0 - ramp(i, YG), 255.0, 0.0]));
+    wheel.extend((0..GC).map(|i| [0.0, 255.0, ramp(i, GC)]));
+    wheel.extend((0..CB).map(|i| [0.0, 255.0 - ramp(i, CB), 255.0]));
+    wheel.extend((0..BM).map(|i| [ramp(i, BM), 0.0, 255.0]));
+    wheel.extend((0..MR).map(|i| [255.0, 0.0, 255.0 - ramp(i, MR)]));
+
+    wheel
+}
+
+/// Maps a normalized flow vector to a colour of the wheel.
+fn colour(wheel: &[[f32; 3]], u: f32, v: f32) -> [u8; 4] {
+    let ncols = wheel.len();
+
+    let radius = u.hypot(v);
+    let angle = (-v).atan2(-u) / PI;
+
+    let fk = (angle + 1.0) / 2.0 * (ncols - 1) as f32;
+    let k0 = fk.floor() as usize % ncols;
+    let k1 = (k0 + 1) % ncols;
+    let f = fk - fk.floor();
+
+    let mut data = [0, 0, 0, 255];
+
+    for channel in 0..3 {
+        let col0 = wheel[k0][channel] / 255.0;
+        let col1 = wheel[k1][channel] / 255.0;
+
+        let col = (1.0 - f) * col0 + f * col1;
+
+        let col = if radius <= 1.0 {
+            // increase saturation with radius
+            1.0 - radius * (1.0 - col)
+        } else {
+            // out of range
+            col * 0.75
+        };
+
+        data[channel] = (255.0 * col) as u8;
+    }
+
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use super::FlowField;
+
+    #[test]
+    fn flo_round_trip() {
+        let mut field = FlowField::new(3, 2);
+        field.set(0, 0, [1.5, -2.0]);
+        field.set(2, 1, [1e10, 0.0]);
+
+        let mut bytes = Vec::new();
+        field.write(&mut bytes).unwrap();
+
+        assert_eq!(bytes.len(), 12 + 3 * 2 * 2 * 4);
+
+        let read = FlowField::read(&mut Cursor::new(bytes)).unwrap();
+
+        assert_eq!(read, field);
+        assert!(!read.is_known(2, 1));
+    }
+
+    #[test]
+    fn flo_wrong_tag() {
+        let bytes = vec![0u8; 16];
+
+        assert!(FlowField::read(&mut Cursor::new(bytes)).is_err());
+    }
+
+    #[test]
+    fn flo_truncated() {
+        let mut field = FlowField::new(3, 2);
+        field.set(1, 1, [0.5, 0.25]);
+
+        let mut bytes = Vec::new();
+        field.write(&mut bytes).unwrap();
+        bytes.truncate(12 + 7 * 4);
+
+        assert!(FlowField::read(&mut Cursor::new(bytes)).is_err());
+
+        // a header announcing the largest field, without any data
+        let mut bytes = Vec::new();
+        FlowField::new(1, 1).write(&mut bytes).unwrap();
+        bytes.truncate(4);
+        bytes.extend_from_slice(&[0x9f, 0x86, 0x01, 0x00, 0x9f, 0x86, 0x01, 0x00]);
+
+        assert!(FlowField::read(&mut Cursor::new(bytes)).is_err());
+    }
+
+    #[test]
+    fn zero_flow_is_white() {
+        let field = FlowField::new(1, 1);
+        let image = field.to_rgba(None);
+
+        assert_eq!(image.get_pixel(0, 0).data, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn error_measures() {
+        let mut field = FlowField::new(2, 1);
+        let mut truth = FlowField::new(2, 1);
+        field.set(0, 0, [3.0, 4.0]);
+        truth.set(1, 0, [1e10, 1e10]);
+
+        assert_eq!(field.endpoint_error(&truth).unwrap(), 5.0);
+        assert_eq!(truth.angular_error(&truth).unwrap(), 0.0);
+        assert!(field.endpoint_error(&FlowField::new(1, 1)).is_err());
+    }
+}
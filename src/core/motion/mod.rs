@@ -1,2 +1,9 @@
-pub use self::optical_flow::{Flow, FlowMut};
-mod optical_flow;
\ No newline at end of file
+pub use self::background::{GaussianMixture, Median, RunningAverage, Shadows, Subtract};
+pub use self::flow_field::FlowField;
+pub use self::model::{Estimate, Estimator, Method, MotionModel, Transform};
+pub use self::optical_flow::{Flow, FlowMut, FlowPoint, FlowStatus};
+
+mod background;
+mod flow_field;
+mod model;
+mod optical_flow;
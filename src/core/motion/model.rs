@@ -0,0 +1,594 @@
+use error::{Error, ErrorKind, Result};
+use nalgebra::{DMat, DVec, Inv};
+use rand::{self, Rng};
+use std::{cmp, f64};
+use super::FlowPoint;
+use utility::plane_euclidean::Point;
+
+/// A planar projective transform (a homography), represented by a 3×3 matrix acting on
+/// homogeneous coordinates.
+///
+/// Similarity and affine transforms are special cases whose last row is `[0 0 1]`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    /// The matrix, in row-major order.
+    pub matrix: [[f64; 3]; 3],
+}
+
+impl Transform {
+
+    /// Returns the identity transform.
+    pub fn identity() -> Transform {
+        Transform { matrix: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]] }
+    }
+
+    /// Returns the similarity transform that scales by `scale`, rotates by `angle` (in radians)
+    /// and then translates by (`tx`, `ty`).
+    pub fn similarity(scale: f64, angle: f64, tx: f64, ty: f64) -> Transform {
+        let (a, b) = (scale * angle.cos(), scale * angle.sin());
+
+        Transform { matrix: [[a, -b, tx], [b, a, ty], [0.0, 0.0, 1.0]] }
+    }
+
+    /// Transforms the point (`x`, `y`).
+    ///
+    /// Points mapped to infinity by a homography have infinite or `NaN` coordinates.
+    pub fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        let m = &self.matrix;
+
+        let w = m[2][0] * x + m[2][1] * y + m[2][2];
+
+        ((m[0][0] * x + m[0][1] * y + m[0][2]) / w, (m[1][0] * x + m[1][1] * y + m[1][2]) / w)
+    }
+
+    /// Returns the composition `self ∘ other`, i.e., the transform `x ↦ self(other(x))`.
+    pub fn compose(&self, other: &Transform) -> Transform {
+        let (a, b) = (&self.matrix, &other.matrix);
+
+        let mut matrix = [[0.0; 3]; 3];
+
+        for r in 0..3 {
+            for c in 0..3 {
+                matrix[r][c] = (0..3).fold(0.0, |sum, k| sum + a[r][k] * b[k][c]);
+            }
+        }
+
+        Transform { matrix }
+    }
+
+    /// Returns the inverse transform, or `None` if the transform is degenerate.
+    pub fn inverse(&self) -> Option<Transform> {
+        let m = &self.matrix;
+
+        let cofactor = |r0: usize, r1: usize, c0: usize, c1: usize| {
+            m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+        };
+
+        let det = m[0][0] * cofactor(1, 2, 1, 2)
+            - m[0][1] * cofactor(1, 2, 0, 2)
+            + m[0][2] * cofactor(1, 2, 0, 1);
+
+        if det.abs() < f64::EPSILON {
+            return None;
+        }
+
+        let adjugate = [
+            [ cofactor(1, 2, 1, 2), -cofactor(0, 2, 1, 2),  cofactor(0, 1, 1, 2)],
+            [-cofactor(1, 2, 0, 2),  cofactor(0, 2, 0, 2), -cofactor(0, 1, 0, 2)],
+            [ cofactor(1, 2, 0, 1), -cofactor(0, 2, 0, 1),  cofactor(0, 1, 0, 1)],
+        ];
+
+        let mut matrix = [[0.0; 3]; 3];
+
+        for r in 0..3 {
+            for c in 0..3 {
+                matrix[r][c] = adjugate[r][c] / det;
+            }
+        }
+
+        Some(Transform { matrix })
+    }
+}
+
+/// The family of transforms to estimate.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum MotionModel {
+    /// Rotation, uniform scale and translation (4 degrees of freedom).
+    Similarity,
+    /// Linear transform and translation (6 degrees of freedom).
+    Affine,
+    /// Projective transform (8 degrees of freedom).
+    Homography,
+}
+
+impl MotionModel {
+
+    /// The minimum number of correspondences that determine a transform.
+    pub fn min_samples(&self) -> usize {
+        match *self {
+            MotionModel::Similarity => 2,
+            MotionModel::Affine => 3,
+            MotionModel::Homography => 4,
+        }
+    }
+
+    fn nparams(&self) -> usize {
+        match *self {
+            MotionModel::Similarity => 4,
+            MotionModel::Affine => 6,
+            MotionModel::Homography => 8,
+        }
+    }
+}
+
+/// The robust estimation method.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Method {
+    /// Random sample consensus: keeps the hypothesis with the largest number of correspondences
+    /// whose reprojection error is below `threshold` (in pixels).
+    Ransac { threshold: f64 },
+    /// Least median of squares: keeps the hypothesis with the smallest median squared
+    /// reprojection error. No threshold is needed, but at most half of the correspondences may
+    /// be outliers.
+    LMedS,
+}
+
+/// The outcome of an estimation.
+#[derive(Clone, Debug)]
+pub struct Estimate {
+    /// The estimated transform, mapping the source points to the destination points.
+    pub transform: Transform,
+    /// `true` for every correspondence consistent with `transform`.
+    pub inliers: Vec<bool>,
+}
+
+impl Estimate {
+
+    /// Returns the number of inliers.
+    pub fn ninliers(&self) -> usize {
+        self.inliers.iter().filter(|&&inlier| inlier).count()
+    }
+}
+
+/// Robust estimation of the global motion between two images from point correspondences.
+///
+/// A transform is fitted to many random minimal subsets of the correspondences (RANSAC or
+/// LMedS); the best hypothesis is then refined by a least-squares fit on all of its inliers.
+/// The coordinates are normalized (Hartley) before every fit to keep the linear systems well
+/// conditioned.
+///
+/// ```rust,ignore
+/// use miro::core::motion::{Estimator, Flow, Method, MotionModel};
+///
+/// let flow = lk.flow(&image_i, &points_i, &image_j)?;
+/// let estimate = Estimator::new(MotionModel::Similarity, Method::Ransac { threshold: 1.5 })
+///     .estimate_flow(&points_i, &flow)?;
+/// ```
+///
+/// ## References/Resources
+///
+/// * [Martin A. Fischler and Robert C. Bolles. Random Sample Consensus. 1981][1]
+/// * [Peter J. Rousseeuw. Least Median of Squares Regression. 1984][2]
+/// * Richard Hartley and Andrew Zisserman. Multiple View Geometry in Computer Vision. 2004
+///
+/// [1]: http://www.dtic.mil/dtic/tr/fulltext/u2/a460585.pdf
+/// [2]: https://doi.org/10.1080/01621459.1984.10477105
+#[derive(Clone, Debug)]
+pub struct Estimator {
+    model: MotionModel,
+    method: Method,
+    /// The maximum number of hypotheses.
+    max_iterations: usize,
+    /// The desired probability that at least one hypothesis is outlier-free. The number of
+    /// hypotheses is adapted to the observed inlier ratio.
+    confidence: f64,
+}
+
+impl Estimator {
+
+    /// Constructs an `Estimator` with at most 2000 hypotheses and a confidence of 0.995.
+    pub fn new(model: MotionModel, method: Method) -> Estimator {
+
+        Estimator { model, method, max_iterations: 2000, confidence: 0.995 }
+    }
+
+    /// Sets the maximum number of hypotheses. If `max_iterations` is 0, 1 is chosen.
+    pub fn max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = cmp::max(1, max_iterations);
+        self
+    }
+
+    /// Sets the confidence, clamped to `[0, 1)`.
+    pub fn confidence(mut self, confidence: f64) -> Self {
+        self.confidence = confidence.max(0.0).min(1.0 - f64::EPSILON);
+        self
+    }
+
+    /// Estimates the transform mapping `points_i` to the tracked points in `flow`.
+    ///
+    /// Points that were not tracked are ignored and are never inliers.
+    pub fn estimate_flow(&self, points_i: &[Point<f32>], flow: &[FlowPoint]) -> Result<Estimate> {
+
+        if points_i.len() != flow.len() {
+            let message = format!("expected {} flow points, found {}", points_i.len(), flow.len());
+
+            return Err(Error::new(ErrorKind::Motion, message));
+        }
+
+        let (mut src, mut dst, mut indices) = (Vec::new(), Vec::new(), Vec::new());
+
+        for (index, (point_i, flow_point)) in points_i.iter().zip(flow).enumerate() {
+            if let Some(point_j) = flow_point.tracked() {
+                src.push(point_i.clone());
+                dst.push(point_j);
+                indices.push(index);
+            }
+        }
+
+        let estimate = self.estimate(&src, &dst)?;
+
+        let mut inliers = vec![false; points_i.len()];
+
+        for (&index, &inlier) in indices.iter().zip(&estimate.inliers) {
+            inliers[index] = inlier;
+        }
+
+        Ok(Estimate { transform: estimate.transform, inliers })
+    }
+
+    /// Estimates the transform mapping `src` to `dst`.
+    pub fn estimate(&self, src: &[Point<f32>], dst: &[Point<f32>]) -> Result<Estimate> {
+
+        self.estimate_with_rng(&mut rand::thread_rng(), src, dst)
+    }
+
+    /// Estimates the transform mapping `src` to `dst`, drawing the samples from `rng`.
+    pub fn estimate_with_rng<R>(&self, rng: &mut R, src: &[Point<f32>], dst: &[Point<f32>])
+        -> Result<Estimate>
+        where R: Rng
+    {
+        let nsamples = self.model.min_samples();
+
+        if src.len() != dst.len() {
+            let message = format!("{} source points but {} destination points", src.len(), dst.len());
+
+            return Err(Error::new(ErrorKind::Motion, message));
+        }
+
+        if src.len() < nsamples {
+            let message = format!("{:?} needs at least {} correspondences, found {}",
+                self.model, nsamples, src.len());
+
+            return Err(Error::new(ErrorKind::Motion, message));
+        }
+
+        let src: Vec<_> = src.iter().map(coordinates).collect();
+        let dst: Vec<_> = dst.iter().map(coordinates).collect();
+
+        let npoints = src.len();
+
+        let mut best: Option<(Transform, f64)> = None;
+
+        let mut niterations = self.max_iterations;
+        let mut iteration = 0;
+
+        let mut sample = Vec::with_capacity(nsamples);
+        let mut sample_src = Vec::with_capacity(nsamples);
+        let mut sample_dst = Vec::with_capacity(nsamples);
+
+        while iteration < niterations {
+            iteration += 1;
+
+            // draw a minimal sample of distinct correspondences
+            sample.clear();
+
+            while sample.len() < nsamples {
+                let index = rng.gen_range(0, npoints);
+
+                if !sample.contains(&index) {
+                    sample.push(index);
+                }
+            }
+
+            sample_src.clear();
+            sample_dst.clear();
+            sample_src.extend(sample.iter().map(|&index| src[index]));
+            sample_dst.extend(sample.iter().map(|&index| dst[index]));
+
+            let hypothesis = match fit(self.model, &sample_src, &sample_dst) {
+                Some(transform) => transform,
+                // degenerate sample (e.g., collinear points)
+                None => continue,
+            };
+
+            let errors = squared_errors(&hypothesis, &src, &dst);
+
+            // lower is better
+            let (cost, ninliers) = match self.method {
+                Method::Ransac { threshold } => {
+                    let threshold = threshold * threshold;
+                    let ninliers = errors.iter().filter(|&&error| error <= threshold).count();
+
+                    (-(ninliers as f64), ninliers)
+                },
+                Method::LMedS => {
+                    let median = median(errors.clone());
+
+                    // assume that at least half of the correspondences are inliers
+                    (median, npoints / 2)
+                },
+            };
+
+            if best.map_or(true, |(_, best_cost)| cost < best_cost) {
+                best = Some((hypothesis, cost));
+
+                niterations = cmp::min(
+                    niterations,
+                    adaptive_iterations(ninliers, npoints, nsamples, self.confidence));
+            }
+        }
+
+        let (hypothesis, _) = best.ok_or_else(|| {
+            Error::new(ErrorKind::Motion, "every sample was degenerate")
+        })?;
+
+        let threshold = self.inlier_threshold(&squared_errors(&hypothesis, &src, &dst));
+        let inliers = inlier_mask(&hypothesis, &src, &dst, threshold);
+
+        // least-squares refinement on the inliers
+        let refined = {
+            let (inlier_src, inlier_dst): (Vec<_>, Vec<_>) = src.iter()
+                .zip(&dst)
+                .zip(&inliers)
+                .filter(|&(_, &inlier)| inlier)
+                .map(|((&s, &d), _)| (s, d))
+                .unzip();
+
+            fit(self.model, &inlier_src, &inlier_dst)
+        };
+
+        let estimate = match refined {
+            Some(transform) => {
+                let refined_inliers = inlier_mask(&transform, &src, &dst, threshold);
+
+                if count(&refined_inliers) >= count(&inliers) {
+                    Estimate { transform, inliers: refined_inliers }
+                } else {
+                    Estimate { transform: hypothesis, inliers }
+                }
+            },
+            None => Estimate { transform: hypothesis, inliers },
+        };
+
+        Ok(estimate)
+    }
+
+    /// The squared reprojection error threshold that separates inliers from outliers.
+    fn inlier_threshold(&self, errors: &[f64]) -> f64 {
+        match self.method {
+            Method::Ransac { threshold } => threshold * threshold,
+            Method::LMedS => {
+                // robust standard deviation (Rousseeuw and Leroy)
+                let nsamples = self.model.min_samples();
+                let dof = cmp::max(1, errors.len().saturating_sub(nsamples)) as f64;
+                let σ = 1.4826 * (1.0 + 5.0 / dof) * median(errors.to_vec()).sqrt();
+
+                (2.5 * σ).powi(2)
+            },
+        }
+    }
+}
+
+fn coordinates(point: &Point<f32>) -> (f64, f64) {
+    let [x, y] = point.coordinates();
+
+    ((x.into(): f32) as f64, (y.into(): f32) as f64)
+}
+
+fn squared_errors(transform: &Transform, src: &[(f64, f64)], dst: &[(f64, f64)]) -> Vec<f64> {
+    src.iter().zip(dst).map(|(&(x, y), &(x_d, y_d))| {
+        let (x_t, y_t) = transform.apply(x, y);
+        let error = (x_t - x_d).powi(2) + (y_t - y_d).powi(2);
+
+        // points mapped to infinity are outliers
+        if error.is_finite() { error } else { f64::INFINITY }
+    }).collect()
+}
+
+fn inlier_mask(transform: &Transform, src: &[(f64, f64)], dst: &[(f64, f64)], threshold: f64)
+    -> Vec<bool>
+{
+    squared_errors(transform, src, dst).into_iter().map(|error| error <= threshold).collect()
+}
+
+fn count(inliers: &[bool]) -> usize {
+    inliers.iter().filter(|&&inlier| inlier).count()
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(cmp::Ordering::Equal));
+
+    values[values.len() / 2]
+}
+
+/// The number of hypotheses needed to draw an outlier-free sample with probability `confidence`.
+fn adaptive_iterations(ninliers: usize, npoints: usize, nsamples: usize, confidence: f64) -> usize {
+    let inlier_ratio = ninliers as f64 / npoints as f64;
+    let outlier_free = inlier_ratio.powi(nsamples as i32);
+
+    if outlier_free <= 0.0 {
+        return usize::max_value();
+    } else if outlier_free >= 1.0 {
+        return 1;
+    }
+
+    ((1.0 - confidence).ln() / (1.0 - outlier_free).ln()).ceil() as usize
+}
+
+/// The similarity transform that moves the centroid of `points` to the origin and scales them
+/// so that their mean distance to the origin is √2.
+fn normalization(points: &[(f64, f64)]) -> Transform {
+    let n = points.len() as f64;
+
+    let (cx, cy) = points.iter().fold((0.0, 0.0), |(sx, sy), &(x, y)| (sx + x, sy + y));
+    let (cx, cy) = (cx / n, cy / n);
+
+    let mean_distance = points.iter().fold(0.0, |sum, &(x, y)| sum + (x - cx).hypot(y - cy)) / n;
+
+    let scale = if mean_distance > 0.0 { f64::consts::SQRT_2 / mean_distance } else { 1.0 };
+
+    Transform { matrix: [[scale, 0.0, -scale * cx], [0.0, scale, -scale * cy], [0.0, 0.0, 1.0]] }
+}
+
+/// Least-squares fit of a transform of the `model` family mapping `src` to `dst`.
+///
+/// Returns `None` if the correspondences don't determine a transform.
+fn fit(model: MotionModel, src: &[(f64, f64)], dst: &[(f64, f64)]) -> Option<Transform> {
+
+    if src.len() < model.min_samples() {
+        return None;
+    }
+
+    let t_src = normalization(src);
+    let t_dst = normalization(dst);
+
+    let nparams = model.nparams();
+
+    // normal equations `AᵀA p = Aᵀb`
+    let mut ata = DMat::new_zeros(nparams, nparams);
+    let mut atb = DVec::new_zeros(nparams);
+
+    {
+        let mut accumulate = |row: &[f64], b: f64| {
+            for r in 0..nparams {
+                for c in 0..nparams {
+                    ata[(r, c)] += row[r] * row[c];
+                }
+
+                atb[r] += row[r] * b;
+            }
+        };
+
+        for (&(x, y), &(x_d, y_d)) in src.iter().zip(dst) {
+            let (x, y) = t_src.apply(x, y);
+            let (x_d, y_d) = t_dst.apply(x_d, y_d);
+
+            match model {
+                // x' = a·x − b·y + tx, y' = b·x + a·y + ty
+                MotionModel::Similarity => {
+                    accumulate(&[x, -y, 1.0, 0.0], x_d);
+                    accumulate(&[y, x, 0.0, 1.0], y_d);
+                },
+                // x' = a·x + b·y + c, y' = d·x + e·y + f
+                MotionModel::Affine => {
+                    accumulate(&[x, y, 1.0, 0.0, 0.0, 0.0], x_d);
+                    accumulate(&[0.0, 0.0, 0.0, x, y, 1.0], y_d);
+                },
+                // x' (h₇·x + h₈·y + 1) = h₁·x + h₂·y + h₃ (and likewise for y')
+                MotionModel::Homography => {
+                    accumulate(&[x, y, 1.0, 0.0, 0.0, 0.0, -x * x_d, -y * x_d], x_d);
+                    accumulate(&[0.0, 0.0, 0.0, x, y, 1.0, -x * y_d, -y * y_d], y_d);
+                },
+            }
+        }
+    }
+
+    let p = match ata.inv() {
+        Some(ata_inv) => ata_inv * atb,
+        None => return None,
+    };
+
+    let matrix = match model {
+        MotionModel::Similarity => [[p[0], -p[1], p[2]], [p[1], p[0], p[3]], [0.0, 0.0, 1.0]],
+        MotionModel::Affine => [[p[0], p[1], p[2]], [p[3], p[4], p[5]], [0.0, 0.0, 1.0]],
+        MotionModel::Homography => [[p[0], p[1], p[2]], [p[3], p[4], p[5]], [p[6], p[7], 1.0]],
+    };
+
+    if matrix.iter().any(|row| row.iter().any(|value| !value.is_finite())) {
+        return None;
+    }
+
+    // undo the normalization: T_dst⁻¹ ∘ M ∘ T_src
+    t_dst.inverse().map(|t_dst_inv| t_dst_inv.compose(&Transform { matrix }).compose(&t_src))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{SeedableRng, XorShiftRng};
+    use super::{fit, Estimator, Method, MotionModel, Transform};
+    use utility::fixtures::{grid, point};
+
+    fn assert_close(a: &Transform, b: &Transform) {
+        for (row_a, row_b) in a.matrix.iter().zip(b.matrix.iter()) {
+            for (value_a, value_b) in row_a.iter().zip(row_b.iter()) {
+                assert!((value_a - value_b).abs() < 1e-6, "{:?} != {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn fit_exact_similarity() {
+        let transform = Transform::similarity(1.2, 0.3, 5.0, -3.0);
+
+        let src = [(0.0, 0.0), (10.0, 0.0), (3.0, 7.0)];
+        let dst: Vec<_> = src.iter().map(|&(x, y)| transform.apply(x, y)).collect();
+
+        assert_close(&fit(MotionModel::Similarity, &src, &dst).unwrap(), &transform);
+    }
+
+    #[test]
+    fn fit_exact_homography() {
+        let transform = Transform { matrix: [[1.1, 0.1, 4.0], [-0.05, 0.9, 2.0], [1e-3, 2e-3, 1.0]] };
+
+        let src = [(0.0, 0.0), (100.0, 0.0), (0.0, 80.0), (100.0, 80.0), (40.0, 30.0)];
+        let dst: Vec<_> = src.iter().map(|&(x, y)| transform.apply(x, y)).collect();
+
+        assert_close(&fit(MotionModel::Homography, &src, &dst).unwrap(), &transform);
+    }
+
+    #[test]
+    fn inverse() {
+        let transform = Transform::similarity(2.0, 1.0, 3.0, 4.0);
+
+        assert_close(&transform.compose(&transform.inverse().unwrap()), &Transform::identity());
+    }
+
+    #[test]
+    fn gross_outliers() {
+        let transform = Transform::similarity(1.1, 0.2, 5.0, -3.0);
+
+        let src = grid((0.0, 0.0), (100.0, 100.0), 6);
+
+        // every third correspondence is a gross outlier, the others carry a little noise
+        let dst: Vec<_> = src.iter().enumerate().map(|(n, p)| {
+            let [x, y] = p.coordinates();
+            let (x, y): (f32, f32) = (x.into(), y.into());
+            let (x_t, y_t) = transform.apply(x as f64, y as f64);
+
+            let (dx, dy) = if n % 3 == 0 {
+                (25.0 + n as f64, -30.0)
+            } else {
+                (0.1 * (n as f64 * 1.7).sin(), 0.1 * (n as f64 * 2.3).cos())
+            };
+
+            point((x_t + dx) as f32, (y_t + dy) as f32)
+        }).collect();
+
+        for &method in &[Method::Ransac { threshold: 1.0 }, Method::LMedS] {
+            let estimate = Estimator::new(MotionModel::Similarity, method)
+                .estimate_with_rng(&mut XorShiftRng::from_seed([1, 2, 3, 4]), &src, &dst)
+                .unwrap();
+
+            let expected: Vec<_> = (0..src.len()).map(|n| n % 3 != 0).collect();
+
+            assert_eq!(estimate.inliers, expected, "{:?}", method);
+
+            for &(x, y) in &[(0.0, 0.0), (100.0, 0.0), (0.0, 100.0), (100.0, 100.0)] {
+                let (x_e, y_e) = estimate.transform.apply(x, y);
+                let (x_t, y_t) = transform.apply(x, y);
+
+                assert!((x_e - x_t).abs() < 0.1 && (y_e - y_t).abs() < 0.1, "{:?}", method);
+            }
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use error::Result;
+use error::{Error, ErrorKind, Result};
 use utility::plane_euclidean::{OptPoint, Point};
 
 /// [Optical flow] or optic flow is the pattern of apparent motion of objects, surfaces, and
@@ -11,8 +11,8 @@ pub trait Flow<I> {
     ///
     /// Both `im_i` and `im_j` are expected to have the same dimensions. Every point in `points_i`
     /// is expected to be within the bounds of the images. A point located outside 
-    /// the images' dimensions will not result in an error, but will return `None` for the 
-    /// optional corresponding point at that index.
+    /// the images' dimensions will not result in an error, but will be reported with the status
+    /// `FlowStatus::OutOfBounds` at that index.
     ///
     /// # Arguments
     ///
@@ -22,10 +22,42 @@ pub trait Flow<I> {
     ///
     /// # Returns
     ///
-    /// Returns optional corresponding points/features on image `J`. If a point has not been lost, 
-    /// the optional corresponding point is `Some` and contains a value, otherwise `None`.
+    /// Returns a `FlowPoint` for every point in `points_i`, in the same order. Each one holds 
+    /// the status of the point, its location on image `J` and quality measures of the match.
     #[allow(non_snake_case)]
-    fn flow(&self, i: &I, points_i: &[Point<f32>], j: &I) -> Result<Vec<OptPoint<f32>>>;
+    fn flow(&self, i: &I, points_i: &[Point<f32>], j: &I) -> Result<Vec<FlowPoint>>;
+
+    /// Calculate movement of selected `points` in a pair of images, starting from a predicted 
+    /// location of each point on the second image.
+    ///
+    /// Predictions can come from a motion model (e.g., the median displacement of the previous 
+    /// frame or a Kalman prediction). A good prediction lets an algorithm follow motions larger 
+    /// than it could from scratch.
+    ///
+    /// The default implementation ignores the predictions and calls `flow`.
+    ///
+    /// # Arguments
+    ///
+    /// * `i` - The first image or image pyramid.
+    /// * `points_i` - Tracked points from the first image or the bottom of the image pyramid.
+    /// * `guesses_j` - The predicted location on the second image of every point in `points_i`.
+    /// * `j` - The second image or image pyramid.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `FlowPoint` for every point in `points_i`, in the same order. Returns an error 
+    /// if `points_i` and `guesses_j` don't have the same length.
+    fn flow_with_guess(&self, i: &I, points_i: &[Point<f32>], guesses_j: &[Point<f32>], j: &I) 
+        -> Result<Vec<FlowPoint>> 
+    {
+        if guesses_j.len() != points_i.len() {
+            let message = format!("expected {} guesses, found {}", points_i.len(), guesses_j.len());
+
+            return Err(Error::new(ErrorKind::Motion, message));
+        }
+
+        self.flow(i, points_i, j)
+    }
 }
 
 pub trait FlowMut<I> {    
@@ -39,7 +71,59 @@ pub trait FlowMut<I> {
     ///
     /// # Returns
     ///
-    /// Returns the corresponding points.
+    /// Returns a `FlowPoint` for every point in `points_i`, in the same order.
     #[allow(non_snake_case)]
-    fn flow_mut(&mut self, i: &I, points_i: &[Point<f32>], j: &I) -> Result<Vec<OptPoint<f32>>>;
-}
\ No newline at end of file
+    fn flow_mut(&mut self, i: &I, points_i: &[Point<f32>], j: &I) -> Result<Vec<FlowPoint>>;
+}
+
+/// The outcome of tracking a single point.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum FlowStatus {
+    /// The point was tracked successfully.
+    Tracked,
+    /// The point was outside of the bounds of the first image.
+    OutOfBounds,
+    /// The spatial gradient matrix around the point was singular, or too weak to be inverted 
+    /// reliably (e.g., the point lies on a flat region or on a straight edge).
+    Singular,
+    /// The iterative estimation of the flow diverged.
+    Diverged,
+    /// The point was tracked, but its location on the second image is outside of the bounds of 
+    /// the image.
+    Lost,
+}
+
+/// A point tracked by a `Flow` algorithm.
+#[derive(Clone, Copy, Debug)]
+pub struct FlowPoint {
+    /// The outcome of the tracking.
+    pub status: FlowStatus,
+    /// The location of the point on the second image.
+    ///
+    /// If the point could not be tracked (`OutOfBounds`, `Singular` or `Diverged`), this is the 
+    /// location of the point on the first image.
+    pub point: Point<f32>,
+    /// The mean absolute difference between the patch around the point on the first image and 
+    /// the patch around `point` on the second image, in intensity levels.
+    ///
+    /// `NaN` if the point could not be tracked.
+    pub residual: f32,
+    /// The minimum eigenvalue of the spatial gradient matrix, divided by the number of pixels 
+    /// in the integration window.
+    ///
+    /// Small values indicate a weak feature. `NaN` if the point was out of bounds.
+    pub min_eigenvalue: f32,
+}
+
+impl FlowPoint {
+
+    /// Returns `true` if the point was tracked successfully.
+    pub fn is_tracked(&self) -> bool {
+        self.status == FlowStatus::Tracked
+    }
+
+    /// Returns the location of the point on the second image if it was tracked successfully.
+    pub fn tracked(&self) -> OptPoint<f32> {
+        if self.is_tracked() { Some(self.point) } else { None }
+    }
+}
@@ -13,6 +13,10 @@ pub struct Error {
 /// A list specifying general error categories.
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub enum ErrorKind {
+    /// An error occurred during detection.
+    Detection,
+    /// An error occurred while reading or writing data.
+    Io,
     /// An error occurred while analyzing information related to motion.
     Motion,
     /// An error occurred during tracking.
@@ -27,9 +31,11 @@ pub enum ErrorKind {
 
 impl ErrorKind {
     fn as_str(&self) -> &'static str {
-        use self::ErrorKind::{Motion, Tracking};
+        use self::ErrorKind::{Detection, Io, Motion, Tracking};
 
         match *self {
+            Detection => "an error occurred during detection",
+            Io => "an error occurred while reading or writing data",
             Motion => "an error occurred while analyzing information related to motion",
             Tracking => "an error occurred during tracking",
             _ => unreachable!(),
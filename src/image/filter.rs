@@ -0,0 +1,137 @@
+use std::cmp;
+use super::{Gray32FImage, Intensity, LumaImage};
+
+/// Blurs `image` with a Gaussian of standard deviation `sigma`.
+///
+/// The kernel is separable and truncated at `3σ`; the image is extended by replicating its
+/// border. If `sigma` isn't positive, the image is only converted.
+///
+/// # Returns
+///
+/// The blurred image, in intensity levels (see `Intensity`).
+pub fn gaussian_blur<T>(image: &LumaImage<T>, sigma: f32) -> Gray32FImage where T: Intensity {
+
+    let (width, height) = image.dimensions();
+    let pixels: Vec<f32> = image.iter().map(|&p| p.to_intensity()).collect();
+
+    if sigma <= 0.0 || width == 0 || height == 0 {
+        return Gray32FImage::from_raw(width, height, pixels).unwrap();
+    }
+
+    let radius = (3.0 * sigma).ceil() as i64;
+
+    let kernel: Vec<f32> = {
+        let kernel: Vec<f32> = (-radius..radius + 1).map(|k| (-(k * k) as f32 / (2.0 * sigma * sigma)).exp()).collect();
+        let sum: f32 = kernel.iter().sum();
+
+        kernel.iter().map(|k| k / sum).collect()
+    };
+
+    // convolves the `len` samples `start + n × step` of `source`
+    let convolve = |source: &[f32], start: usize, step: usize, len: usize, target: &mut [f32]| {
+        for n in 0..len {
+            target[start + n * step] = kernel.iter().enumerate().fold(0.0, |sum, (k, weight)| {
+                let m = cmp::max(0, cmp::min(len as i64 - 1, n as i64 + k as i64 - radius)) as usize;
+
+                sum + weight * source[start + m * step]
+            });
+        }
+    };
+
+    let (width, height) = (width as usize, height as usize);
+    let mut horizontal = vec![0.0; width * height];
+    let mut blurred = vec![0.0; width * height];
+
+    for y in 0..height {
+        convolve(&pixels, y * width, 1, width, &mut horizontal);
+    }
+
+    for x in 0..width {
+        convolve(&horizontal, x, width, height, &mut blurred);
+    }
+
+    Gray32FImage::from_raw(width as u32, height as u32, blurred).unwrap()
+}
+
+/// Computes the horizontal and vertical derivatives of `image` with the 3×3 Sobel operator.
+///
+/// The image is extended by replicating its border.
+///
+/// # Returns
+///
+/// The derivatives along `x` and `y`, in intensity levels per pixel (the operator is scaled by
+/// 1/8).
+pub fn sobel<T>(image: &LumaImage<T>) -> (Gray32FImage, Gray32FImage) where T: Intensity {
+
+    let (width, height) = image.dimensions();
+
+    if width == 0 || height == 0 {
+        return (Gray32FImage::new(width, height), Gray32FImage::new(width, height));
+    }
+
+    let pixels: &[T] = &**image;
+
+    let px = |x: i64, y: i64| {
+        let x = cmp::max(0, cmp::min(width as i64 - 1, x));
+        let y = cmp::max(0, cmp::min(height as i64 - 1, y));
+
+        pixels[(y * width as i64 + x) as usize].to_intensity()
+    };
+
+    let mut gx = Vec::with_capacity((width * height) as usize);
+    let mut gy = Vec::with_capacity((width * height) as usize);
+
+    for y in 0..height as i64 {
+        for x in 0..width as i64 {
+            gx.push((px(x + 1, y - 1) + 2.0 * px(x + 1, y) + px(x + 1, y + 1)
+                   - px(x - 1, y - 1) - 2.0 * px(x - 1, y) - px(x - 1, y + 1)) / 8.0);
+            gy.push((px(x - 1, y + 1) + 2.0 * px(x, y + 1) + px(x + 1, y + 1)
+                   - px(x - 1, y - 1) - 2.0 * px(x, y - 1) - px(x + 1, y - 1)) / 8.0);
+        }
+    }
+
+    (Gray32FImage::from_raw(width, height, gx).unwrap(), Gray32FImage::from_raw(width, height, gy).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use piston_image::{GrayImage, Luma};
+    use super::{gaussian_blur, sobel};
+    use utility::fixtures;
+
+    #[test]
+    fn zero_sigma_is_the_identity() {
+        let image = fixtures::texture(32, 24, 0.0, 0.0);
+        let blurred = gaussian_blur(&image, 0.0);
+
+        assert_eq!(blurred.dimensions(), (32, 24));
+
+        for (p, q) in image.pixels().zip(blurred.pixels()) {
+            assert_eq!(p.data[0] as f32, q.data[0]);
+        }
+    }
+
+    #[test]
+    fn blur_preserves_a_constant_image() {
+        let blurred = gaussian_blur(&GrayImage::from_pixel(17, 13, Luma { data: [90] }), 1.5);
+
+        assert!(blurred.pixels().all(|p| (p.data[0] - 90.0).abs() < 1e-3));
+    }
+
+    #[test]
+    fn sobel_of_a_ramp() {
+        // slopes of 3 along x and 2 along y
+        let (gx, gy) = sobel(&GrayImage::from_fn(8, 6, |x, y| Luma { data: [(3 * x + 2 * y) as u8] }));
+
+        for y in 0..6 {
+            for x in 0..8 {
+                // the replicated border halves the derivative across it
+                let expected_x = if x == 0 || x == 7 { 1.5 } else { 3.0 };
+                let expected_y = if y == 0 || y == 5 { 1.0 } else { 2.0 };
+
+                assert_eq!(gx.get_pixel(x, y).data[0], expected_x);
+                assert_eq!(gy.get_pixel(x, y).data[0], expected_y);
+            }
+        }
+    }
+}
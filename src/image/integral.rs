@@ -1,13 +1,229 @@
-use piston_image::{Image, Luma};
-use piston_imageproc::definitions::VecBuffer;
+use std::cmp;
+use super::{Intensity, LumaImage};
 
-pub type IntegralImage(VecBuffer<Luma<u32>>);
+/// The integral image (summed-area table) of a grayscale image
+///
+/// The sum and the sum of squares of the intensities of any rectangle are computed in constant
+/// time, from 4 lookups each. The tables are `(width + 1) × (height + 1)`, with a first row and
+/// column of zeros, and accumulate in `f64` so that large images don't lose precision.
+///
+/// An image constructed `with_tilted` also sums the rectangles rotated by 45° (the tilted Haar
+/// features of Lienhart and Maydt).
+///
+/// ## References
+///
+/// * Crow, F. C. (1984). *Summed-area tables for texture mapping*. ACM SIGGRAPH Computer
+///   Graphics, 18(3), 207-212.
+/// * Lienhart, R., & Maydt, J. (2002). *An extended set of Haar-like features for rapid object
+///   detection*. In International Conference on Image Processing (Vol. 1, pp. 900-903). IEEE.
+#[derive(Clone, Debug)]
+pub struct IntegralImage {
+    width: u32,
+    height: u32,
+    sums: Vec<f64>,
+    squared_sums: Vec<f64>,
+    tilted_sums: Option<Vec<f64>>,
+}
 
 impl IntegralImage {
 
-    pub fn new()
+    /// Computes the integral image of `image`.
+    pub fn new<T>(image: &LumaImage<T>) -> IntegralImage where T: Intensity {
+
+        let (width, height) = image.dimensions();
+        let stride = width as usize + 1;
+
+        let mut sums = vec![0.0; stride * (height as usize + 1)];
+        let mut squared_sums = vec![0.0; stride * (height as usize + 1)];
+
+        for (y, row) in image.chunks(cmp::max(1, width as usize)).enumerate() {
+            let (mut sum, mut squared_sum) = (0.0, 0.0);
+
+            for (x, &p) in row.iter().enumerate() {
+                let p = p.to_intensity() as f64;
+
+                sum += p;
+                squared_sum += p * p;
+
+                let (above, index) = (y * stride + x + 1, (y + 1) * stride + x + 1);
+
+                sums[index] = sums[above] + sum;
+                squared_sums[index] = squared_sums[above] + squared_sum;
+            }
+        }
+
+        IntegralImage { width, height, sums, squared_sums, tilted_sums: None }
+    }
+
+    /// Computes the integral image of `image`, with the table of the tilted rectangles.
+    pub fn with_tilted<T>(image: &LumaImage<T>) -> IntegralImage where T: Intensity {
+
+        let mut integral = IntegralImage::new(image);
+        let (width, height) = (integral.width as i64, integral.height as i64);
+
+        // `T(x + 1, y + 1)` is the sum of the triangle of the pixels (`u`, `v`) such that
+        // `v ≤ y` and `|u - x| ≤ y - v`: the difference of the sums of the row prefixes along the
+        // two diagonals through (`x`, `y`), accumulated row by row.
+        let stride = width as usize + 1;
+        let mut table = vec![0.0; stride * (height as usize + 1)];
+
+        let mut prefix = vec![0.0; width as usize];
+        let mut right = vec![0.0; (width + height) as usize];
+        let mut left = vec![0.0; (width + height + 1) as usize];
+
+        for (y, row) in image.chunks(cmp::max(1, width as usize)).enumerate() {
+            let y = y as i64;
+            let mut sum = 0.0;
+
+            for (x, &p) in row.iter().enumerate() {
+                sum += p.to_intensity() as f64;
+                prefix[x] = sum;
+            }
+
+            let at = |x: i64| if x < 0 { 0.0 } else { prefix[cmp::min(x, width - 1) as usize] };
+
+            // `right[d + 1]` sums the prefixes ending at (`d - v`, `v`)
+            for d in -1..width + height - 1 {
+                right[(d + 1) as usize] += at(d - y);
+            }
+
+            // `left[e + height + 1]` sums the prefixes ending at (`e + v`, `v`)
+            for e in -height - 1..width {
+                left[(e + height + 1) as usize] += at(e + y);
+            }
+
+            for x in -1..width {
+                table[(y + 1) as usize * stride + (x + 1) as usize] =
+                    right[(x + y + 1) as usize] - left[(x - y - 1 + height + 1) as usize];
+            }
+        }
+
+        integral.tilted_sums = Some(table);
+        integral
+    }
+
+    /// Returns the dimensions of the image.
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Returns the sum of the intensities of the `width × height` rectangle at (`x`, `y`).
+    ///
+    /// The rectangle must be inside the image.
+    pub fn sum(&self, x: u32, y: u32, width: u32, height: u32) -> f64 {
+        self.rectangle(&self.sums, x, y, width, height)
+    }
+
+    /// Returns the sum of the squared intensities of the `width × height` rectangle at (`x`,
+    /// `y`).
+    ///
+    /// The rectangle must be inside the image.
+    pub fn squared_sum(&self, x: u32, y: u32, width: u32, height: u32) -> f64 {
+        self.rectangle(&self.squared_sums, x, y, width, height)
+    }
+
+    /// Returns the mean and the variance of the intensities of the `width × height` rectangle
+    /// at (`x`, `y`), or `(0, 0)` if the rectangle is empty.
+    ///
+    /// The rectangle must be inside the image.
+    pub fn mean_variance(&self, x: u32, y: u32, width: u32, height: u32) -> (f64, f64) {
+
+        let n = width as f64 * height as f64;
+
+        if n == 0.0 {
+            return (0.0, 0.0);
+        }
+
+        let mean = self.sum(x, y, width, height) / n;
+        let variance = self.squared_sum(x, y, width, height) / n - mean * mean;
+
+        (mean, variance.max(0.0))
+    }
+
+    /// Returns the sum of the intensities of the rectangle rotated by 45° whose top corner is
+    /// (`x`, `y`), with a side of `width` pixels towards the bottom-right and a side of `height`
+    /// pixels towards the bottom-left (the convention of OpenCV).
+    ///
+    /// The rectangle must be inside the image (`x ≥ height`, `x + width ≤ W` and
+    /// `y + width + height ≤ H`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the image wasn't constructed `with_tilted`.
+    pub fn tilted_sum(&self, x: u32, y: u32, width: u32, height: u32) -> f64 {
+
+        debug_assert!(x >= height && x + width <= self.width && y + width + height <= self.height);
+
+        let table = self.tilted_sums.as_ref().expect("the integral image has no tilted table");
+
+        let stride = self.width as usize + 1;
+        let at = |x: u32, y: u32| table[y as usize * stride + x as usize];
+
+        at(x, y) - at(x - height, y + height) - at(x + width, y + width) + at(x + width - height, y + width + height)
+    }
+
+    fn rectangle(&self, table: &[f64], x: u32, y: u32, width: u32, height: u32) -> f64 {
+
+        debug_assert!(x + width <= self.width && y + height <= self.height);
+
+        let stride = self.width as usize + 1;
+        let at = |x: u32, y: u32| table[y as usize * stride + x as usize];
+
+        at(x + width, y + height) - at(x, y + height) - at(x + width, y) + at(x, y)
+    }
 }
 
-impl Image for IntegralImage {
+#[cfg(test)]
+mod tests {
+    use piston_image::{GrayImage, Luma};
+    use super::IntegralImage;
 
-}
\ No newline at end of file
+    #[test]
+    fn sums_of_rectangles() {
+        let image = GrayImage::from_fn(7, 5, |x, y| Luma { data: [(x * 3 + y * 5) as u8] });
+        let integral = IntegralImage::new(&image);
+
+        let (mut sum, mut squared_sum) = (0.0, 0.0);
+
+        for y in 1..4 {
+            for x in 2..6 {
+                let p = image.get_pixel(x, y).data[0] as f64;
+
+                sum += p;
+                squared_sum += p * p;
+            }
+        }
+
+        assert!((integral.sum(2, 1, 4, 3) - sum).abs() < 1e-9);
+        assert!((integral.squared_sum(2, 1, 4, 3) - squared_sum).abs() < 1e-9);
+        assert!(integral.sum(0, 0, 0, 5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn empty_image() {
+        let integral = IntegralImage::new(&GrayImage::new(0, 4));
+
+        assert_eq!(integral.dimensions(), (0, 4));
+        assert!(integral.sum(0, 0, 0, 4).abs() < 1e-9);
+        assert_eq!(integral.mean_variance(0, 1, 0, 2), (0.0, 0.0));
+    }
+
+    #[test]
+    fn sums_of_tilted_rectangles() {
+        let image = GrayImage::from_fn(10, 10, |x, y| Luma { data: [(x * 7 + y * 11 + x * y) as u8] });
+        let integral = IntegralImage::with_tilted(&image);
+
+        for &(x, y, w, h) in &[(5, 1, 3, 2), (2, 0, 8, 2), (9, 3, 1, 6)] {
+            // the pixels between the diagonals through the corners of the rectangle
+            let (u0, v0) = (x as i64 + y as i64 - 2, y as i64 - x as i64);
+
+            let sum: f64 = image.enumerate_pixels().filter(|&(px, py, _)| {
+                let (u, v) = (px as i64 + py as i64, py as i64 - px as i64);
+
+                u > u0 && u <= u0 + 2 * w as i64 && v > v0 && v <= v0 + 2 * h as i64
+            }).map(|(_, _, p)| p.data[0] as f64).sum();
+
+            assert!((integral.tilted_sum(x, y, w, h) - sum).abs() < 1e-9);
+        }
+    }
+}
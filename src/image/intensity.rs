@@ -0,0 +1,58 @@
+use piston_image::{ImageBuffer, Luma, Primitive};
+
+/// A single-channel image with channels of type `T`.
+pub type LumaImage<T> = ImageBuffer<Luma<T>, Vec<T>>;
+
+/// A 16-bit grayscale image (e.g., a thermal or a depth frame).
+pub type Gray16Image = LumaImage<u16>;
+
+/// A floating point grayscale image.
+pub type Gray32FImage = LumaImage<f32>;
+
+/// The channel type of a grayscale pixel (`Luma<T>`), read as an `f32` intensity.
+///
+/// Algorithms generic over `Intensity` work on the raw range of the channel type (0-255 for 
+/// `u8`, 0-65535 for `u16`, unchanged for `f32`): thresholds expressed in intensity levels 
+/// should be scaled accordingly.
+pub trait Intensity: Primitive + Send + Sync + 'static {
+
+    /// Returns the value of the channel as an intensity.
+    fn to_intensity(self) -> f32;
+
+    /// Converts an intensity to a channel value, rounding and saturating if the channel type 
+    /// is an integer.
+    fn from_intensity(intensity: f32) -> Self;
+}
+
+impl Intensity for u8 {
+
+    fn to_intensity(self) -> f32 {
+        self as f32
+    }
+
+    fn from_intensity(intensity: f32) -> u8 {
+        intensity.round().max(0.0).min(255.0) as u8
+    }
+}
+
+impl Intensity for u16 {
+
+    fn to_intensity(self) -> f32 {
+        self as f32
+    }
+
+    fn from_intensity(intensity: f32) -> u16 {
+        intensity.round().max(0.0).min(65535.0) as u16
+    }
+}
+
+impl Intensity for f32 {
+
+    fn to_intensity(self) -> f32 {
+        self
+    }
+
+    fn from_intensity(intensity: f32) -> f32 {
+        intensity
+    }
+}
@@ -1,4 +1,9 @@
-pub use self::pyramid::{GrayPyramid, Pyramid};
+pub use self::filter::{gaussian_blur, sobel};
+pub use self::integral::IntegralImage;
+pub use self::intensity::{Gray16Image, Gray32FImage, Intensity, LumaImage};
+pub use self::pyramid::{GrayPyramid, LumaPyramid, Pyramid};
 
-//mod integral;
-mod pyramid;
\ No newline at end of file
+mod filter;
+mod integral;
+mod intensity;
+mod pyramid;
@@ -1,8 +1,13 @@
 use piston_image::GrayImage;
+use std::cmp;
 use std::ops::Deref;
+use super::{Intensity, LumaImage};
 
 pub type GrayPyramid = Pyramid<GrayImage>;
 
+/// A pyramid of single-channel images with channels of type `T` (see `Intensity`).
+pub type LumaPyramid<T> = Pyramid<LumaImage<T>>;
+
 /// A pyramid representation of an image of type `I`.
 #[derive(Debug)]
 pub struct Pyramid<I> {
@@ -46,6 +51,108 @@ impl<I> Pyramid<I> {
     }
 }
 
+impl<T> LumaPyramid<T> where T: Intensity {
+
+    /// Builds the Gaussian pyramid of `image`, as described in Bouguet's paper.
+    ///
+    /// Each level is computed from the previous one by anti-aliasing (low-pass filtering) it with 
+    /// the kernel `[1/16 1/4 3/8 1/4 1/16] × [1/16 1/4 3/8 1/4 1/16]ᵀ` and keeping every other 
+    /// pixel. A level of size `w × h` has a successor of size `⌈w/2⌉ × ⌈h/2⌉`.
+    ///
+    /// # Arguments
+    ///
+    /// * `image` - The highest resolution image (the "zeroᵗʰ" level image).
+    /// * `height` - The height of the pyramid. If `height` is 0, 1 is chosen.
+    pub fn build(image: &LumaImage<T>, height: usize) -> LumaPyramid<T> {
+
+        let images = (0..cmp::max(1, height)).map(|_| LumaImage::new(0, 0)).collect();
+
+        let mut pyramid = Pyramid { images };
+
+        pyramid.rebuild_from(image);
+
+        pyramid
+    }
+
+    /// Rebuilds the pyramid from `image`, keeping the same height.
+    ///
+    /// The existing level buffers are reused: no memory is allocated unless the dimensions 
+    /// of `image` differ from the dimensions of the bottom level. Rebuilding a pyramid for every 
+    /// frame of a video is therefore allocation-free once the first frame has been processed.
+    pub fn rebuild_from(&mut self, image: &LumaImage<T>) {
+
+        if self.images[0].dimensions() == image.dimensions() {
+            self.images[0].copy_from_slice(image);
+        } else {
+            self.images[0] = image.clone();
+        }
+
+        for n in 1..self.images.len() {
+            let (prev_w, prev_h) = self.images[n - 1].dimensions();
+            let (width, height) = ((prev_w + 1) / 2, (prev_h + 1) / 2);
+
+            if self.images[n].dimensions() != (width, height) {
+                self.images[n] = LumaImage::new(width, height);
+            }
+
+            let (prev, next) = self.images.split_at_mut(n);
+
+            downsample(&prev[n - 1], &mut next[0]);
+        }
+    }
+}
+
+/// Blurs and decimates `src` into `dst` in a single pass.
+///
+/// Anti-aliasing with the 3×3 kernel `[1/4 1/2 1/4] × [1/4 1/2 1/4]ᵀ` and then averaging the 
+/// neighbourhood of every other pixel with the same kernel amounts to a 5×5 binomial filter, 
+/// `[1 4 6 4 1] × [1 4 6 4 1]ᵀ / 256`, evaluated only at the retained pixels. The image is 
+/// extended by replicating its border.
+fn downsample<T>(src: &LumaImage<T>, dst: &mut LumaImage<T>) where T: Intensity {
+
+    const WEIGHTS: [f32; 5] = [1.0, 4.0, 6.0, 4.0, 1.0];
+
+    let (src_w, src_h) = src.dimensions();
+    let (src_w, src_h) = (src_w as isize, src_h as isize);
+    let (dst_w, _) = dst.dimensions();
+
+    if dst_w == 0 {
+        return;
+    }
+
+    let src: &[T] = src;
+
+    let clamp = |value: isize, len: isize| cmp::min(cmp::max(value, 0), len - 1) as usize;
+
+    for (y, dst_row) in dst.chunks_mut(dst_w as usize).enumerate() {
+
+        let row = |offset: isize| {
+            let start = clamp(2 * y as isize + offset, src_h) * src_w as usize;
+
+            &src[start..start + src_w as usize]
+        };
+
+        let rows = [row(-2), row(-1), row(0), row(1), row(2)];
+
+        // vertical pass at column `c`
+        let column = |c: usize| {
+            rows.iter().zip(WEIGHTS.iter()).fold(0.0, |sum, (row, weight)| {
+                sum + weight * row[c].to_intensity()
+            })
+        };
+
+        for (x, pixel) in dst_row.iter_mut().enumerate() {
+            let c = 2 * x as isize;
+
+            let sum = WEIGHTS.iter().enumerate().fold(0.0, |sum, (k, weight)| {
+                sum + weight * column(clamp(c + k as isize - 2, src_w))
+            });
+
+            *pixel = T::from_intensity(sum / 256.0);
+        }
+    }
+}
+
 impl<I> Deref for Pyramid<I> {
 
     type Target = Vec<I>;
@@ -15,6 +15,10 @@ extern crate log;
 // #[cfg(feature = "pilot")]
 // extern crate ..
 
+#[cfg(feature = "parallel")]
+extern crate rayon;
+
+extern crate byteorder;
 extern crate euclidean;
 extern crate float;
 extern crate image as piston_image;
@@ -22,6 +26,7 @@ extern crate imageproc as piston_imageproc;
 extern crate nalgebra;
 extern crate num;
 extern crate rand;
+extern crate xml;
 
 pub mod core;
 pub mod image;
@@ -0,0 +1,566 @@
+use error::{Error, ErrorKind, Result};
+use euclidean::Region2D as Region;
+use image::IntegralImage;
+use piston_image::GrayImage;
+use std::{cmp, f64};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use super::{group_rectangles, window};
+use xml::reader::{EventReader, XmlEvent};
+
+/// A rectangle of a Haar-like feature: `(x, y, width, height, weight)`, in pixels of the window.
+type Rectangle = (u32, u32, u32, u32, f64);
+
+#[derive(Clone, Debug)]
+struct Feature {
+    rectangles: Vec<Rectangle>,
+    tilted: bool,
+}
+
+/// A node of a decision tree. A child that isn't positive is the leaf `-child`.
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    left: i32,
+    right: i32,
+    feature: usize,
+    threshold: f64,
+}
+
+#[derive(Clone, Debug)]
+struct Tree {
+    nodes: Vec<Node>,
+    leaves: Vec<f64>,
+}
+
+#[derive(Clone, Debug)]
+struct Stage {
+    threshold: f64,
+    trees: Vec<Tree>,
+}
+
+/// The features of a cascade scaled to a size of the window.
+struct Scaled {
+    features: Vec<Feature>,
+    /// The rectangle over which the window is normalized.
+    normalization: (u32, u32, u32, u32),
+    /// The size of the window covered by the features.
+    extent: (u32, u32),
+}
+
+/// A Viola-Jones cascade of boosted Haar-like features
+///
+/// A window is a detection if it passes every stage of the cascade: the sum of the leaves
+/// reached in the decision trees of the stage is at least the threshold of the stage. The
+/// features are differences of (upright or tilted) rectangle sums, normalized by the standard
+/// deviation of the window, and evaluated in constant time on the `IntegralImage`.
+///
+/// The cascades are read from the XML files of OpenCV (e.g., `haarcascade_frontalface_default.xml`),
+/// in the format of `opencv_traincascade` (`opencv-cascade-classifier`, `BOOST` stages and
+/// `HAAR` features).
+///
+/// `detect` slides the window over the image with `detection::window`, scaling the features
+/// rather than the image, and groups the hits with `group_rectangles`.
+///
+/// ## References
+///
+/// * Viola, P., & Jones, M. (2001). *Rapid object detection using a boosted cascade of simple
+///   features*. In Proceedings of the IEEE Computer Society Conference on Computer Vision and
+///   Pattern Recognition (Vol. 1, pp. I-511). IEEE.
+/// * Lienhart, R., & Maydt, J. (2002). *An extended set of Haar-like features for rapid object
+///   detection*. In International Conference on Image Processing (Vol. 1, pp. 900-903). IEEE.
+#[derive(Clone, Debug)]
+pub struct HaarCascade {
+    width: u32,
+    height: u32,
+    stages: Vec<Stage>,
+    features: Vec<Feature>,
+
+    scale_factor: f64,
+    step: u32,
+    min_neighbours: usize,
+    min_size: (u32, u32),
+}
+
+impl HaarCascade {
+
+    /// Reads a cascade from an OpenCV XML file.
+    ///
+    /// The detection parameters are those of OpenCV's `detectMultiScale`: a scale factor of
+    /// 1.1, 3 minimum neighbours, and the size of the cascade as the minimum size. The window
+    /// moves by 2 pixels.
+    pub fn open<P>(path: P) -> Result<HaarCascade> where P: AsRef<Path> {
+
+        let file = File::open(path).map_err(|e| Error::new(ErrorKind::Io, e))?;
+
+        HaarCascade::from_reader(BufReader::new(file))
+    }
+
+    /// Reads a cascade from an OpenCV XML document (see `open`).
+    pub fn from_reader<R>(reader: R) -> Result<HaarCascade> where R: Read {
+
+        let document = Element::parse(reader)?;
+
+        let cascade = document.children.first()
+            .and_then(|storage| storage.children.first())
+            .ok_or_else(|| invalid("the document is empty"))?;
+
+        if cascade.child("stageType").map(|e| e.text.trim()) != Some("BOOST")
+            || cascade.child("featureType").map(|e| e.text.trim()) != Some("HAAR")
+        {
+            return Err(invalid("only the boosted Haar cascades of `opencv_traincascade` are supported"));
+        }
+
+        let width = cascade.number::<u32>("width")?;
+        let height = cascade.number::<u32>("height")?;
+
+        if width < 3 || height < 3 {
+            return Err(invalid("the window of the cascade is too small"));
+        }
+
+        let mut features = Vec::new();
+
+        for feature in &cascade.required("features")?.children {
+            let mut rectangles = Vec::new();
+
+            for rectangle in &feature.required("rects")?.children {
+                let values: Vec<f64> = numbers(&rectangle.text)?;
+
+                if values.len() != 5 || values[..4].iter().any(|&v| v < 0.0) {
+                    return Err(invalid("a rectangle isn't `x y width height weight`"));
+                }
+
+                rectangles.push((values[0] as u32, values[1] as u32, values[2] as u32, values[3] as u32, values[4]));
+            }
+
+            let tilted = match feature.child("tilted") {
+                Some(tilted) => tilted.text.trim() != "0",
+                None => false,
+            };
+
+            let inside = rectangles.iter().all(|&(x, y, w, h, _)| {
+                if tilted {
+                    x >= h && x + w <= width && y + w + h <= height
+                } else {
+                    x + w <= width && y + h <= height
+                }
+            });
+
+            if rectangles.is_empty() || !inside {
+                return Err(invalid("a feature isn't inside the window"));
+            }
+
+            features.push(Feature { rectangles, tilted });
+        }
+
+        let mut stages = Vec::new();
+
+        for stage in &cascade.required("stages")?.children {
+            let threshold = stage.number::<f64>("stageThreshold")?;
+            let mut trees = Vec::new();
+
+            for classifier in &stage.required("weakClassifiers")?.children {
+                let values: Vec<f64> = numbers(&classifier.required("internalNodes")?.text)?;
+                let leaves: Vec<f64> = numbers(&classifier.required("leafValues")?.text)?;
+
+                if values.is_empty() || values.len() % 4 != 0 {
+                    return Err(invalid("the internal nodes aren't `left right feature threshold`"));
+                }
+
+                let nodes: Vec<Node> = values.chunks(4).map(|node| {
+                    Node { left: node[0] as i32, right: node[1] as i32, feature: node[2] as usize, threshold: node[3] }
+                }).collect();
+
+                // the children follow their parents, so that every path ends on a leaf
+                let valid = nodes.iter().enumerate().all(|(n, node)| {
+                    let child = |c: i32| if c > 0 { c as usize > n && (c as usize) < nodes.len() } else { ((-c) as usize) < leaves.len() };
+
+                    node.feature < features.len() && child(node.left) && child(node.right)
+                });
+
+                if !valid {
+                    return Err(invalid("a weak classifier refers to a missing node, leaf or feature"));
+                }
+
+                trees.push(Tree { nodes, leaves });
+            }
+
+            stages.push(Stage { threshold, trees });
+        }
+
+        if stages.is_empty() {
+            return Err(invalid("the cascade has no stage"));
+        }
+
+        Ok(HaarCascade {
+            width,
+            height,
+            stages,
+            features,
+            scale_factor: 1.1,
+            step: 2,
+            min_neighbours: 3,
+            min_size: (width, height),
+        })
+    }
+
+    /// Returns the size of the window of the cascade.
+    pub fn window_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Sets the scale factor between two sizes of the window. If `scale_factor` isn't greater
+    /// than 1, only the smallest window is used.
+    pub fn scale_factor(mut self, scale_factor: f64) -> Self {
+        self.scale_factor = scale_factor;
+        self
+    }
+
+    /// Sets the step of the smallest window, in pixels. The step grows with the window (it is
+    /// `step × scale`, rounded). If `step` is 0, 1 is chosen.
+    pub fn step(mut self, step: u32) -> Self {
+        self.step = cmp::max(1, step);
+        self
+    }
+
+    /// Sets the minimum number of neighbours of a detection (see `group_rectangles`).
+    pub fn min_neighbours(mut self, min_neighbours: usize) -> Self {
+        self.min_neighbours = min_neighbours;
+        self
+    }
+
+    /// Sets the minimum size of the objects. The smallest window keeps the aspect ratio of the
+    /// cascade, and is never smaller than the cascade.
+    pub fn min_size(mut self, min_size: (u32, u32)) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    /// Detects the objects of `image`.
+    ///
+    /// # Returns
+    ///
+    /// The grouped detections, with the best score of their group (see `hits`), from the
+    /// largest group. As the windows of `detection::window` never reach the right and bottom
+    /// edges, a detection never covers the last column or row of `image`.
+    pub fn detect(&self, image: &GrayImage) -> Vec<(Region, f32)> {
+
+        group_rectangles(&self.hits(image), self.min_neighbours, 0.2).into_iter()
+            .map(|(region, score, _)| (region, score))
+            .collect()
+    }
+
+    /// Returns every window of `image` that passes the cascade, before grouping.
+    ///
+    /// The score of a window is the margin of the sum of its last stage over the threshold of
+    /// the stage.
+    pub fn hits(&self, image: &GrayImage) -> Vec<(Region, f32)> {
+
+        let integral = if self.features.iter().any(|feature| feature.tilted) {
+            IntegralImage::with_tilted(image)
+        } else {
+            IntegralImage::new(image)
+        };
+
+        let (image_width, image_height) = image.dimensions();
+        let (width, height) = (self.width as f64, self.height as f64);
+
+        let mut scale = (self.min_size.0 as f64 / width).max(self.min_size.1 as f64 / height).max(1.0);
+        let mut hits = Vec::new();
+
+        // the windows of each scale, until they don't fit in the image (see `window`)
+        while ((width * scale) as u32) < image_width && ((height * scale) as u32) < image_height {
+
+            let scaled = self.scale(scale);
+
+            // the step grows with the window, as in OpenCV
+            let step = cmp::max(1, (self.step as f64 * scale).round() as u32);
+
+            for region in window(image.dimensions(), (width * scale, height * scale), (step, step), 1.0) {
+                let (x, y, _, _) = region.bounds();
+                let (x, y) = (x as u32, y as u32);
+
+                if x + scaled.extent.0 > integral.dimensions().0 || y + scaled.extent.1 > integral.dimensions().1 {
+                    continue;
+                }
+
+                if let Some(score) = self.evaluate(&integral, &scaled, x, y) {
+                    hits.push((region, score as f32));
+                }
+            }
+
+            if self.scale_factor <= 1.0 {
+                break;
+            }
+
+            scale *= self.scale_factor;
+        }
+
+        hits
+    }
+
+    /// Scales the features, compensating the weights for the rounding of the rectangles.
+    fn scale(&self, scale: f64) -> Scaled {
+
+        let round = |v: u32| (v as f64 * scale).round() as u32;
+
+        let normalization = (round(1), round(1), round(self.width - 2), round(self.height - 2));
+        let area = (normalization.2 * normalization.3) as f64;
+
+        let mut extent = (normalization.0 + normalization.2, normalization.1 + normalization.3);
+
+        let features = self.features.iter().map(|feature| {
+            let ratio = (if feature.tilted { 0.5 } else { 1.0 }) / area;
+
+            let mut rectangles: Vec<Rectangle> = feature.rectangles.iter().map(|&(x, y, w, h, weight)| {
+                (round(x), round(y), round(w), round(h), weight * ratio)
+            }).collect();
+
+            for &(x, y, w, h, _) in &rectangles {
+                let (right, bottom) = if feature.tilted { (x + w, y + w + h) } else { (x + w, y + h) };
+
+                extent = (cmp::max(extent.0, right), cmp::max(extent.1, bottom));
+            }
+
+            // the first rectangle balances the others, as it did before the rounding
+            let (_, _, w0, h0, _) = rectangles[0];
+
+            if rectangles.len() > 1 && w0 * h0 > 0 {
+                let others: f64 = rectangles[1..].iter().map(|&(_, _, w, h, weight)| weight * (w * h) as f64).sum();
+
+                rectangles[0].4 = -others / (w0 * h0) as f64;
+            }
+
+            Feature { rectangles, tilted: feature.tilted }
+        }).collect();
+
+        Scaled { features, normalization, extent }
+    }
+
+    /// Returns the score of the window at (`x`, `y`), or `None` if a stage rejects it.
+    fn evaluate(&self, integral: &IntegralImage, scaled: &Scaled, x: u32, y: u32) -> Option<f64> {
+
+        let (nx, ny, nw, nh) = scaled.normalization;
+        let (_, variance) = integral.mean_variance(x + nx, y + ny, nw, nh);
+        let deviation = if variance > 0.0 { variance.sqrt() } else { 1.0 };
+
+        let value = |feature: &Feature| {
+            feature.rectangles.iter().fold(0.0, |value, &(rx, ry, w, h, weight)| {
+                let sum = if feature.tilted {
+                    integral.tilted_sum(x + rx, y + ry, w, h)
+                } else {
+                    integral.sum(x + rx, y + ry, w, h)
+                };
+
+                value + weight * sum
+            })
+        };
+
+        let mut score = f64::NAN;
+
+        for stage in &self.stages {
+            let mut sum = 0.0;
+
+            for tree in &stage.trees {
+                let mut index = 0;
+
+                loop {
+                    let node = tree.nodes[index];
+
+                    let next = if value(&scaled.features[node.feature]) < node.threshold * deviation {
+                        node.left
+                    } else {
+                        node.right
+                    };
+
+                    if next <= 0 {
+                        sum += tree.leaves[(-next) as usize];
+                        break;
+                    }
+
+                    index = next as usize;
+                }
+            }
+
+            if sum < stage.threshold {
+                return None;
+            }
+
+            score = sum - stage.threshold;
+        }
+
+        Some(score)
+    }
+}
+
+/// An element of an XML document, with its text and its child elements.
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    text: String,
+    children: Vec<Element>,
+}
+
+impl Element {
+
+    /// Parses a document. The returned element is the (unnamed) document itself.
+    fn parse<R>(reader: R) -> Result<Element> where R: Read {
+
+        let mut stack = vec![Element::default()];
+
+        for event in EventReader::new(reader) {
+            match event.map_err(|e| Error::new(ErrorKind::Io, e.to_string()))? {
+                XmlEvent::StartElement { name, .. } => {
+                    stack.push(Element { name: name.local_name, ..Element::default() });
+                },
+                XmlEvent::EndElement { .. } => {
+                    let element = stack.pop().unwrap();
+
+                    stack.last_mut().unwrap().children.push(element);
+                },
+                XmlEvent::Characters(text) | XmlEvent::CData(text) => {
+                    stack.last_mut().unwrap().text.push_str(&text);
+                },
+                _ => {},
+            }
+        }
+
+        Ok(stack.pop().unwrap())
+    }
+
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn required(&self, name: &str) -> Result<&Element> {
+        self.child(name).ok_or_else(|| invalid(format!("`<{}>` has no `<{}>`", self.name, name)))
+    }
+
+    fn number<T>(&self, name: &str) -> Result<T> where T: ::std::str::FromStr {
+        self.required(name)?.text.trim().parse().map_err(|_| invalid(format!("`<{}>` isn't a number", name)))
+    }
+}
+
+fn numbers<T>(text: &str) -> Result<Vec<T>> where T: ::std::str::FromStr {
+    text.split_whitespace().map(|v| v.parse().map_err(|_| invalid(format!("`{}` isn't a number", v)))).collect()
+}
+
+fn invalid<S>(message: S) -> Error where S: Into<String> {
+    Error::new(ErrorKind::Detection, format!("invalid cascade: {}", message.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use piston_image::{GrayImage, Luma};
+    use super::HaarCascade;
+
+    /// A 6×6 cascade of a single stump, which accepts the windows brighter on the right.
+    const CASCADE: &str = r#"<?xml version="1.0"?>
+<opencv_storage>
+<cascade type_id="opencv-cascade-classifier">
+  <stageType>BOOST</stageType>
+  <featureType>HAAR</featureType>
+  <height>6</height>
+  <width>6</width>
+  <stageNum>1</stageNum>
+  <stages>
+    <_>
+      <maxWeakCount>1</maxWeakCount>
+      <stageThreshold>0.</stageThreshold>
+      <weakClassifiers>
+        <_>
+          <internalNodes>0 -1 0 -1.</internalNodes>
+          <leafValues>1. -1.</leafValues></_></weakClassifiers></_></stages>
+  <features>
+    <_>
+      <rects>
+        <_>0 0 6 6 1.</_>
+        <_>3 0 3 6 -2.</_></rects></_></features></cascade>
+</opencv_storage>
+"#;
+
+    #[test]
+    fn vertical_edge() {
+        let cascade = HaarCascade::from_reader(CASCADE.as_bytes()).unwrap().step(1).scale_factor(1.25).min_neighbours(2);
+
+        assert_eq!(cascade.window_size(), (6, 6));
+
+        let image = GrayImage::from_fn(24, 12, |x, _| Luma { data: [if x < 12 { 20 } else { 220 }] });
+        let hits = cascade.hits(&image);
+
+        assert!(!hits.is_empty());
+
+        // every hit straddles the edge
+        for &(region, _) in &hits {
+            let (x, _, w, _) = region.bounds();
+
+            assert!(x < 12.0 && x + w > 12.0);
+        }
+
+        assert!(!cascade.detect(&image).is_empty());
+
+        // a uniform image has no edge
+        assert!(cascade.hits(&GrayImage::from_pixel(24, 12, Luma { data: [128] })).is_empty());
+    }
+
+    /// A 12×12 cascade of a single tilted stump, which accepts the windows split by an edge along
+    /// the diagonal, brighter on the upper right.
+    const TILTED: &str = r#"<?xml version="1.0"?>
+<opencv_storage>
+<cascade type_id="opencv-cascade-classifier">
+  <stageType>BOOST</stageType>
+  <featureType>HAAR</featureType>
+  <height>12</height>
+  <width>12</width>
+  <stageNum>1</stageNum>
+  <stages>
+    <_>
+      <maxWeakCount>1</maxWeakCount>
+      <stageThreshold>0.</stageThreshold>
+      <weakClassifiers>
+        <_>
+          <internalNodes>0 -1 0 0.25</internalNodes>
+          <leafValues>-1. 1.</leafValues></_></weakClassifiers></_></stages>
+  <features>
+    <_>
+      <rects>
+        <_>6 0 6 6 -1.</_>
+        <_>6 0 6 3 2.</_></rects>
+      <tilted>1</tilted></_></features></cascade>
+</opencv_storage>
+"#;
+
+    #[test]
+    fn tilted_features() {
+        let cascade = HaarCascade::from_reader(TILTED.as_bytes()).unwrap().step(1).scale_factor(1.0);
+
+        // a smooth edge, brighter where `f(x, y)` is positive
+        let edge = |f: &Fn(i32, i32) -> i32| {
+            GrayImage::from_fn(32, 32, |x, y| {
+                Luma { data: [(120.0 + 100.0 * (f(x as i32, y as i32) as f64 / 3.0).tanh()) as u8] }
+            })
+        };
+
+        let hits = cascade.hits(&edge(&|x, y| x - y));
+
+        assert!(!hits.is_empty());
+
+        // every hit is on the diagonal
+        for &(region, _) in &hits {
+            let (x, y, _, _) = region.bounds();
+
+            assert!((x - y - 1.0).abs() <= 3.0);
+        }
+
+        // the other edges: mirrored, along the other diagonal, vertical and horizontal
+        for edge in &[edge(&|x, y| y - x), edge(&|x, y| x + y - 31), edge(&|x, _| x - 16), edge(&|_, y| 16 - y)] {
+            assert!(cascade.hits(edge).is_empty());
+        }
+    }
+
+    #[test]
+    fn invalid_cascades() {
+        assert!(HaarCascade::from_reader("<opencv_storage></opencv_storage>".as_bytes()).is_err());
+        assert!(HaarCascade::from_reader(CASCADE.replace("0 -1 0 -1.", "0 -1 3 -1.").as_bytes()).is_err());
+    }
+}
@@ -1,2 +1,10 @@
-pub use self::sliding_window::window;
-mod sliding_window;
\ No newline at end of file
+//! Object detection and localization
+pub use self::haar::HaarCascade;
+pub use self::sliding_window::{window, Window};
+pub use self::suppression::{group_rectangles, iou, non_max_suppression, soft_non_max_suppression, Decay};
+pub use self::template::{best_matches, match_template, Method};
+
+mod haar;
+mod sliding_window;
+mod suppression;
+mod template;
@@ -1,270 +1,245 @@
-// use std::iter::{Iterator, Map};
-// use std::mem;
-// use std::ops::Range;
-
-//pub type StepBy<T> = Map<::std::iter::StepBy<u32, Range<u32>>, fn(u32) -> T>;
-
-pub fn window() {}
-
-// pub struct Window {
-//     // empty: bool,
-
-//     // factor: f64,
-
-//     // image_width: u32,
-//     // image_height: u32,
-
-//     // width: f64,
-//     // height: f64,
-
-//     // x: f64,
-//     // y: f64,
-
-//     // xstep: u32,
-//     // ystep: u32,
-
-//     // horiz: StepBy<f64>,
-//     // vert: StepBy<f64>,
-// }
-
-
-// /// ```rust,ignore
-// /// use high::{capture, piston};
-// /// use miro::detection;
-// /// 
-// /// let color = [0.8125, 0.8125, 0.8125, 0.75];
-// /// let image_size = (640, 360);
-// /// let min_window_size = (100.0, 100.0);
-// /// let step = (20, 20);
-// /// let mut win = detection::window(image_size, min_window_size, step, 1.5);
-// /// 
-// /// capture::conn();
-// /// 
-// /// 'window: while piston::open() {
-// /// 
-// ///     if piston::render() {
-// /// 
-// ///         piston::clear([1.0; 4])?;
-// /// 
-// ///         let rgba_image = capture::read();
-// /// 
-// ///         piston::draw_image(&rgba_image)?;
-// /// 
-// ///         if let Some(r) = win.next() {
-// /// 
-// ///             piston::draw_border(color, r)?;
-// ///         }
-// ///     }
-// /// }
-// /// 
-// /// capture::disconn();
-// /// ``
-// pub fn window(im: (u32, u32), win: (f64, f64), step: (u32, u32), f: f64) -> Window {
-
-//     let (image_width, image_height) = im;
-//     let (width, height) = win;
-//     let (xstep, ystep) = step;
-
-//     if width as u32 >= image_width || height as u32 >= image_height {
-
-//         return unsafe { Window {
-//             empty: true,
-//             factor: mem::uninitialized(),
-//             image_width: mem::uninitialized(),
-//             image_height: mem::uninitialized(),
-//             width: mem::uninitialized(),
-//             height: mem::uninitialized(),
-//             x: mem::uninitialized(),
-//             y: mem::uninitialized(),
-//             xstep: mem::uninitialized(),
-//             ystep: mem::uninitialized(),
-//             horiz: mem::uninitialized(),
-//             vert: mem::uninitialized(),
-//         }};
-//     }
-
-//     let horiz: StepBy<f64> = (0..image_width).step_by(xstep).map(Into::<f64>::into);
-//     let mut vert: StepBy<f64> = (0..image_height).step_by(ystep).map(Into::<f64>::into);
+use euclidean::Region2D as Region;
+use std::cmp;
+
+/// A multi-scale sliding window, created by `window`.
+#[derive(Clone, Debug)]
+pub struct Window {
+    image_width: u32,
+    image_height: u32,
+
+    width: f64,
+    height: f64,
+
+    xstep: u32,
+    ystep: u32,
+
+    factor: f64,
+
+    x: u32,
+    y: u32,
+
+    done: bool,
+}
+
+/// Slides a window over an image, row by row, and then grows it by `f` until it doesn't fit in
+/// the image anymore.
+///
+/// A window at (`x`, `y`) fits if `⌊x + w⌋ < W` and `⌊y + h⌋ < H`: the windows never reach the
+/// right and bottom edges of the image (e.g., a window as wide as the image is never returned).
+///
+/// # Arguments
+///
+/// * `im` - The dimensions of the image.
+/// * `win` - The dimensions of the smallest window.
+/// * `step` - The horizontal and vertical steps of the window, in pixels. If a step is 0, 1 is
+///   chosen.
+/// * `f` - The scale factor between two sizes of the window. If `f` isn't greater than 1, only
+///   the smallest window is slid.
+///
+/// ```rust,ignore
+/// use high::{capture, piston};
+/// use miro::modules::detection;
+///
+/// let color = [0.8125, 0.8125, 0.8125, 0.75];
+/// let image_size = (640, 360);
+/// let min_window_size = (100.0, 100.0);
+/// let step = (20, 20);
+/// let mut win = detection::window(image_size, min_window_size, step, 1.5);
+///
+/// capture::conn();
+///
+/// 'window: while piston::open() {
+///
+///     if piston::render() {
+///
+///         piston::clear([1.0; 4])?;
+///
+///         let rgba_image = capture::read();
+///
+///         piston::draw_image(&rgba_image)?;
+///
+///         if let Some(r) = win.next() {
+///
+///             piston::draw_border(color, r)?;
+///         }
+///     }
+/// }
+///
+/// capture::disconn();
+/// ```
+pub fn window(im: (u32, u32), win: (f64, f64), step: (u32, u32), f: f64) -> Window {
+
+    let (image_width, image_height) = im;
+    let (width, height) = win;
+    let (xstep, ystep) = step;
+
+    Window {
+        image_width,
+        image_height,
+        width,
+        height,
+        xstep: cmp::max(1, xstep),
+        ystep: cmp::max(1, ystep),
+        factor: f,
+        x: 0,
+        y: 0,
+        done: !(width > 0.0 && height > 0.0),
+    }
+}
+
+impl Iterator for Window {
+
+    type Item = Region;
+
+    fn next(&mut self) -> Option<Self::Item> {
+
+        while !self.done {
+
+            if self.width as u32 >= self.image_width || self.height as u32 >= self.image_height {
+                self.done = true;
+                break;
+            }
+
+            // the window doesn't fit below the current row: next scale
+            if (self.y as f64 + self.height) as u32 >= self.image_height {
+
+                if self.factor <= 1.0 {
+                    self.done = true;
+                    break;
+                }
+
+                self.width *= self.factor;
+                self.height *= self.factor;
+                self.x = 0;
+                self.y = 0;
+
+                continue;
+            }
+
+            // the window doesn't fit right of the current column: next row
+            if (self.x as f64 + self.width) as u32 >= self.image_width {
+                self.x = 0;
+                self.y += self.ystep;
+
+                continue;
+            }
+
+            let region = [self.x as f64, self.y as f64, self.width, self.height];
+
+            self.x += self.xstep;
+
+            return Some(region.into());
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    #[test]
+    fn test1() {
+        let image_width = 640;
+        let image_height = 360;
+
+        let factor: f64 = 1.5;
+
+        let (xstep, ystep) = (10, 10);
+
+        let (min_w, min_h) = (100.0, 100.0);
 
-//     let _ = vert.next().unwrap();
+        let mut slider = super::window((image_width, image_height), (min_w, min_h), (xstep, ystep), factor);
 
-//     Window {
-//         empty: false,
-//         factor: f,
-//         image_width: image_width,
-//         image_height: image_height,
+        let scaled = |n: u32| {
+            let f = factor.powi(n as i32);
 
-//         width: width,
-//         height: height,
+            (min_w * f, min_h * f)
+        };
 
-//         x: 0.0,
-//         y: 0.0,
+        // iterate over a range of scaled regions (smallest scale -> largest)
+        for (w, h) in (0..).map(scaled) {
 
-//         xstep: xstep,
-//         ystep: ystep,
+            if w as u32 >= image_width || h as u32 >= image_height {
 
-//         horiz: horiz,
-//         vert: vert
-//     }
-// }
+                // scale is bigger than image, so break.
+                break
+            }
 
-// impl Iterator for Window {
+            // for each row, scan each column
+            for y in (0..image_height).filter(|y| y % ystep == 0).map(|y| y as f64) {
 
-//     type Item = [f64; 4];
+                let bottom = (y + h) as u32;
 
-//     fn next(&mut self) -> Option<Self::Item> {
+                if bottom >= image_height {
 
-//         if self.empty {
+                    break
+                }
 
-//             return None;
-//         }
+                for x in (0..image_width).filter(|x| x % xstep == 0).map(|x| x as f64) {
 
-//         if let Some(x) = self.horiz.next() {
-//             let top_r = (x + self.width) as u32;
+                    let top_r = (x + w) as u32;
 
-//             if !(top_r >= self.image_width) {
-//                 self.x = x;
-//                 return Some([self.x, self.y, self.width, self.height]);
-//             }
-//         }
+                    if top_r >= image_width {
 
-//         if let Some(y) = self.vert.next() {
-//             self.horiz = (0..self.image_width).step_by(self.xstep).map(Into::<f64>::into);
-//             let x = self.horiz.next().unwrap();
-//             self.x = x;
+                        break
+                    }
 
-//             let bottom = (y + self.height) as u32;
+                    let a = slider.next().unwrap().bounds();
+                    let b = (x, y, w, h);
 
-//             if !(bottom >=  self.image_height) {
-//                 self.y = y;
-//                 return Some([self.x, self.y, self.width, self.height]);
-//             }
-//         }
+                    assert_eq!(a, b);
+                }
+            }
+        }
 
-//         self.width *= self.factor;
-//         self.height *= self.factor;
+        assert!(slider.next().is_none());
+        assert!(slider.next().is_none());
+        assert!(slider.next().is_none());
+        assert!(slider.next().is_none());
+        assert!(slider.next().is_none());
+        assert!(slider.next().is_none());
+    }
 
-//         if !(self.width as u32 >= self.image_width || self.height as u32 >= self.image_height) {
+    #[test]
+    fn test2() {
+        let dim = (640, 360);
+        let step = (10, 10);
+        let min = (100.0, 360.0);
+        let factor = 1.5;
 
-//             self.vert = (0..self.image_height).step_by(self.ystep).map(Into::<f64>::into);
-//             let y = self.vert.next().unwrap();
-//             self.y = y;
-            
-//             return Some([self.x, self.y, self.width, self.height]);
-//         }
+        let mut slider = super::window(dim, min, step, factor);
 
-//         self.empty = true;
+        assert!(slider.next().is_none());
+        assert!(slider.next().is_none());
+    }
 
-//         None
-//     }
-// }
+    #[test]
+    fn test3() {
+        let dim = (640, 360);
+        let step = (10, 10);
+        let min = (640.0, 100.0);
+        let factor = 1.5;
 
-// #[cfg(test)]
-// mod tests {
+        let mut slider = super::window(dim, min, step, factor);
 
-//     #[test]
-//     fn test1() {
-//         let image_width = 640;
-//         let image_height = 360;
+        assert!(slider.next().is_none());
+        assert!(slider.next().is_none());
+    }
 
-//         let factor: f64 = 1.5;
+    #[test]
+    fn test4() {
+        let dim = (640, 360);
+        let step = (540, 260);
+        let min = (100.0, 100.0);
+        let factor = 1.5;
 
-//         let (xstep, ystep) = (10, 10);
+        let mut slider = super::window(dim, min, step, factor);
 
-//         let (minW, minH) = (100.0, 100.0);
-
-//         let mut slider = super::window((image_width, image_height), (minW, minH), (xstep, ystep), factor);
-
-//         let scaled = |n: u32| {
-//             let f = factor.powi(n as i32);
-
-//             (minW * f, minH * f)
-//         };
-
-//         // iterate over a range of scaled regions (smallest scale -> largest)
-//         for (w, h) in (0..).map(scaled) {
-
-//             if w as u32 >= image_width || h as u32 >= image_height {
-
-//                 // scale is bigger than image, so break.
-//                 break
-//             }
-
-
-//             // for each row, scan each column
-//             for y in (0..image_height).step_by(ystep).map(|y| y as f64) {
-
-//                 let bottom = (y + h) as u32;
-
-//                 if bottom >=  image_height {
-
-//                     break
-//                 }
-
-//                 for x in (0..image_width).step_by(xstep).map(|x| x as f64) {
-
-//                     let top_r = (x + w) as u32;
-
-//                     if top_r >= image_width {
-
-//                         break
-//                     }
-
-//                     let a = slider.next().unwrap();
-//                     let b = [x, y, w, h];
-
-//                     assert_eq!(a, b);
-//                 }
-//             }
-//         }
-
-//         assert!(slider.next().is_none());
-//         assert!(slider.next().is_none());
-//         assert!(slider.next().is_none());
-//         assert!(slider.next().is_none());
-//         assert!(slider.next().is_none());
-//         assert!(slider.next().is_none());
-//     }
-
-//     #[test]
-//     fn test2() {
-//         let dim = (640, 360);
-//         let step = (10, 10);
-//         let min = (100.0, 360.0);
-//         let factor = 1.5;
-
-//         let mut slider = super::window(dim, min, step, factor);
-
-//         assert!(slider.next().is_none());
-//         assert!(slider.next().is_none());
-//     }
-
-//     #[test]
-//     fn test3() {
-//         let dim = (640, 360);
-//         let step = (10, 10);
-//         let min = (640.0, 100.0);
-//         let factor = 1.5;
-
-//         let mut slider = super::window(dim, min, step, factor);
-
-//         assert!(slider.next().is_none());
-//         assert!(slider.next().is_none());
-//     }
-
-//     #[test]
-//     fn test4() {
-//         let dim = (640, 360);
-//         let step = (540, 260);
-//         let min = (100.0, 100.0);
-//         let factor = 1.5;
-
-//         let mut slider = super::window(dim, min, step, factor);
-
-//         assert!(slider.next().is_some()); // (0, 0) 100 x 100
-//         assert!(slider.next().is_some()); // (0, 0) (100 x 100) * 1.5 = (0, 0) (150 x 150)
-//         assert!(slider.next().is_some()); // (0, 0) (150 x 150) * 1.5 = (0, 0) (225 x 225)
-//         assert!(slider.next().is_some()); // (0, 0) (225 x 225) * 1.5 = (0, 0) (337.5 x 337.5)
-//         assert!(slider.next().is_none()); // (0, 0) (337.5 x 337.5) * 1.5 = (0, 0) (506.25 x 506.25) > (w x 360)
-//     }
-// }
\ No newline at end of file
+        assert!(slider.next().is_some()); // (0, 0) 100 x 100
+        assert!(slider.next().is_some()); // (0, 0) (100 x 100) * 1.5 = (0, 0) (150 x 150)
+        assert!(slider.next().is_some()); // (0, 0) (150 x 150) * 1.5 = (0, 0) (225 x 225)
+        assert!(slider.next().is_some()); // (0, 0) (225 x 225) * 1.5 = (0, 0) (337.5 x 337.5)
+        assert!(slider.next().is_none()); // (0, 0) (337.5 x 337.5) * 1.5 = (0, 0) (506.25 x 506.25) > (w x 360)
+    }
+}
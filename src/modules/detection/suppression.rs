@@ -0,0 +1,281 @@
+use euclidean::Region2D as Region;
+use std::cmp;
+
+/// The decay of the scores of the detections overlapping a better detection, in soft-NMS.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Decay {
+    /// The score is multiplied by `1 - IoU` if the IoU is above the threshold.
+    Linear(f64),
+    /// The score is multiplied by `exp(-IoU² / σ)`.
+    Gaussian(f64),
+}
+
+/// Returns the intersection over union (the Jaccard index) of two regions, in `[0, 1]`.
+///
+/// Returns 0 if both regions are empty.
+pub fn iou(a: Region, b: Region) -> f64 {
+
+    let (ax, ay, aw, ah) = a.bounds();
+    let (bx, by, bw, bh) = b.bounds();
+
+    let w = ((ax + aw).min(bx + bw) - ax.max(bx)).max(0.0);
+    let h = ((ay + ah).min(by + bh) - ay.max(by)).max(0.0);
+
+    let intersection = w * h;
+    let union = aw * ah + bw * bh - intersection;
+
+    if union <= 0.0 { 0.0 } else { intersection / union }
+}
+
+/// Greedy non-maximum suppression
+///
+/// The detections are visited from the highest score; a detection is kept unless its IoU with a
+/// kept detection is above `threshold`.
+///
+/// # Returns
+///
+/// The kept detections, from the highest score.
+pub fn non_max_suppression(detections: &[(Region, f32)], threshold: f64) -> Vec<(Region, f32)> {
+
+    let mut kept: Vec<(Region, f32)> = Vec::new();
+
+    for &(region, score) in &by_score(detections) {
+        if kept.iter().all(|&(other, _)| iou(region, other) <= threshold) {
+            kept.push((region, score));
+        }
+    }
+
+    kept
+}
+
+/// Soft non-maximum suppression
+///
+/// Rather than discarding the detections that overlap a better one, their scores decay with
+/// the overlap. The detection with the highest remaining score is kept, the scores of the
+/// others decay, and so on. The detections whose score falls below `min_score` are discarded.
+///
+/// Nearby objects survive better than with `non_max_suppression`, at the cost of a final
+/// threshold on the scores.
+///
+/// # Returns
+///
+/// The kept detections, with their decayed scores, from the highest score.
+///
+/// ## References
+///
+/// * Bodla, N., Singh, B., Chellappa, R., & Davis, L. S. (2017). *Soft-NMS — Improving object
+///   detection with one line of code*. In Proceedings of the IEEE International Conference on
+///   Computer Vision (pp. 5561-5569).
+pub fn soft_non_max_suppression(detections: &[(Region, f32)], decay: Decay, min_score: f32) -> Vec<(Region, f32)> {
+
+    let mut remaining: Vec<(Region, f32)> = detections.iter().cloned().filter(|&(_, score)| score >= min_score).collect();
+    let mut kept = Vec::new();
+
+    while !remaining.is_empty() {
+        let best = (0..remaining.len()).fold(0, |best, n| if remaining[n].1 > remaining[best].1 { n } else { best });
+        let (region, score) = remaining.swap_remove(best);
+
+        for detection in &mut remaining {
+            let overlap = iou(region, detection.0);
+
+            let factor = match decay {
+                Decay::Linear(threshold) => if overlap > threshold { 1.0 - overlap } else { 1.0 },
+                Decay::Gaussian(sigma) => (-overlap * overlap / sigma).exp(),
+            };
+
+            detection.1 *= factor as f32;
+        }
+
+        remaining.retain(|&(_, score)| score >= min_score);
+        kept.push((region, score));
+    }
+
+    kept
+}
+
+/// Clusters similar regions, as `groupRectangles` of OpenCV
+///
+/// Two regions are similar if each of their sides is within
+/// `eps × (min(w₁, w₂) + min(h₁, h₂)) / 2` of the other. The clusters of similar regions (the
+/// transitive closure of the similarity) with more than `min_neighbours` regions are averaged.
+/// The averaged regions inside a larger cluster are then discarded.
+///
+/// As in OpenCV, the regions are returned unchanged (each averaging itself) if `min_neighbours`
+/// is 0.
+///
+/// # Returns
+///
+/// The averaged regions, with the highest score of their cluster and the number of regions
+/// they average, from the largest cluster.
+pub fn group_rectangles(detections: &[(Region, f32)], min_neighbours: usize, eps: f64) -> Vec<(Region, f32, usize)> {
+
+    if min_neighbours == 0 {
+        return detections.iter().map(|&(region, score)| (region, score, 1)).collect();
+    }
+
+    let count = detections.len();
+
+    // union-find of the similar regions
+    let mut parents: Vec<usize> = (0..count).collect();
+
+    fn root(parents: &mut [usize], mut n: usize) -> usize {
+        while parents[n] != n {
+            parents[n] = parents[parents[n]];
+            n = parents[n];
+        }
+
+        n
+    }
+
+    for i in 0..count {
+        for j in i + 1..count {
+            if similar(detections[i].0, detections[j].0, eps) {
+                let (a, b) = (root(&mut parents, i), root(&mut parents, j));
+
+                parents[a] = b;
+            }
+        }
+    }
+
+    // the sums of the bounds, the best scores and the sizes of the clusters
+    let mut clusters: Vec<([f64; 4], f32, usize)> = Vec::new();
+    let mut labels: Vec<Option<usize>> = vec![None; count];
+
+    for (n, &(region, score)) in detections.iter().enumerate() {
+        let r = root(&mut parents, n);
+
+        let label = match labels[r] {
+            Some(label) => label,
+            None => {
+                clusters.push(([0.0; 4], score, 0));
+                labels[r] = Some(clusters.len() - 1);
+                clusters.len() - 1
+            },
+        };
+
+        let (x, y, w, h) = region.bounds();
+        let cluster = &mut clusters[label];
+
+        for (sum, value) in cluster.0.iter_mut().zip(&[x, y, w, h]) {
+            *sum += *value;
+        }
+
+        cluster.1 = cluster.1.max(score);
+        cluster.2 += 1;
+    }
+
+    let averages: Vec<(Region, f32, usize)> = clusters.into_iter()
+        .filter(|&(_, _, size)| size > min_neighbours)
+        .map(|(sums, score, size)| {
+            let n = size as f64;
+
+            ([sums[0] / n, sums[1] / n, sums[2] / n, sums[3] / n].into(), score, size)
+        })
+        .collect();
+
+    // discards the small clusters inside a large one
+    let mut groups: Vec<(Region, f32, usize)> = averages.iter().enumerate()
+        .filter(|&(i, &(inner, _, inner_size))| {
+            !averages.iter().enumerate().any(|(j, &(outer, _, outer_size))| {
+                i != j && (outer_size > cmp::max(3, inner_size) || inner_size < 3) && inside(inner, outer, eps)
+            })
+        })
+        .map(|(_, &group)| group)
+        .collect();
+
+    groups.sort_by(|a, b| b.2.cmp(&a.2));
+
+    groups
+}
+
+/// Returns the detections sorted by decreasing score.
+fn by_score(detections: &[(Region, f32)]) -> Vec<(Region, f32)> {
+
+    let mut sorted = detections.to_vec();
+
+    sorted.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(cmp::Ordering::Equal));
+
+    sorted
+}
+
+fn similar(a: Region, b: Region, eps: f64) -> bool {
+
+    let (ax, ay, aw, ah) = a.bounds();
+    let (bx, by, bw, bh) = b.bounds();
+
+    let delta = eps * (aw.min(bw) + ah.min(bh)) / 2.0;
+
+    (ax - bx).abs() <= delta && (ay - by).abs() <= delta
+        && (ax + aw - bx - bw).abs() <= delta && (ay + ah - by - bh).abs() <= delta
+}
+
+/// Returns `true` if `inner` is inside `outer`, with a margin of `eps` times the size of
+/// `outer`.
+fn inside(inner: Region, outer: Region, eps: f64) -> bool {
+
+    let (ix, iy, iw, ih) = inner.bounds();
+    let (ox, oy, ow, oh) = outer.bounds();
+
+    let (dx, dy) = ((ow * eps).round(), (oh * eps).round());
+
+    ix >= ox - dx && iy >= oy - dy && ix + iw <= ox + ow + dx && iy + ih <= oy + oh + dy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{group_rectangles, iou, non_max_suppression, soft_non_max_suppression, Decay};
+
+    #[test]
+    fn greedy_and_soft() {
+        let detections = vec![
+            ([0.0, 0.0, 10.0, 10.0].into(), 0.9),
+            ([1.0, 1.0, 10.0, 10.0].into(), 0.8),
+            ([30.0, 30.0, 10.0, 10.0].into(), 0.7),
+        ];
+
+        assert!((iou(detections[0].0, detections[1].0) - 81.0 / 119.0).abs() < 1e-9);
+
+        let kept = non_max_suppression(&detections, 0.5);
+
+        assert_eq!(kept.len(), 2);
+        assert!((kept[0].1 - 0.9).abs() < 1e-6 && (kept[1].1 - 0.7).abs() < 1e-6);
+
+        // the overlapping detection decays below the isolated one, but survives
+        let soft = soft_non_max_suppression(&detections, Decay::Gaussian(0.5), 0.1);
+
+        assert_eq!(soft.len(), 3);
+        assert!((soft[1].1 - 0.7).abs() < 1e-6 && soft[2].1 < 0.8);
+    }
+
+    #[test]
+    fn groups() {
+        let mut detections = Vec::new();
+
+        for &(x, y) in &[(10.0, 10.0), (11.0, 10.0), (10.0, 12.0), (12.0, 11.0)] {
+            detections.push(([x, y, 20.0, 20.0].into(), 1.0));
+        }
+
+        // an isolated false positive
+        detections.push(([60.0, 60.0, 20.0, 20.0].into(), 2.0));
+
+        let groups = group_rectangles(&detections, 1, 0.2);
+
+        assert_eq!(groups.len(), 1);
+
+        let (x, y, w, h) = groups[0].0.bounds();
+
+        assert!((x - 10.75).abs() < 1e-9 && (y - 10.75).abs() < 1e-9);
+        assert!((w - 20.0).abs() < 1e-9 && (h - 20.0).abs() < 1e-9);
+        assert_eq!(groups[0].2, 4);
+
+        // no grouping without a minimum number of neighbours
+        let ungrouped = group_rectangles(&detections, 0, 0.2);
+
+        assert_eq!(ungrouped.len(), detections.len());
+
+        for (&(region, score, count), &(detection, detection_score)) in ungrouped.iter().zip(&detections) {
+            assert_eq!(region.bounds(), detection.bounds());
+            assert_eq!((score, count), (detection_score, 1));
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use error::{Error, ErrorKind, Result};
+use euclidean::Region2D as Region;
+use image::{Gray32FImage, IntegralImage, Intensity, LumaImage};
+use piston_image::Luma;
+use std::cmp;
+
+/// The score of a template at a position of an image.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Method {
+    /// The sum of squared differences. The lower, the better (0 is a perfect match).
+    Ssd,
+    /// The sum of absolute differences. The lower, the better (0 is a perfect match).
+    Sad,
+    /// The zero-mean normalized cross-correlation, in `[-1, 1]`. The higher, the better (1 is a
+    /// perfect match up to an affine change of intensity).
+    ///
+    /// The score of a uniform window (or template) is 0.
+    Zncc,
+}
+
+impl Method {
+
+    /// Returns `true` if the score `a` is a better match than the score `b`.
+    pub fn is_better(&self, a: f32, b: f32) -> bool {
+        match *self {
+            Method::Ssd | Method::Sad => a < b,
+            Method::Zncc => a > b,
+        }
+    }
+}
+
+/// Slides `template` over `image` and scores every position with `method`.
+///
+/// The sums over the windows of `image` come from its `IntegralImage`: `Ssd` only computes the
+/// cross-correlation with the template, and `Zncc` normalizes it in constant time per
+/// position. `Sad` compares every pixel.
+///
+/// To search a region only (e.g., around the last known location of a target), match the
+/// template on a crop of the image and offset the results.
+///
+/// # Returns
+///
+/// The `(W - w + 1) × (H - h + 1)` score map, whose pixel (`x`, `y`) is the score of the
+/// template with its top-left corner at (`x`, `y`), or an error if the template is empty or
+/// larger than the image.
+pub fn match_template<T>(image: &LumaImage<T>, template: &LumaImage<T>, method: Method) -> Result<Gray32FImage>
+    where T: Intensity
+{
+    let (width, height) = image.dimensions();
+    let (w, h) = template.dimensions();
+
+    if w == 0 || h == 0 {
+        return Err(Error::new(ErrorKind::Detection, "the template is empty"));
+    }
+
+    if w > width || h > height {
+        return Err(Error::new(ErrorKind::Detection, "the template is larger than the image"));
+    }
+
+    let pixels: Vec<f32> = image.iter().map(|&p| p.to_intensity()).collect();
+    let mut template: Vec<f32> = template.iter().map(|&p| p.to_intensity()).collect();
+
+    let n = (w * h) as f64;
+    let template_sum: f64 = template.iter().map(|&t| t as f64).sum();
+    let template_squared_sum: f64 = template.iter().map(|&t| t as f64 * t as f64).sum();
+
+    // with a zero-mean template, the cross-correlation is already centred
+    let template_variance_sum = template_squared_sum - template_sum * template_sum / n;
+
+    if method == Method::Zncc {
+        let mean = (template_sum / n) as f32;
+
+        for t in &mut template {
+            *t -= mean;
+        }
+    }
+
+    let integral = match method {
+        Method::Ssd | Method::Zncc => Some(IntegralImage::new(image)),
+        Method::Sad => None,
+    };
+
+    // the sum of `f(window pixel, template pixel)` over the template
+    let fold = |x: u32, y: u32, f: &Fn(f32, f32) -> f32| {
+        let mut sum = 0.0;
+
+        for v in 0..h {
+            let row = ((y + v) * width + x) as usize;
+            let window = &pixels[row..row + w as usize];
+
+            for (&p, &t) in window.iter().zip(&template[(v * w) as usize..((v + 1) * w) as usize]) {
+                sum += f(p, t) as f64;
+            }
+        }
+
+        sum
+    };
+
+    Ok(Gray32FImage::from_fn(width - w + 1, height - h + 1, |x, y| {
+        let score = match method {
+            Method::Sad => fold(x, y, &|p, t| (p - t).abs()),
+            Method::Ssd => {
+                let squared_sum = integral.as_ref().unwrap().squared_sum(x, y, w, h);
+
+                (squared_sum - 2.0 * fold(x, y, &|p, t| p * t) + template_squared_sum).max(0.0)
+            },
+            Method::Zncc => {
+                let (_, variance) = integral.as_ref().unwrap().mean_variance(x, y, w, h);
+                let denominator = (variance * n * template_variance_sum).sqrt();
+
+                if denominator <= 1e-6 {
+                    0.0
+                } else {
+                    (fold(x, y, &|p, t| p * t) / denominator).max(-1.0).min(1.0)
+                }
+            },
+        };
+
+        Luma { data: [score as f32] }
+    }))
+}
+
+/// Finds the best matches of a score map computed by `match_template`, with non-maximum
+/// suppression.
+///
+/// The positions are visited from the best score to the worst; a position is kept unless a
+/// better match was kept less than half the template away along both axes.
+///
+/// # Arguments
+///
+/// * `scores` - The score map.
+/// * `method` - The method of the score map.
+/// * `template` - The dimensions of the template.
+/// * `max_matches` - The maximum number of matches.
+///
+/// # Returns
+///
+/// The regions of the matches, in the coordinates of the image, and their scores, from the best
+/// match.
+pub fn best_matches(scores: &Gray32FImage, method: Method, (w, h): (u32, u32), max_matches: usize)
+    -> Vec<(Region, f32)>
+{
+    let width = scores.width();
+
+    let mut positions: Vec<(u32, f32)> = scores.iter().cloned().enumerate()
+        .filter(|&(_, score)| !score.is_nan())
+        .map(|(index, score)| (index as u32, score))
+        .collect();
+
+    positions.sort_by(|a, b| {
+        let (a, b) = (a.1, b.1);
+
+        if method.is_better(a, b) {
+            cmp::Ordering::Less
+        } else if method.is_better(b, a) {
+            cmp::Ordering::Greater
+        } else {
+            cmp::Ordering::Equal
+        }
+    });
+
+    let (half_w, half_h) = (w as f64 / 2.0, h as f64 / 2.0);
+    let mut kept: Vec<(u32, u32, f32)> = Vec::new();
+
+    for &(index, score) in &positions {
+        if kept.len() >= max_matches {
+            break;
+        }
+
+        let (x, y) = (index % width, index / width);
+
+        let suppressed = kept.iter().any(|&(kx, ky, _)| {
+            ((x as f64 - kx as f64).abs()) < half_w && ((y as f64 - ky as f64).abs()) < half_h
+        });
+
+        if !suppressed {
+            kept.push((x, y, score));
+        }
+    }
+
+    kept.into_iter().map(|(x, y, score)| ([x as f64, y as f64, w as f64, h as f64].into(), score)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use piston_image::{GrayImage, Luma};
+    use super::{best_matches, match_template, Method};
+
+    fn texture(x: u32, y: u32) -> u8 {
+        ((x * 37 + y * 91 + x * y * 13) % 251) as u8
+    }
+
+    #[test]
+    fn finds_the_template() {
+        let image = GrayImage::from_fn(40, 30, |x, y| Luma { data: [texture(x, y)] });
+        let template = GrayImage::from_fn(8, 6, |x, y| Luma { data: [texture(x + 21, y + 13)] });
+
+        for &method in &[Method::Ssd, Method::Sad, Method::Zncc] {
+            let scores = match_template(&image, &template, method).unwrap();
+
+            assert_eq!(scores.dimensions(), (33, 25));
+
+            let matches = best_matches(&scores, method, (8, 6), 3);
+            let (x, y, w, h) = matches[0].0.bounds();
+
+            assert_eq!((x as u32, y as u32, w as u32, h as u32), (21, 13, 8, 6));
+
+            match method {
+                Method::Zncc => assert!(matches[0].1 > 0.999),
+                _ => assert!(matches[0].1 < 1e-2),
+            }
+
+            // the other matches are away from the best one
+            for &(region, _) in &matches[1..] {
+                let (mx, my, _, _) = region.bounds();
+
+                assert!((mx - x).abs() >= 4.0 || (my - y).abs() >= 3.0);
+            }
+        }
+    }
+
+    #[test]
+    fn template_larger_than_image() {
+        let image = GrayImage::new(4, 4);
+        let template = GrayImage::new(5, 2);
+
+        assert!(match_template(&image, &template, Method::Ssd).is_err());
+    }
+}
@@ -0,0 +1,212 @@
+use core::feature::{BinaryDescriptor, Describe, Keypoint};
+use error::Result;
+use image::GrayPyramid;
+use piston_image::GrayImage;
+use rand::{Rng, SeedableRng, XorShiftRng};
+use rand::distributions::{IndependentSample, Normal};
+
+/// The radius of the patch described around a keypoint (a 31×31 patch).
+pub const PATCH_RADIUS: i32 = 15;
+
+/// The half-side of the box over which the tested pixels are smoothed (a 5×5 box).
+const BOX_RADIUS: i32 = 2;
+
+/// The seed of the default sampling pattern, so that the descriptors computed by different
+/// `Brief` instances are comparable.
+const SEED: [u32; 4] = [0x193a_6754, 0xa8a7_d469, 0x9783_0e05, 0x113b_a7bb];
+
+/// A test: the first point is compared to the second.
+type Pair = ((f32, f32), (f32, f32));
+
+/// BRIEF descriptor
+///
+/// Each bit of the descriptor is a binary test: is the (smoothed) intensity at a point of the
+/// patch smaller than the intensity at another point? The pairs of points are sampled once, at
+/// random, from an isotropic Gaussian centred on the keypoint, the same idea as the binary tests
+/// of random ferns. The sampling isn't shared with `RandomFerns`, whose tests aren't implemented
+/// (the `classification` module isn't built). The intensities are smoothed by averaging 5×5
+/// boxes, which makes the tests robust to noise.
+///
+/// BRIEF isn't invariant to rotation; see `Orb` for the steered variant.
+///
+/// Keypoints found on the level `l` of a `GrayPyramid` are described on that level.
+///
+/// ## References
+///
+/// * Calonder, M., Lepetit, V., Strecha, C., & Fua, P. (2010). *BRIEF: Binary robust independent
+///   elementary features*. In European Conference on Computer Vision (pp. 778-792). Springer
+///   Berlin Heidelberg.
+#[derive(Clone, Debug)]
+pub struct Brief {
+    pairs: Vec<Pair>,
+}
+
+impl Brief {
+
+    /// Constructs a descriptor of `bits` tests (usually 128, 256 or 512), sampled with a fixed
+    /// seed.
+    pub fn new(bits: usize) -> Brief {
+        Brief::with_rng(bits, &mut XorShiftRng::from_seed(SEED))
+    }
+
+    /// Constructs a descriptor of `bits` tests, sampled with `rng`.
+    pub fn with_rng<R>(bits: usize, rng: &mut R) -> Brief where R: Rng {
+
+        // σ = S / 5, as recommended by the authors (the tests are clamped to the patch)
+        let normal = Normal::new(0.0, (2 * PATCH_RADIUS + 1) as f64 / 5.0);
+        let limit = (PATCH_RADIUS - BOX_RADIUS) as f64;
+
+        let mut sample = || normal.ind_sample(rng).round().max(-limit).min(limit) as f32;
+
+        let pairs = (0..bits).map(|_| ((sample(), sample()), (sample(), sample()))).collect();
+
+        Brief { pairs }
+    }
+
+    /// Returns the number of bits of the descriptors.
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    /// Returns `true` if the descriptors have no bits.
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    /// Describes the patch centred on (`x`, `y`), with the tests rotated by `angle`.
+    ///
+    /// Returns `None` if a test falls outside `image`.
+    pub fn describe_at(&self, image: &GrayImage, x: f32, y: f32, angle: f32) -> Option<BinaryDescriptor> {
+
+        let (sin, cos) = angle.sin_cos();
+        let rotate = |(u, v): (f32, f32)| ((x + cos * u - sin * v).round() as i32, (y + sin * u + cos * v).round() as i32);
+
+        let mut descriptor = BinaryDescriptor::new(self.pairs.len());
+
+        for (n, &(p, q)) in self.pairs.iter().enumerate() {
+            let (a, b) = match (box_sum(image, rotate(p)), box_sum(image, rotate(q))) {
+                (Some(a), Some(b)) => (a, b),
+                _ => return None,
+            };
+
+            descriptor.set(n, a < b);
+        }
+
+        Some(descriptor)
+    }
+
+    /// Describes a keypoint on its level of `pyramid`, with the tests rotated by `angle`.
+    pub fn describe_keypoint(&self, pyramid: &GrayPyramid, keypoint: &Keypoint, angle: f32)
+        -> Option<BinaryDescriptor>
+    {
+        let image = match pyramid.get(keypoint.level) {
+            Some(image) => image,
+            None => return None,
+        };
+
+        let (x, y) = keypoint.coordinates();
+        let scale = (1 << keypoint.level) as f32;
+
+        self.describe_at(image, x / scale, y / scale, angle)
+    }
+}
+
+impl Default for Brief {
+
+    /// Constructs a descriptor of 256 bits.
+    fn default() -> Brief {
+        Brief::new(256)
+    }
+}
+
+/// Returns the sum of the 5×5 box centred on (`x`, `y`), or `None` if the box isn't inside
+/// `image`.
+fn box_sum(image: &GrayImage, (x, y): (i32, i32)) -> Option<u32> {
+
+    let (width, height) = image.dimensions();
+
+    if x < BOX_RADIUS || y < BOX_RADIUS || x + BOX_RADIUS >= width as i32 || y + BOX_RADIUS >= height as i32 {
+        return None;
+    }
+
+    let pixels: &[u8] = &**image;
+    let stride = width as usize;
+
+    let sum = (y - BOX_RADIUS..y + BOX_RADIUS + 1).fold(0, |sum, v| {
+        let start = v as usize * stride + (x - BOX_RADIUS) as usize;
+
+        pixels[start..start + (2 * BOX_RADIUS + 1) as usize].iter().fold(sum, |sum, &p| sum + p as u32)
+    });
+
+    Some(sum)
+}
+
+impl Describe<GrayImage> for Brief {
+
+    type Descriptor = BinaryDescriptor;
+
+    /// Describes the keypoints on `image`, ignoring their level and orientation.
+    fn describe(&self, image: &GrayImage, keypoints: &[Keypoint]) -> Result<Vec<Option<BinaryDescriptor>>> {
+
+        Ok(keypoints.iter().map(|keypoint| {
+            let (x, y) = keypoint.coordinates();
+
+            self.describe_at(image, x, y, 0.0)
+        }).collect())
+    }
+}
+
+impl Describe<GrayPyramid> for Brief {
+
+    type Descriptor = BinaryDescriptor;
+
+    /// Describes the keypoints on their level of `pyramid`, ignoring their orientation.
+    fn describe(&self, pyramid: &GrayPyramid, keypoints: &[Keypoint]) -> Result<Vec<Option<BinaryDescriptor>>> {
+        Ok(keypoints.iter().map(|keypoint| self.describe_keypoint(pyramid, keypoint, 0.0)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Brief;
+    use utility::fixtures::texture;
+
+    #[test]
+    fn deterministic() {
+        let image = texture(96, 96, 0.0, 0.0);
+
+        let a = Brief::new(256).describe_at(&image, 48.0, 48.0, 0.0).unwrap();
+        let b = Brief::new(256).describe_at(&image, 48.0, 48.0, 0.0).unwrap();
+
+        assert_eq!(a.hamming(&b), 0);
+    }
+
+    #[test]
+    fn identical_patches() {
+        let brief = Brief::default();
+
+        // the same patch, at (40, 40) and at (50, 45) on the translated texture
+        let a = brief.describe_at(&texture(96, 96, 0.0, 0.0), 40.0, 40.0, 0.0).unwrap();
+        let b = brief.describe_at(&texture(96, 96, 10.0, 5.0), 50.0, 45.0, 0.0).unwrap();
+
+        assert_eq!(a.hamming(&b), 0);
+    }
+
+    #[test]
+    fn different_patches() {
+        let brief = Brief::default();
+
+        let a = brief.describe_at(&texture(96, 96, 0.0, 0.0), 48.0, 48.0, 0.0).unwrap();
+        let b = brief.describe_at(&texture(96, 96, 12.0, 12.0), 48.0, 48.0, 0.0).unwrap();
+
+        // about half of the tests of unrelated patches differ
+        assert!(a.hamming(&b) > 100, "distance: {}", a.hamming(&b));
+    }
+
+    #[test]
+    fn patches_near_the_border() {
+        let image = texture(96, 96, 0.0, 0.0);
+
+        assert!(Brief::default().describe_at(&image, 5.0, 48.0, 0.0).is_none());
+    }
+}
@@ -0,0 +1,191 @@
+use image::{gaussian_blur, sobel, Gray32FImage};
+use piston_image::{GrayImage, Luma};
+use std::f32;
+
+/// The value of edge pixels in the edge images.
+const EDGE: u8 = 255;
+
+/// The output of the `Canny` edge detector.
+#[derive(Clone, Debug)]
+pub struct EdgeMap {
+    /// The binary edge image: 255 on edges, 0 elsewhere.
+    pub edges: GrayImage,
+    /// The magnitude of the gradient of the smoothed image, in intensity levels per pixel.
+    pub magnitude: Gray32FImage,
+    /// The orientation of the gradient of the smoothed image, in radians in `(−π, π]`
+    /// (pointing towards the brighter side of the edges).
+    pub orientation: Gray32FImage,
+}
+
+/// Canny edge detector
+///
+/// 1) The image is smoothed by a Gaussian of standard deviation `sigma`.
+/// 2) The gradient is computed with the Sobel operator.
+/// 3) Edges are thinned by non-maximum suppression: a pixel is kept if its gradient magnitude
+///    is a maximum along the (quantized) direction of the gradient.
+/// 4) Hysteresis: the pixels above the `high` threshold are edges, and so are the pixels above
+///    the `low` threshold connected to them.
+///
+/// The thresholds apply to the gradient magnitude, in intensity levels per pixel.
+///
+/// ## References
+///
+/// * Canny, J. (1986). *A computational approach to edge detection*. IEEE Transactions on
+///   Pattern Analysis and Machine Intelligence, (6), 679-698.
+#[derive(Clone, Copy, Debug)]
+pub struct Canny {
+    low: f32,
+    high: f32,
+    sigma: f32,
+}
+
+impl Canny {
+
+    /// Constructs a detector with the hysteresis thresholds `low` and `high`, smoothing with
+    /// `σ = 1.4`.
+    pub fn new(low: f32, high: f32) -> Canny {
+        Canny { low: low.min(high), high: high.max(low), sigma: 1.4 }
+    }
+
+    /// Sets the standard deviation of the smoothing (0 disables the smoothing).
+    pub fn sigma(mut self, sigma: f32) -> Self {
+        self.sigma = sigma;
+        self
+    }
+
+    /// Returns the binary edge image of `image`.
+    pub fn edges(&self, image: &GrayImage) -> GrayImage {
+        self.edge_map(image).edges
+    }
+
+    /// Returns the edges of `image` and the gradient they were found on.
+    pub fn edge_map(&self, image: &GrayImage) -> EdgeMap {
+
+        let (width, height) = image.dimensions();
+
+        let smoothed = gaussian_blur(image, self.sigma);
+        let (gx, gy) = sobel(&smoothed);
+
+        let magnitude = Gray32FImage::from_fn(width, height, |x, y| {
+            Luma { data: [gx.get_pixel(x, y).data[0].hypot(gy.get_pixel(x, y).data[0])] }
+        });
+
+        let orientation = Gray32FImage::from_fn(width, height, |x, y| {
+            Luma { data: [gy.get_pixel(x, y).data[0].atan2(gx.get_pixel(x, y).data[0])] }
+        });
+
+        let thin = self.suppress(&magnitude, &orientation);
+        let edges = self.hysteresis(&thin, width, height);
+
+        EdgeMap { edges, magnitude, orientation }
+    }
+
+    /// Returns the magnitude of the pixels that are maxima along the gradient, 0 elsewhere.
+    fn suppress(&self, magnitude: &Gray32FImage, orientation: &Gray32FImage) -> Vec<f32> {
+
+        let (width, height) = magnitude.dimensions();
+        let mut thin = vec![0.0; (width * height) as usize];
+
+        if width < 3 || height < 3 {
+            return thin;
+        }
+
+        let at = |x: u32, y: u32| magnitude.get_pixel(x, y).data[0];
+
+        for y in 1..height - 1 {
+            for x in 1..width - 1 {
+                let m = at(x, y);
+
+                if m < self.low {
+                    continue;
+                }
+
+                // the direction of the gradient, quantized to 0°, 45°, 90° or 135°
+                let mut angle = orientation.get_pixel(x, y).data[0];
+
+                if angle < 0.0 {
+                    angle += f32::consts::PI;
+                }
+
+                let sector = ((angle / f32::consts::FRAC_PI_4).round() as u32) % 4;
+
+                let (before, after) = match sector {
+                    0 => (at(x - 1, y), at(x + 1, y)),
+                    1 => (at(x - 1, y - 1), at(x + 1, y + 1)),
+                    2 => (at(x, y - 1), at(x, y + 1)),
+                    _ => (at(x + 1, y - 1), at(x - 1, y + 1)),
+                };
+
+                // ties are broken towards one side so that plateaus yield one-pixel edges
+                if m > before && m >= after {
+                    thin[(y * width + x) as usize] = m;
+                }
+            }
+        }
+
+        thin
+    }
+
+    /// Keeps the strong pixels and the weak pixels connected to them.
+    fn hysteresis(&self, thin: &[f32], width: u32, height: u32) -> GrayImage {
+
+        let mut edges = vec![0; (width * height) as usize];
+
+        // a depth-first traversal of the weak pixels, from every strong pixel
+        let mut stack: Vec<(u32, u32)> = Vec::new();
+
+        for y in 0..height {
+            for x in 0..width {
+                let index = (y * width + x) as usize;
+
+                if thin[index] < self.high || edges[index] == EDGE {
+                    continue;
+                }
+
+                edges[index] = EDGE;
+                stack.push((x, y));
+
+                while let Some((u, v)) = stack.pop() {
+                    for nv in v.saturating_sub(1)..(v + 2).min(height) {
+                        for nu in u.saturating_sub(1)..(u + 2).min(width) {
+                            let neighbour = (nv * width + nu) as usize;
+
+                            if edges[neighbour] != EDGE && thin[neighbour] >= self.low && thin[neighbour] > 0.0 {
+                                edges[neighbour] = EDGE;
+                                stack.push((nu, nv));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        GrayImage::from_raw(width, height, edges).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use piston_image::{GrayImage, Luma};
+    use super::Canny;
+
+    #[test]
+    fn thin_edges_of_a_square() {
+        let image = GrayImage::from_fn(48, 48, |x, y| {
+            let inside = x >= 12 && x < 36 && y >= 12 && y < 36;
+
+            Luma { data: [if inside { 200 } else { 40 }] }
+        });
+
+        let edges = Canny::new(10.0, 30.0).edges(&image);
+
+        // one pixel wide on every side of the square
+        for &y in &[20, 24, 28] {
+            assert_eq!((0..48).filter(|&x| edges.get_pixel(x, y).data[0] == 255).count(), 2);
+        }
+
+        // nothing far from the border of the square
+        assert_eq!(edges.get_pixel(24, 24).data[0], 0);
+        assert_eq!(edges.get_pixel(4, 4).data[0], 0);
+    }
+}
@@ -0,0 +1,291 @@
+use core::feature::{Detect, Keypoint};
+use error::Result;
+use euclidean::Region2D as Region;
+use image::sobel;
+use piston_image::GrayImage;
+use std::cmp;
+use std::collections::HashMap;
+
+/// The cornerness measure of a `Corners` detector.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Score {
+    /// `det(M) − k tr(M)²`, with `k` usually in `[0.04, 0.06]`.
+    Harris { k: f32 },
+    /// The minimum eigenvalue of `M` (Shi-Tomasi).
+    MinEigenvalue,
+}
+
+/// Harris and Shi-Tomasi corner detector ("good features to track")
+///
+/// The structure tensor `M` (the spatial gradient matrix of Lucas-Kanade) is summed over a
+/// `window × window` neighbourhood of every pixel, and scored. The local maxima of the score
+/// that are above `quality ×` the best score are corners. Corners closer than `min_distance`
+/// to a stronger corner are suppressed, and only the `max_corners` strongest are kept.
+///
+/// The minimum eigenvalue is the quantity that makes a point trackable by Lucas-Kanade, so
+/// Shi-Tomasi corners are a natural choice of points for `PyramLk`.
+///
+/// ## References
+///
+/// * Harris, C., & Stephens, M. (1988). *A combined corner and edge detector*. In Alvey Vision
+///   Conference (Vol. 15, No. 50, pp. 147-151).
+/// * Shi, J., & Tomasi, C. (1994). *Good features to track*. In Proceedings of the IEEE
+///   Conference on Computer Vision and Pattern Recognition (pp. 593-600). IEEE.
+#[derive(Clone, Copy, Debug)]
+pub struct Corners {
+    score: Score,
+    window: u32,
+    quality: f32,
+    min_distance: f32,
+    max_corners: Option<usize>,
+}
+
+impl Corners {
+
+    /// Constructs a Harris detector.
+    pub fn harris(k: f32) -> Corners {
+        Corners { score: Score::Harris { k }, .. Corners::shi_tomasi() }
+    }
+
+    /// Constructs a Shi-Tomasi detector with a 3×3 window, a quality of 0.01, a minimum
+    /// distance of 10 pixels and no limit on the number of corners.
+    pub fn shi_tomasi() -> Corners {
+        Corners { score: Score::MinEigenvalue, window: 3, quality: 0.01, min_distance: 10.0, max_corners: None }
+    }
+
+    /// Sets the side of the window over which the structure tensor is summed. If `window` is 0,
+    /// 1 is chosen.
+    pub fn window(mut self, window: u32) -> Self {
+        self.window = cmp::max(1, window);
+        self
+    }
+
+    /// Sets the minimum score of a corner, relative to the best score of the image.
+    pub fn quality(mut self, quality: f32) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    /// Sets the minimum distance between two corners, in pixels.
+    pub fn min_distance(mut self, min_distance: f32) -> Self {
+        self.min_distance = min_distance;
+        self
+    }
+
+    /// Sets the maximum number of corners (the strongest are kept).
+    pub fn max_corners<N: Into<Option<usize>>>(mut self, max_corners: N) -> Self {
+        self.max_corners = max_corners.into();
+        self
+    }
+
+    /// Detects the corners inside `region`.
+    ///
+    /// # Returns
+    ///
+    /// The corners, sorted by decreasing response.
+    pub fn detect_region(&self, image: &GrayImage, region: Region) -> Vec<Keypoint> {
+
+        let (width, height) = image.dimensions();
+        let (x, y, w, h) = region.bounds();
+
+        // the Sobel operator and the window need a margin of `1 + window / 2` pixels
+        let margin = 1 + self.window as i64 / 2;
+
+        let x0 = cmp::max(margin, x.floor() as i64);
+        let y0 = cmp::max(margin, y.floor() as i64);
+        let x1 = cmp::min(width as i64 - margin, (x + w).ceil() as i64);
+        let y1 = cmp::min(height as i64 - margin, (y + h).ceil() as i64);
+
+        if x0 >= x1 || y0 >= y1 {
+            return Vec::new();
+        }
+
+        let (x0, y0, x1, y1) = (x0 as u32, y0 as u32, x1 as u32, y1 as u32);
+
+        let scores = self.scores(image, (x0 - margin as u32 + 1, y0 - margin as u32 + 1),
+            (x1 + margin as u32 - 1, y1 + margin as u32 - 1), (x0, y0, x1, y1));
+
+        let (roi_width, roi_height) = (x1 - x0, y1 - y0);
+        let score = |u: u32, v: u32| scores[(v * roi_width + u) as usize];
+
+        let best = scores.iter().cloned().fold(0.0, f32::max);
+
+        if best <= 0.0 {
+            return Vec::new();
+        }
+
+        let threshold = self.quality * best;
+
+        // local maxima of the score
+        let mut candidates = Vec::new();
+
+        for v in 0..roi_height {
+            for u in 0..roi_width {
+                let s = score(u, v);
+
+                if s < threshold || s <= 0.0 {
+                    continue;
+                }
+
+                let maximum = (v.saturating_sub(1)..cmp::min(roi_height, v + 2)).all(|vv| {
+                    (u.saturating_sub(1)..cmp::min(roi_width, u + 2)).all(|uu| score(uu, vv) <= s)
+                });
+
+                if maximum {
+                    candidates.push(Keypoint::new((x0 + u) as f32, (y0 + v) as f32, s));
+                }
+            }
+        }
+
+        candidates.sort_by(|a, b| b.response.partial_cmp(&a.response).unwrap());
+
+        suppress(candidates, self.min_distance, self.max_corners)
+    }
+
+    /// Computes the scores of the pixels of `[x0, x1) × [y0, y1)`, from the gradients of
+    /// `[gx0, gx1) × [gy0, gy1)`.
+    fn scores(&self, image: &GrayImage, (gx0, gy0): (u32, u32), (gx1, gy1): (u32, u32),
+              (x0, y0, x1, y1): (u32, u32, u32, u32)) -> Vec<f32>
+    {
+        let (gw, gh) = (gx1 - gx0, gy1 - gy0);
+
+        // the Sobel gradients of a copy of the rectangle with a margin of 1 pixel, so that the
+        // gradients of the rectangle don't depend on the replicated border
+        let (gx, gy) = sobel(&GrayImage::from_fn(gw + 2, gh + 2, |x, y| *image.get_pixel(gx0 + x - 1, gy0 + y - 1)));
+
+        // the products of the gradients: (Ix², IxIy, Iy²)
+        let mut products = vec![(0.0, 0.0, 0.0); (gw * gh) as usize];
+
+        for y in 0..gh {
+            for x in 0..gw {
+                let (ix, iy) = (gx.get_pixel(x + 1, y + 1).data[0], gy.get_pixel(x + 1, y + 1).data[0]);
+
+                products[(y * gw + x) as usize] = (ix * ix, ix * iy, iy * iy);
+            }
+        }
+
+        let half = self.window / 2;
+        let mut scores = Vec::with_capacity(((x1 - x0) * (y1 - y0)) as usize);
+
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let (mut a, mut b, mut c) = (0.0, 0.0, 0.0);
+
+                for wy in y - half..y - half + self.window {
+                    for wx in x - half..x - half + self.window {
+                        let (xx, xy, yy) = products[((wy - gy0) * gw + wx - gx0) as usize];
+
+                        a += xx;
+                        b += xy;
+                        c += yy;
+                    }
+                }
+
+                let score = match self.score {
+                    Score::Harris { k } => a * c - b * b - k * (a + c) * (a + c),
+                    Score::MinEigenvalue => (a + c) / 2.0 - (((a - c) / 2.0).powi(2) + b * b).sqrt(),
+                };
+
+                scores.push(score);
+            }
+        }
+
+        scores
+    }
+}
+
+impl Default for Corners {
+
+    fn default() -> Corners {
+        Corners::shi_tomasi()
+    }
+}
+
+impl Detect<GrayImage> for Corners {
+
+    /// Detects the corners of `image`, sorted by decreasing response.
+    fn detect(&self, image: &GrayImage) -> Result<Vec<Keypoint>> {
+        let (width, height) = image.dimensions();
+
+        Ok(self.detect_region(image, [0.0, 0.0, width as f64, height as f64].into()))
+    }
+}
+
+/// Keeps the keypoints (sorted by decreasing response) that are at least `min_distance` away
+/// from every stronger keypoint that was kept.
+fn suppress(keypoints: Vec<Keypoint>, min_distance: f32, max_corners: Option<usize>) -> Vec<Keypoint> {
+
+    let limit = max_corners.unwrap_or(keypoints.len());
+
+    if min_distance <= 0.0 {
+        return keypoints.into_iter().take(limit).collect();
+    }
+
+    // the kept keypoints are binned on a grid of `min_distance` cells, so that only the 3×3
+    // neighbouring cells must be searched
+    let cell = min_distance;
+    let mut grid: HashMap<(i64, i64), Vec<(f32, f32)>> = HashMap::new();
+
+    let mut kept = Vec::new();
+
+    for keypoint in keypoints {
+
+        if kept.len() >= limit {
+            break;
+        }
+
+        let (x, y) = keypoint.coordinates();
+        let (cx, cy) = ((x / cell).floor() as i64, (y / cell).floor() as i64);
+
+        let isolated = (cy - 1..cy + 2).all(|gy| {
+            (cx - 1..cx + 2).all(|gx| {
+                grid.get(&(gx, gy)).map_or(true, |points| {
+                    points.iter().all(|&(px, py)| (px - x).hypot(py - y) >= min_distance)
+                })
+            })
+        });
+
+        if isolated {
+            grid.entry((cx, cy)).or_insert_with(Vec::new).push((x, y));
+            kept.push(keypoint);
+        }
+    }
+
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use core::feature::Detect;
+    use piston_image::{GrayImage, Luma};
+    use super::Corners;
+
+    fn squares() -> GrayImage {
+        GrayImage::from_fn(64, 64, |x, y| {
+            let inside = (x >= 8 && x < 24 && y >= 8 && y < 24) || (x >= 40 && x < 56 && y >= 40 && y < 56);
+
+            Luma { data: [if inside { 200 } else { 50 }] }
+        })
+    }
+
+    #[test]
+    fn corners_of_squares() {
+        for corners in &[Corners::shi_tomasi(), Corners::harris(0.04)] {
+            let keypoints = corners.min_distance(5.0).detect(&squares()).unwrap();
+
+            assert_eq!(keypoints.len(), 8, "{:?}", keypoints);
+
+            for pair in keypoints.windows(2) {
+                assert!(pair[0].response >= pair[1].response);
+            }
+        }
+    }
+
+    #[test]
+    fn max_corners_and_region() {
+        let corners = Corners::shi_tomasi().min_distance(5.0);
+
+        assert_eq!(corners.max_corners(3).detect(&squares()).unwrap().len(), 3);
+        assert_eq!(corners.detect_region(&squares(), [32.0, 32.0, 32.0, 32.0].into()).len(), 4);
+    }
+}
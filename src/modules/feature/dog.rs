@@ -0,0 +1,345 @@
+use core::feature::{Detect, Keypoint};
+use error::Result;
+use image::{gaussian_blur, Gray32FImage, Pyramid};
+use piston_image::{GrayImage, Luma};
+use std::cmp;
+
+/// The blur assumed to be present in the input image (from the camera).
+const INITIAL_SIGMA: f32 = 0.5;
+
+/// The number of refinement steps of an extremum.
+const MAX_REFINEMENTS: usize = 5;
+
+/// The pixels ignored on the border of every DoG image.
+const BORDER: u32 = 5;
+
+/// Difference-of-Gaussians (DoG) blob detector
+///
+/// 1) The image (scaled to `[0, 1]`) is blurred by Gaussians of increasing standard deviation,
+///    `σ 2^(i / intervals)`, and the differences of consecutive blurred images (which
+///    approximate the scale-normalized Laplacian) are computed. Every `intervals` images, the
+///    blurred image is halved to start the next octave.
+/// 2) The extrema of the DoG over their 26 neighbours in space and scale are located with
+///    sub-pixel and sub-scale precision by fitting a quadratic.
+/// 3) Extrema whose contrast is below `contrast_threshold` are rejected, as are extrema on
+///    edges, whose ratio of principal curvatures is above `edge_threshold`.
+///
+/// The keypoints have a `scale` relative to `σ` (the blob has a standard deviation of
+/// `σ × scale` pixels on the image) and their `level` is their octave.
+///
+/// ## References
+///
+/// * Lowe, D. G. (2004). *Distinctive image features from scale-invariant keypoints*.
+///   International Journal of Computer Vision, 60(2), 91-110.
+#[derive(Clone, Copy, Debug)]
+pub struct Dog {
+    octaves: usize,
+    intervals: usize,
+    sigma: f32,
+    contrast_threshold: f32,
+    edge_threshold: f32,
+}
+
+impl Dog {
+
+    /// Constructs a detector with 4 octaves of 3 intervals, `σ = 1.6`, a contrast threshold of
+    /// 0.03 and an edge threshold of 10, as recommended by Lowe.
+    pub fn new() -> Dog {
+        Dog { octaves: 4, intervals: 3, sigma: 1.6, contrast_threshold: 0.03, edge_threshold: 10.0 }
+    }
+
+    /// Sets the number of octaves. If `octaves` is 0, 1 is chosen.
+    pub fn octaves(mut self, octaves: usize) -> Self {
+        self.octaves = cmp::max(1, octaves);
+        self
+    }
+
+    /// Sets the number of intervals per octave. If `intervals` is 0, 1 is chosen.
+    pub fn intervals(mut self, intervals: usize) -> Self {
+        self.intervals = cmp::max(1, intervals);
+        self
+    }
+
+    /// Sets the standard deviation of the first blurred image.
+    pub fn sigma(mut self, sigma: f32) -> Self {
+        self.sigma = sigma;
+        self
+    }
+
+    /// Sets the minimum absolute DoG value of an extremum (for an image in `[0, 1]`).
+    pub fn contrast_threshold(mut self, contrast_threshold: f32) -> Self {
+        self.contrast_threshold = contrast_threshold;
+        self
+    }
+
+    /// Sets the maximum ratio of the principal curvatures of an extremum.
+    pub fn edge_threshold(mut self, edge_threshold: f32) -> Self {
+        self.edge_threshold = edge_threshold;
+        self
+    }
+
+    /// Builds the blurred images of an octave, from its first image.
+    fn octave(&self, base: &Gray32FImage) -> Vec<Gray32FImage> {
+
+        let k = 2f32.powf(1.0 / self.intervals as f32);
+
+        let mut gaussians = vec![base.clone()];
+
+        for i in 1..self.intervals + 3 {
+            // blur incrementally: σᵢ² = σᵢ₋₁² + δ²
+            let previous = self.sigma * k.powi(i as i32 - 1);
+            let delta = previous * (k * k - 1.0).sqrt();
+
+            let blurred = gaussian_blur(&gaussians[i - 1], delta);
+
+            gaussians.push(blurred);
+        }
+
+        gaussians
+    }
+
+    /// Finds the extrema of the DoG images of an octave.
+    fn extrema(&self, dogs: &[Gray32FImage], octave: usize) -> Vec<Keypoint> {
+
+        let (width, height) = dogs[0].dimensions();
+        let mut keypoints = Vec::new();
+
+        if width <= 2 * BORDER || height <= 2 * BORDER {
+            return keypoints;
+        }
+
+        let prefilter = 0.5 * self.contrast_threshold / self.intervals as f32;
+
+        for i in 1..dogs.len() - 1 {
+            for y in BORDER..height - BORDER {
+                for x in BORDER..width - BORDER {
+                    let value = dogs[i].get_pixel(x, y).data[0];
+
+                    if value.abs() <= prefilter || !is_extremum(dogs, i, x, y, value) {
+                        continue;
+                    }
+
+                    if let Some(keypoint) = self.refine(dogs, i, x, y, octave) {
+                        keypoints.push(keypoint);
+                    }
+                }
+            }
+        }
+
+        keypoints
+    }
+
+    /// Refines the location of an extremum and filters it.
+    fn refine(&self, dogs: &[Gray32FImage], i: usize, x: u32, y: u32, octave: usize) -> Option<Keypoint> {
+
+        let (width, height) = dogs[0].dimensions();
+        let (mut i, mut x, mut y) = (i, x, y);
+
+        for _ in 0..MAX_REFINEMENTS {
+            // the sample of this iteration, so that `d` doesn't borrow `i`, `x` and `y`
+            let (si, sx, sy) = (i as i32, x as i32, y as i32);
+
+            let d = |di: i32, dx: i32, dy: i32| {
+                dogs[(si + di) as usize].get_pixel((sx + dx) as u32, (sy + dy) as u32).data[0]
+            };
+
+            let centre = d(0, 0, 0);
+
+            // gradient and Hessian by finite differences, over (x, y, scale)
+            let gradient = [
+                (d(0, 1, 0) - d(0, -1, 0)) / 2.0,
+                (d(0, 0, 1) - d(0, 0, -1)) / 2.0,
+                (d(1, 0, 0) - d(-1, 0, 0)) / 2.0,
+            ];
+
+            let dxx = d(0, 1, 0) + d(0, -1, 0) - 2.0 * centre;
+            let dyy = d(0, 0, 1) + d(0, 0, -1) - 2.0 * centre;
+            let dss = d(1, 0, 0) + d(-1, 0, 0) - 2.0 * centre;
+            let dxy = (d(0, 1, 1) - d(0, -1, 1) - d(0, 1, -1) + d(0, -1, -1)) / 4.0;
+            let dxs = (d(1, 1, 0) - d(1, -1, 0) - d(-1, 1, 0) + d(-1, -1, 0)) / 4.0;
+            let dys = (d(1, 0, 1) - d(1, 0, -1) - d(-1, 0, 1) + d(-1, 0, -1)) / 4.0;
+
+            let hessian = [[dxx, dxy, dxs], [dxy, dyy, dys], [dxs, dys, dss]];
+
+            // offset = −H⁻¹ ∇D
+            let offset = match solve(&hessian, &gradient) {
+                Some(solution) => [-solution[0], -solution[1], -solution[2]],
+                None => return None,
+            };
+
+            if offset.iter().all(|o| o.abs() < 0.5) {
+                let contrast = centre + 0.5 * (gradient[0] * offset[0] + gradient[1] * offset[1] + gradient[2] * offset[2]);
+
+                if contrast.abs() < self.contrast_threshold / self.intervals as f32 {
+                    return None;
+                }
+
+                // the ratio of the principal curvatures is bounded by tr² / det < (r + 1)² / r
+                let (trace, det) = (dxx + dyy, dxx * dyy - dxy * dxy);
+                let r = self.edge_threshold;
+
+                if det <= 0.0 || trace * trace * r >= (r + 1.0) * (r + 1.0) * det {
+                    return None;
+                }
+
+                let factor = (1 << octave) as f32;
+                let scale = factor * 2f32.powf((i as f32 + offset[2]) / self.intervals as f32);
+
+                let mut keypoint = Keypoint::new(
+                    (x as f32 + offset[0]) * factor, (y as f32 + offset[1]) * factor, contrast.abs());
+
+                keypoint.scale = scale;
+                keypoint.level = octave;
+
+                return Some(keypoint);
+            }
+
+            // move to the neighbouring sample, and start over (the bounds are checked before the
+            // casts, as the offsets may be negative)
+            let step = |v: i32, o: f32| v as i64 + o.round() as i64;
+
+            let (next_i, next_x, next_y) = (step(si, offset[2]), step(sx, offset[0]), step(sy, offset[1]));
+
+            if next_i < 1 || next_i + 1 >= dogs.len() as i64
+                || next_x < BORDER as i64 || next_x >= (width - BORDER) as i64
+                || next_y < BORDER as i64 || next_y >= (height - BORDER) as i64
+            {
+                return None;
+            }
+
+            i = next_i as usize;
+            x = next_x as u32;
+            y = next_y as u32;
+        }
+
+        None
+    }
+}
+
+impl Default for Dog {
+
+    fn default() -> Dog {
+        Dog::new()
+    }
+}
+
+/// Returns `true` if `value` is larger (or smaller) than its 26 neighbours.
+fn is_extremum(dogs: &[Gray32FImage], i: usize, x: u32, y: u32, value: f32) -> bool {
+
+    let neighbours = || {
+        (i - 1..i + 2).flat_map(move |j| {
+            (y - 1..y + 2).flat_map(move |v| (x - 1..x + 2).map(move |u| (j, u, v)))
+        }).filter(move |&(j, u, v)| (j, u, v) != (i, x, y))
+    };
+
+    if value > 0.0 {
+        neighbours().all(|(j, u, v)| value > dogs[j].get_pixel(u, v).data[0])
+    } else {
+        neighbours().all(|(j, u, v)| value < dogs[j].get_pixel(u, v).data[0])
+    }
+}
+
+/// Solves `a x = b`, or returns `None` if `a` is singular.
+fn solve(a: &[[f32; 3]; 3], b: &[f32; 3]) -> Option<[f32; 3]> {
+
+    let det3 = |m: &[[f32; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+
+    let det = det3(a);
+
+    if det.abs() < 1e-12 {
+        return None;
+    }
+
+    // Cramer's rule
+    let mut x = [0.0; 3];
+
+    for (c, value) in x.iter_mut().enumerate() {
+        let mut m = *a;
+
+        for r in 0..3 {
+            m[r][c] = b[r];
+        }
+
+        *value = det3(&m) / det;
+    }
+
+    Some(x)
+}
+
+/// Keeps every other pixel of `image`.
+fn decimate(image: &Gray32FImage) -> Gray32FImage {
+
+    let (width, height) = image.dimensions();
+
+    Gray32FImage::from_fn(cmp::max(1, width / 2), cmp::max(1, height / 2), |x, y| *image.get_pixel(2 * x, 2 * y))
+}
+
+impl Detect<GrayImage> for Dog {
+
+    /// Detects the blobs of `image`, sorted by decreasing contrast.
+    fn detect(&self, image: &GrayImage) -> Result<Vec<Keypoint>> {
+
+        let normalized = Gray32FImage::from_fn(image.width(), image.height(), |x, y| {
+            Luma { data: [image.get_pixel(x, y).data[0] as f32 / 255.0] }
+        });
+
+        let sigma = self.sigma;
+        let base = gaussian_blur(&normalized, (sigma * sigma - INITIAL_SIGMA * INITIAL_SIGMA).max(0.0).sqrt());
+
+        // the first image of the next octave has twice the blur of the first image of an
+        // octave, i.e., an extra blur of σ√3, before it is decimated
+        let bases = Pyramid::new(base, self.octaves, |image| decimate(&gaussian_blur(image, sigma * 3f32.sqrt())));
+
+        let mut keypoints = Vec::new();
+
+        for (octave, base) in bases.iter().enumerate() {
+            let gaussians = self.octave(base);
+
+            let dogs: Vec<Gray32FImage> = gaussians.windows(2).map(|pair| {
+                Gray32FImage::from_fn(pair[0].width(), pair[0].height(), |x, y| {
+                    Luma { data: [pair[1].get_pixel(x, y).data[0] - pair[0].get_pixel(x, y).data[0]] }
+                })
+            }).collect();
+
+            keypoints.extend(self.extrema(&dogs, octave));
+        }
+
+        keypoints.sort_by(|a, b| b.response.partial_cmp(&a.response).unwrap());
+
+        Ok(keypoints)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::feature::Detect;
+    use piston_image::{GrayImage, Luma};
+    use super::Dog;
+
+    fn blob(radius: f32) -> GrayImage {
+        GrayImage::from_fn(96, 96, |x, y| {
+            let d2 = (x as f32 - 48.0).powi(2) + (y as f32 - 48.0).powi(2);
+
+            Luma { data: [(40.0 + 180.0 * (-d2 / (2.0 * radius * radius)).exp()) as u8] }
+        })
+    }
+
+    #[test]
+    fn blob_scale() {
+        let small = Dog::new().detect(&blob(3.0)).unwrap();
+        let large = Dog::new().detect(&blob(6.0)).unwrap();
+
+        let (x, y) = small[0].coordinates();
+
+        assert!((x - 48.0).abs() < 1.0 && (y - 48.0).abs() < 1.0);
+
+        // the strongest response is at the scale of the blob
+        let ratio = large[0].scale / small[0].scale;
+
+        assert!(ratio > 1.5 && ratio < 2.5, "scales: {} and {}", small[0].scale, large[0].scale);
+    }
+}
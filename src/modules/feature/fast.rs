@@ -0,0 +1,231 @@
+use core::feature::{Detect, Keypoint};
+use error::Result;
+use image::GrayPyramid;
+use piston_image::GrayImage;
+use std::cmp;
+
+/// The offsets of the 16 pixels of the Bresenham circle of radius 3, clockwise from the top.
+const CIRCLE: [(i32, i32); 16] = [
+    (0, -3), (1, -3), (2, -2), (3, -1), (3, 0), (3, 1), (2, 2), (1, 3),
+    (0, 3), (-1, 3), (-2, 2), (-3, 1), (-3, 0), (-3, -1), (-2, -2), (-1, -3),
+];
+
+/// FAST corner detector
+///
+/// A pixel `p` is a corner if `arc` contiguous pixels of the circle of radius 3 around it are
+/// all brighter than `p + threshold`, or all darker than `p − threshold`. The response of a
+/// corner is the sum of the absolute differences (minus `threshold`) between `p` and the
+/// brighter (or darker) pixels of the circle, whichever is larger.
+///
+/// With non-maximum suppression, only the corners whose response is the maximum of their 3×3
+/// neighbourhood are kept (ties are broken in raster order).
+///
+/// `Fast` detects the corners of a `GrayImage`, or of every level of a `GrayPyramid`; the
+/// keypoints found on level `l` have a `scale` of `2^l`.
+///
+/// ## References
+///
+/// * Rosten, E., & Drummond, T. (2006). *Machine learning for high-speed corner detection*. In
+///   European Conference on Computer Vision (pp. 430-443). Springer Berlin Heidelberg.
+#[derive(Clone, Copy, Debug)]
+pub struct Fast {
+    threshold: u8,
+    arc: usize,
+    nonmax_suppression: bool,
+}
+
+impl Fast {
+
+    /// Constructs a FAST-9 detector (the most repeatable variant), with non-maximum
+    /// suppression.
+    pub fn new(threshold: u8) -> Fast {
+        Fast { threshold, arc: 9, nonmax_suppression: true }
+    }
+
+    /// Constructs a FAST-12 detector, with non-maximum suppression.
+    pub fn fast12(threshold: u8) -> Fast {
+        Fast { threshold, arc: 12, nonmax_suppression: true }
+    }
+
+    /// Sets the length of the contiguous arc, clamped to `[9, 16]`.
+    pub fn arc(mut self, arc: usize) -> Self {
+        self.arc = cmp::max(9, cmp::min(16, arc));
+        self
+    }
+
+    /// Enables or disables non-maximum suppression.
+    pub fn nonmax_suppression(mut self, nonmax_suppression: bool) -> Self {
+        self.nonmax_suppression = nonmax_suppression;
+        self
+    }
+
+    /// Returns the response of the pixel at (`x`, `y`), or 0 if it isn't a corner.
+    fn response(&self, image: &GrayImage, x: u32, y: u32) -> f32 {
+
+        let pixels: &[u8] = &**image;
+        let stride = image.width() as i32;
+        let at = |(dx, dy): (i32, i32)| pixels[((y as i32 + dy) * stride + x as i32 + dx) as usize] as i32;
+
+        let p = at((0, 0));
+        let t = self.threshold as i32;
+
+        // -1: darker, 0: similar, 1: brighter
+        let class = |value: i32| if value > p + t { 1 } else if value < p - t { -1 } else { 0 };
+
+        // any arc of `arc` pixels covers at least `arc / 4` of the 4 compass points
+        let (mut brighter, mut darker) = (0, 0);
+
+        for &k in &[0, 4, 8, 12] {
+            match class(at(CIRCLE[k])) {
+                1 => brighter += 1,
+                -1 => darker += 1,
+                _ => (),
+            }
+        }
+
+        if brighter < self.arc / 4 && darker < self.arc / 4 {
+            return 0.0;
+        }
+
+        let mut values = [0; 16];
+        let mut classes = [0; 16];
+
+        for k in 0..16 {
+            values[k] = at(CIRCLE[k]);
+            classes[k] = class(values[k]);
+        }
+
+        let corner = [1, -1].iter().any(|&c| {
+            let mut run = 0;
+
+            (0..16 + self.arc - 1).any(|k| {
+                run = if classes[k % 16] == c { run + 1 } else { 0 };
+                run >= self.arc
+            })
+        });
+
+        if !corner {
+            return 0.0;
+        }
+
+        let (mut bright, mut dark) = (0, 0);
+
+        for k in 0..16 {
+            match classes[k] {
+                1 => bright += values[k] - p - t,
+                -1 => dark += p - values[k] - t,
+                _ => (),
+            }
+        }
+
+        cmp::max(bright, dark) as f32
+    }
+
+    /// Detects the corners of a single image, reporting them on the bottom of the pyramid.
+    fn detect_level(&self, image: &GrayImage, level: usize) -> Vec<Keypoint> {
+
+        let (width, height) = image.dimensions();
+
+        if width < 7 || height < 7 {
+            return Vec::new();
+        }
+
+        let mut responses = vec![0.0; (width * height) as usize];
+
+        for y in 3..height - 3 {
+            for x in 3..width - 3 {
+                responses[(y * width + x) as usize] = self.response(image, x, y);
+            }
+        }
+
+        let scale = (1 << level) as f32;
+        let mut keypoints = Vec::new();
+
+        for y in 3..height - 3 {
+            for x in 3..width - 3 {
+                let response = responses[(y * width + x) as usize];
+
+                if response <= 0.0 {
+                    continue;
+                }
+
+                if self.nonmax_suppression {
+                    // ties are resolved in favour of the last pixel in raster order
+                    let neighbours = [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+
+                    let maximum = neighbours.iter().enumerate().all(|(n, &(dx, dy))| {
+                        let other = responses[((y as i32 + dy) as u32 * width + (x as i32 + dx) as u32) as usize];
+
+                        if n < 4 { response >= other } else { response > other }
+                    });
+
+                    if !maximum {
+                        continue;
+                    }
+                }
+
+                let mut keypoint = Keypoint::new(x as f32 * scale, y as f32 * scale, response);
+
+                keypoint.scale = scale;
+                keypoint.level = level;
+
+                keypoints.push(keypoint);
+            }
+        }
+
+        keypoints
+    }
+}
+
+impl Detect<GrayImage> for Fast {
+
+    fn detect(&self, image: &GrayImage) -> Result<Vec<Keypoint>> {
+        Ok(self.detect_level(image, 0))
+    }
+}
+
+impl Detect<GrayPyramid> for Fast {
+
+    /// Detects the corners of every level of the pyramid.
+    fn detect(&self, pyramid: &GrayPyramid) -> Result<Vec<Keypoint>> {
+        Ok(pyramid.iter().enumerate().flat_map(|(level, image)| self.detect_level(image, level)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::feature::Detect;
+    use image::GrayPyramid;
+    use piston_image::{GrayImage, Luma};
+    use super::Fast;
+
+    fn square() -> GrayImage {
+        GrayImage::from_fn(64, 64, |x, y| {
+            let inside = x >= 16 && x < 48 && y >= 16 && y < 48;
+
+            Luma { data: [if inside { 200 } else { 50 }] }
+        })
+    }
+
+    #[test]
+    fn corners_of_a_square() {
+        let keypoints = Fast::new(20).detect(&square()).unwrap();
+
+        assert!(keypoints.len() >= 4);
+
+        for keypoint in &keypoints {
+            let (x, y) = keypoint.coordinates();
+            let near = |v: f32| (v - 16.0).abs() <= 3.0 || (v - 47.0).abs() <= 3.0;
+
+            assert!(near(x) && near(y), "({}, {}) is not a corner", x, y);
+        }
+    }
+
+    #[test]
+    fn flat_image() {
+        let image = GrayImage::from_pixel(32, 32, Luma { data: [100] });
+
+        assert!(Fast::new(10).detect(&image).unwrap().is_empty());
+        assert!(Fast::fast12(10).detect(&GrayPyramid::build(&image, 3)).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,330 @@
+use euclidean::Region2D as Region;
+use modules::detection::window;
+use piston_image::GrayImage;
+use std::{cmp, f32};
+
+/// The regularization of the block normalization.
+const EPSILON: f32 = 1e-3;
+
+/// The largest component of an L2-normalized block (the "Hys" of L2-Hys).
+const CLIP: f32 = 0.2;
+
+/// Histogram of oriented gradients (HOG)
+///
+/// The region is divided in square cells of `cell × cell` pixels. Every pixel votes for the
+/// orientation of its gradient in the histogram of its cell, with a weight equal to the
+/// magnitude of the gradient (split between the two nearest bins). The histograms of blocks of
+/// `block × block` cells, spaced by `block_stride` cells, are normalized with L2-Hys
+/// (L2-normalized, clipped to 0.2 and L2-normalized again) and concatenated.
+///
+/// The orientations are unsigned (in `[0, π)`) unless `signed` is set.
+///
+/// The descriptor of a single region is computed with `describe_region`. To describe many
+/// overlapping windows (e.g., for sliding window detection), `cells` computes the cell
+/// histograms of the whole image once, and the descriptor of every window is assembled from
+/// them.
+///
+/// ## References
+///
+/// * Dalal, N., & Triggs, B. (2005). *Histograms of oriented gradients for human detection*. In
+///   IEEE Computer Society Conference on Computer Vision and Pattern Recognition (Vol. 1,
+///   pp. 886-893). IEEE.
+#[derive(Clone, Copy, Debug)]
+pub struct Hog {
+    cell: u32,
+    block: u32,
+    bins: usize,
+    block_stride: u32,
+    signed: bool,
+}
+
+impl Hog {
+
+    /// Constructs a HOG descriptor.
+    ///
+    /// # Arguments
+    ///
+    /// * `cell` - The side of the cells, in pixels. If `cell` is 0, 1 is chosen.
+    /// * `block` - The side of the blocks, in cells. If `block` is 0, 1 is chosen.
+    /// * `bins` - The number of orientation bins. If `bins` is 0, 1 is chosen.
+    pub fn new(cell: u32, block: u32, bins: usize) -> Hog {
+        Hog { cell: cmp::max(1, cell), block: cmp::max(1, block), bins: cmp::max(1, bins), block_stride: 1, signed: false }
+    }
+
+    /// Sets the spacing of the blocks, in cells. If `block_stride` is 0, 1 is chosen.
+    pub fn block_stride(mut self, block_stride: u32) -> Self {
+        self.block_stride = cmp::max(1, block_stride);
+        self
+    }
+
+    /// Uses signed orientations (in `[0, 2π)`) rather than unsigned orientations.
+    pub fn signed(mut self, signed: bool) -> Self {
+        self.signed = signed;
+        self
+    }
+
+    /// Returns the length of the descriptor of a window of `cells_x × cells_y` cells, or 0 if
+    /// the window is smaller than a block.
+    pub fn len(&self, cells_x: u32, cells_y: u32) -> usize {
+        let (blocks_x, blocks_y) = self.blocks(cells_x, cells_y);
+
+        (blocks_x * blocks_y * self.block * self.block) as usize * self.bins
+    }
+
+    /// Describes `region`.
+    ///
+    /// The region is divided in `⌊width / cell⌋ × ⌊height / cell⌋` cells, from its top-left
+    /// corner.
+    ///
+    /// Returns `None` if `region` isn't inside `image`, or is smaller than a block.
+    pub fn describe_region(&self, image: &GrayImage, region: Region) -> Option<Vec<f32>> {
+
+        let (x, y, width, height) = region.bounds();
+        let (image_width, image_height) = image.dimensions();
+
+        if x < 0.0 || y < 0.0 || x + width > image_width as f64 || y + height > image_height as f64 {
+            return None;
+        }
+
+        let (cells_x, cells_y) = (width as u32 / self.cell, height as u32 / self.cell);
+
+        if cells_x < self.block || cells_y < self.block {
+            return None;
+        }
+
+        let histograms = self.histograms(image, (x as u32, y as u32), (cells_x, cells_y));
+
+        Some(self.normalize_blocks(&histograms, cells_x, (0, 0), (cells_x, cells_y)))
+    }
+
+    /// Computes the cell histograms of the whole image.
+    pub fn cells(&self, image: &GrayImage) -> HogCells {
+
+        let (width, height) = image.dimensions();
+        let (cells_x, cells_y) = (width / self.cell, height / self.cell);
+
+        HogCells { hog: *self, cells_x, cells_y, histograms: self.histograms(image, (0, 0), (cells_x, cells_y)) }
+    }
+
+    fn blocks(&self, cells_x: u32, cells_y: u32) -> (u32, u32) {
+
+        if cells_x < self.block || cells_y < self.block {
+            return (0, 0);
+        }
+
+        ((cells_x - self.block) / self.block_stride + 1, (cells_y - self.block) / self.block_stride + 1)
+    }
+
+    /// Computes the histograms of `cells_x × cells_y` cells, from (`x0`, `y0`).
+    fn histograms(&self, image: &GrayImage, (x0, y0): (u32, u32), (cells_x, cells_y): (u32, u32)) -> Vec<f32> {
+
+        let (width, height) = image.dimensions();
+        let pixels: &[u8] = &**image;
+
+        // the gradient is computed with centred differences, clamped at the borders of the image
+        let px = |x: u32, y: u32| pixels[(y * width + x) as usize] as f32;
+
+        let range = if self.signed { 2.0 * f32::consts::PI } else { f32::consts::PI };
+        let bin_width = range / self.bins as f32;
+
+        let mut histograms = vec![0.0; (cells_x * cells_y) as usize * self.bins];
+
+        for v in 0..cells_y * self.cell {
+            for u in 0..cells_x * self.cell {
+                let (x, y) = (x0 + u, y0 + v);
+
+                let gx = px(cmp::min(width - 1, x + 1), y) - px(x.saturating_sub(1), y);
+                let gy = px(x, cmp::min(height - 1, y + 1)) - px(x, y.saturating_sub(1));
+
+                let magnitude = gx.hypot(gy);
+
+                if magnitude == 0.0 {
+                    continue;
+                }
+
+                let mut angle = gy.atan2(gx);
+
+                if angle < 0.0 {
+                    angle += 2.0 * f32::consts::PI;
+                }
+
+                if !self.signed && angle >= f32::consts::PI {
+                    angle -= f32::consts::PI;
+                }
+
+                // interpolate between the two nearest bin centres
+                let position = angle / bin_width - 0.5;
+                let lower = position.floor();
+                let fraction = position - lower;
+
+                let bins = self.bins as i64;
+                let bin_0 = ((lower as i64 % bins + bins) % bins) as usize;
+                let bin_1 = (bin_0 + 1) % self.bins;
+
+                let cell = ((v / self.cell) * cells_x + u / self.cell) as usize * self.bins;
+
+                histograms[cell + bin_0] += magnitude * (1.0 - fraction);
+                histograms[cell + bin_1] += magnitude * fraction;
+            }
+        }
+
+        histograms
+    }
+
+    /// Normalizes and concatenates the blocks of the window of `cells_x × cells_y` cells at
+    /// (`cx`, `cy`) of a grid of cells of width `stride`.
+    fn normalize_blocks(&self, histograms: &[f32], stride: u32, (cx, cy): (u32, u32),
+                        (cells_x, cells_y): (u32, u32)) -> Vec<f32>
+    {
+        let (blocks_x, blocks_y) = self.blocks(cells_x, cells_y);
+        let mut descriptor = Vec::with_capacity(self.len(cells_x, cells_y));
+
+        for by in 0..blocks_y {
+            for bx in 0..blocks_x {
+                let start = descriptor.len();
+
+                for y in 0..self.block {
+                    for x in 0..self.block {
+                        let cell_x = cx + bx * self.block_stride + x;
+                        let cell_y = cy + by * self.block_stride + y;
+                        let cell = (cell_y * stride + cell_x) as usize * self.bins;
+
+                        descriptor.extend_from_slice(&histograms[cell..cell + self.bins]);
+                    }
+                }
+
+                l2_hys(&mut descriptor[start..]);
+            }
+        }
+
+        descriptor
+    }
+}
+
+impl Default for Hog {
+
+    /// Constructs the descriptor of Dalal and Triggs: 8×8 cells, 2×2 blocks and 9 bins.
+    fn default() -> Hog {
+        Hog::new(8, 2, 9)
+    }
+}
+
+fn l2_hys(block: &mut [f32]) {
+
+    let normalize = |block: &mut [f32]| {
+        let norm = (block.iter().map(|v| v * v).sum::<f32>() + EPSILON * EPSILON).sqrt();
+
+        for v in block.iter_mut() {
+            *v /= norm;
+        }
+    };
+
+    normalize(block);
+
+    for v in block.iter_mut() {
+        *v = v.min(CLIP);
+    }
+
+    normalize(block);
+}
+
+/// The cell histograms of an image, computed by `Hog::cells`.
+#[derive(Clone, Debug)]
+pub struct HogCells {
+    hog: Hog,
+    cells_x: u32,
+    cells_y: u32,
+    histograms: Vec<f32>,
+}
+
+impl HogCells {
+
+    /// Returns the number of cells along each axis.
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.cells_x, self.cells_y)
+    }
+
+    /// Describes the window of `cells_x × cells_y` cells whose top-left cell is (`cx`, `cy`).
+    ///
+    /// Returns `None` if the window isn't inside the image, or is smaller than a block.
+    pub fn describe(&self, (cx, cy): (u32, u32), (cells_x, cells_y): (u32, u32)) -> Option<Vec<f32>> {
+
+        if cx + cells_x > self.cells_x || cy + cells_y > self.cells_y {
+            return None;
+        }
+
+        if cells_x < self.hog.block || cells_y < self.hog.block {
+            return None;
+        }
+
+        Some(self.hog.normalize_blocks(&self.histograms, self.cells_x, (cx, cy), (cells_x, cells_y)))
+    }
+
+    /// Describes every window of `cells_x × cells_y` cells, spaced by `stride` cells.
+    ///
+    /// The windows are slid over the grid of cells by `detection::window`. If `stride` is 0, 1
+    /// is chosen.
+    ///
+    /// # Returns
+    ///
+    /// The windows (in pixels), in raster order, and their descriptors.
+    pub fn windows(&self, (cells_x, cells_y): (u32, u32), stride: u32) -> Vec<(Region, Vec<f32>)> {
+
+        let cell = self.hog.cell as f64;
+
+        // `window` never reaches the last column and row of its grid: slide over a grid one
+        // cell larger, so that the windows can end on the last cells
+        let grid = (self.cells_x + 1, self.cells_y + 1);
+
+        window(grid, (cells_x as f64, cells_y as f64), (stride, stride), 1.0).filter_map(|cells| {
+            let (cx, cy, _, _) = cells.bounds();
+            let (cx, cy) = (cx as u32, cy as u32);
+
+            self.describe((cx, cy), (cells_x, cells_y)).map(|descriptor| {
+                let region = [cx as f64 * cell, cy as f64 * cell, cells_x as f64 * cell, cells_y as f64 * cell];
+
+                (region.into(), descriptor)
+            })
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use piston_image::{GrayImage, Luma};
+    use super::Hog;
+
+    fn stripes() -> GrayImage {
+        GrayImage::from_fn(64, 64, |x, _| Luma { data: [if (x / 4) % 2 == 0 { 50 } else { 200 }] })
+    }
+
+    #[test]
+    fn dense_matches_region() {
+        let hog = Hog::default();
+        let image = stripes();
+
+        let cells = hog.cells(&image);
+        let windows = cells.windows((4, 4), 2);
+
+        assert_eq!(windows.len(), 9);
+        // the last window ends on the last cells
+        assert_eq!(windows[8].0.bounds(), (32.0, 32.0, 32.0, 32.0));
+
+        for &(region, ref dense) in &windows {
+            let single = hog.describe_region(&image, region).unwrap();
+
+            assert_eq!(dense.len(), hog.len(4, 4));
+            assert!(dense.iter().zip(&single).all(|(a, b)| (a - b).abs() < 1e-5));
+        }
+    }
+
+    #[test]
+    fn vertical_edges_vote_horizontal_gradients() {
+        let descriptor = Hog::new(8, 1, 9).describe_region(&stripes(), [8.0, 8.0, 8.0, 8.0].into()).unwrap();
+
+        // the gradients are horizontal: the bins around 0 (and π) hold all the weight
+        let strongest = (0..9).fold(0, |best, bin| if descriptor[bin] > descriptor[best] { bin } else { best });
+
+        assert!(strongest == 0 || strongest == 8);
+    }
+}
@@ -0,0 +1,609 @@
+use piston_image::GrayImage;
+use rand::{self, Rng};
+use std::{cmp, f32};
+use super::EdgeMap;
+use utility::plane_euclidean::Point;
+
+/// A line in normal form: the points (`x`, `y`) such that `x cos θ + y sin θ = ρ`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Line {
+    /// The signed distance from the origin (the top-left corner of the image), in pixels.
+    pub rho: f32,
+    /// The angle of the normal of the line, in radians in `[0, π)`.
+    pub theta: f32,
+    /// The number of edge pixels on the line.
+    pub votes: u32,
+}
+
+impl Line {
+
+    /// Returns the segment of the line inside an image of `width × height` pixels, or `None` if
+    /// the line doesn't cross the image.
+    pub fn clip(&self, width: u32, height: u32) -> Option<Segment> {
+
+        let (cos, sin) = (self.theta.cos(), self.theta.sin());
+        let (max_x, max_y) = (width as f32 - 1.0, height as f32 - 1.0);
+
+        // the intersections with the 4 sides of the image
+        let mut ends: Vec<(f32, f32)> = Vec::with_capacity(4);
+
+        if sin.abs() > 1e-6 {
+            for &x in &[0.0, max_x] {
+                let y = (self.rho - x * cos) / sin;
+
+                if y >= 0.0 && y <= max_y {
+                    ends.push((x, y));
+                }
+            }
+        }
+
+        if cos.abs() > 1e-6 {
+            for &y in &[0.0, max_y] {
+                let x = (self.rho - y * sin) / cos;
+
+                if x >= 0.0 && x <= max_x {
+                    ends.push((x, y));
+                }
+            }
+        }
+
+        // the two most distant intersections (corners are found twice)
+        let mut best: Option<((f32, f32), (f32, f32))> = None;
+
+        for (n, &a) in ends.iter().enumerate() {
+            for &b in &ends[n + 1..] {
+                let length = |&(p, q): &((f32, f32), (f32, f32))| (p.0 - q.0).hypot(p.1 - q.1);
+
+                if best.as_ref().map_or(true, |pair| length(&(a, b)) > length(pair)) {
+                    best = Some((a, b));
+                }
+            }
+        }
+
+        best.map(|(a, b)| Segment { start: [a.0, a.1].into(), end: [b.0, b.1].into() })
+    }
+}
+
+/// A line segment.
+#[derive(Clone, Copy, Debug)]
+pub struct Segment {
+    /// The first end of the segment.
+    pub start: Point<f32>,
+    /// The second end of the segment.
+    pub end: Point<f32>,
+}
+
+impl Segment {
+
+    /// Returns the length of the segment.
+    pub fn length(&self) -> f32 {
+        let (x0, y0) = coordinates(&self.start);
+        let (x1, y1) = coordinates(&self.end);
+
+        (x1 - x0).hypot(y1 - y0)
+    }
+}
+
+/// A circle.
+#[derive(Clone, Copy, Debug)]
+pub struct Circle {
+    /// The centre of the circle.
+    pub centre: Point<f32>,
+    /// The radius of the circle, in pixels.
+    pub radius: f32,
+    /// The number of edge pixels on the circle.
+    pub votes: u32,
+}
+
+/// The Hough transform for lines
+///
+/// Every edge pixel votes for the lines, in normal form, that pass through it. The lines that
+/// get at least `threshold` votes, and more than their neighbours in the `(ρ, θ)` space, are
+/// detected by `lines`.
+///
+/// `segments` implements the progressive probabilistic Hough transform: edge pixels vote in a
+/// random order, and as soon as a line gets `threshold` votes, the segments of edge pixels
+/// along it (of at least `min_length` pixels, with gaps of at most `max_gap` pixels) are
+/// extracted and their pixels withdraw their votes. It is much faster on large edge maps, and
+/// finds segments rather than infinite lines.
+///
+/// ## References
+///
+/// * Duda, R. O., & Hart, P. E. (1972). *Use of the Hough transformation to detect lines and
+///   curves in pictures*. Communications of the ACM, 15(1), 11-15.
+/// * Matas, J., Galambos, C., & Kittler, J. (2000). *Robust detection of lines using the
+///   progressive probabilistic Hough transform*. Computer Vision and Image Understanding,
+///   78(1), 119-137.
+#[derive(Clone, Copy, Debug)]
+pub struct HoughLines {
+    threshold: u32,
+    rho_resolution: f32,
+    theta_resolution: f32,
+    min_length: f32,
+    max_gap: f32,
+}
+
+impl HoughLines {
+
+    /// Constructs a transform detecting the lines of at least `threshold` pixels, with a
+    /// resolution of 1 pixel and 1 degree, a minimum segment length of 30 pixels and a maximum
+    /// gap of 5 pixels.
+    pub fn new(threshold: u32) -> HoughLines {
+
+        HoughLines {
+            threshold: cmp::max(1, threshold),
+            rho_resolution: 1.0,
+            theta_resolution: f32::consts::PI / 180.0,
+            min_length: 30.0,
+            max_gap: 5.0,
+        }
+    }
+
+    /// Sets the resolution of the accumulator.
+    ///
+    /// # Arguments
+    ///
+    /// * `rho` - The resolution of the distance, in pixels.
+    /// * `theta` - The resolution of the angle, in radians.
+    pub fn resolution(mut self, rho: f32, theta: f32) -> Self {
+        self.rho_resolution = rho;
+        self.theta_resolution = theta;
+        self
+    }
+
+    /// Sets the minimum length of the segments, in pixels.
+    pub fn min_length(mut self, min_length: f32) -> Self {
+        self.min_length = min_length;
+        self
+    }
+
+    /// Sets the maximum gap between two pixels of a segment, in pixels.
+    pub fn max_gap(mut self, max_gap: f32) -> Self {
+        self.max_gap = max_gap;
+        self
+    }
+
+    /// Detects the lines of an edge image (see `Canny`), whose non-zero pixels are edges.
+    ///
+    /// # Returns
+    ///
+    /// The lines, sorted by decreasing votes.
+    pub fn lines(&self, edges: &GrayImage) -> Vec<Line> {
+
+        let mut accumulator = Accumulator::new(self, edges.dimensions());
+
+        for (x, y, pixel) in edges.enumerate_pixels() {
+            if pixel.data[0] != 0 {
+                accumulator.vote(x, y, 1);
+            }
+        }
+
+        let mut lines = Vec::new();
+
+        for t in 0..accumulator.thetas {
+            for r in 0..accumulator.rhos {
+                let votes = accumulator.get(t as i64, r as i64);
+
+                if votes < self.threshold as i32 {
+                    continue;
+                }
+
+                // a local maximum of its 4 neighbours (ties are resolved towards the origin)
+                let maximum = votes > accumulator.get(t as i64 - 1, r as i64)
+                    && votes > accumulator.get(t as i64, r as i64 - 1)
+                    && votes >= accumulator.get(t as i64 + 1, r as i64)
+                    && votes >= accumulator.get(t as i64, r as i64 + 1);
+
+                if maximum {
+                    lines.push(Line { rho: accumulator.rho(r), theta: accumulator.theta(t), votes: votes as u32 });
+                }
+            }
+        }
+
+        lines.sort_by(|a, b| b.votes.cmp(&a.votes));
+
+        lines
+    }
+
+    /// Detects the segments of an edge image, with the progressive probabilistic transform.
+    pub fn segments(&self, edges: &GrayImage) -> Vec<Segment> {
+        self.segments_with_rng(edges, &mut rand::thread_rng())
+    }
+
+    /// Detects the segments of an edge image, with the progressive probabilistic transform and
+    /// a user-provided random number generator.
+    pub fn segments_with_rng<R>(&self, edges: &GrayImage, rng: &mut R) -> Vec<Segment> where R: Rng {
+
+        let (width, height) = edges.dimensions();
+        let mut accumulator = Accumulator::new(self, (width, height));
+
+        let mut points: Vec<(u32, u32)> = {
+            edges.enumerate_pixels().filter(|&(_, _, p)| p.data[0] != 0).map(|(x, y, _)| (x, y)).collect()
+        };
+
+        rng.shuffle(&mut points);
+
+        // 0: not an edge (or already in a segment), 1: an edge, 2: an edge that voted
+        let mut mask: Vec<u8> = edges.iter().map(|&p| if p != 0 { 1 } else { 0 }).collect();
+        let index = |x: i64, y: i64| (y * width as i64 + x) as usize;
+
+        let mut segments = Vec::new();
+
+        for &(x, y) in &points {
+
+            if mask[index(x as i64, y as i64)] != 1 {
+                continue;
+            }
+
+            mask[index(x as i64, y as i64)] = 2;
+
+            let (t, votes) = accumulator.vote(x, y, 1);
+
+            if votes < self.threshold as i32 {
+                continue;
+            }
+
+            // walk along the line, in both directions, with a step of one pixel along the major
+            // axis
+            let theta = accumulator.theta(t);
+            let (dx, dy) = (-theta.sin(), theta.cos());
+            let major = dx.abs().max(dy.abs());
+            let (dx, dy) = (dx / major, dy / major);
+
+            let max_gap = (self.max_gap / (dx.hypot(dy))).ceil() as i64;
+
+            let mut ends = [(x as i64, y as i64); 2];
+
+            for (end, &sign) in ends.iter_mut().zip(&[1.0, -1.0]) {
+                let mut gap = 0;
+
+                for k in 1.. {
+                    let px = (x as f32 + sign * k as f32 * dx).round() as i64;
+                    let py = (y as f32 + sign * k as f32 * dy).round() as i64;
+
+                    if px < 0 || py < 0 || px >= width as i64 || py >= height as i64 {
+                        break;
+                    }
+
+                    if mask[index(px, py)] != 0 {
+                        gap = 0;
+                        *end = (px, py);
+                    } else {
+                        gap += 1;
+
+                        if gap > max_gap {
+                            break;
+                        }
+                    }
+                }
+            }
+
+            let length = ((ends[0].0 - ends[1].0) as f32).hypot((ends[0].1 - ends[1].1) as f32);
+            let good = length >= self.min_length;
+
+            // remove the pixels of the segment, and withdraw their votes if it is kept
+            let steps = cmp::max((ends[0].0 - ends[1].0).abs(), (ends[0].1 - ends[1].1).abs());
+
+            for k in 0..steps + 1 {
+                let f = if steps == 0 { 0.0 } else { k as f32 / steps as f32 };
+                let px = (ends[1].0 as f32 + f * (ends[0].0 - ends[1].0) as f32).round() as i64;
+                let py = (ends[1].1 as f32 + f * (ends[0].1 - ends[1].1) as f32).round() as i64;
+
+                let i = index(px, py);
+
+                if good && mask[i] == 2 {
+                    accumulator.vote(px as u32, py as u32, -1);
+                }
+
+                if good || mask[i] == 1 {
+                    mask[i] = 0;
+                }
+            }
+
+            if good {
+                segments.push(Segment {
+                    start: [ends[1].0 as f32, ends[1].1 as f32].into(),
+                    end: [ends[0].0 as f32, ends[0].1 as f32].into(),
+                });
+            }
+        }
+
+        segments
+    }
+}
+
+/// The `(θ, ρ)` accumulator of the Hough transform for lines.
+struct Accumulator {
+    thetas: usize,
+    rhos: usize,
+    rho_resolution: f32,
+    theta_resolution: f32,
+    max_rho: f32,
+    cos_sin: Vec<(f32, f32)>,
+    votes: Vec<i32>,
+}
+
+impl Accumulator {
+
+    fn new(hough: &HoughLines, (width, height): (u32, u32)) -> Accumulator {
+
+        let theta_resolution = hough.theta_resolution.max(1e-4);
+        let rho_resolution = hough.rho_resolution.max(1e-2);
+
+        let thetas = cmp::max(1, (f32::consts::PI / theta_resolution).round() as usize);
+        let max_rho = (width as f32).hypot(height as f32);
+        let rhos = (2.0 * max_rho / rho_resolution).ceil() as usize + 1;
+
+        let cos_sin = (0..thetas).map(|t| {
+            let theta = t as f32 * theta_resolution;
+
+            (theta.cos(), theta.sin())
+        }).collect();
+
+        Accumulator { thetas, rhos, rho_resolution, theta_resolution, max_rho, cos_sin, votes: vec![0; thetas * rhos] }
+    }
+
+    fn rho(&self, r: usize) -> f32 {
+        r as f32 * self.rho_resolution - self.max_rho
+    }
+
+    fn theta(&self, t: usize) -> f32 {
+        t as f32 * self.theta_resolution
+    }
+
+    /// Returns the votes of a cell, or 0 outside the accumulator.
+    fn get(&self, t: i64, r: i64) -> i32 {
+
+        if t < 0 || r < 0 || t >= self.thetas as i64 || r >= self.rhos as i64 {
+            return 0;
+        }
+
+        self.votes[t as usize * self.rhos + r as usize]
+    }
+
+    /// Adds `weight` votes for the lines through (`x`, `y`).
+    ///
+    /// Returns the angle index and the votes of the most voted of these lines.
+    fn vote(&mut self, x: u32, y: u32, weight: i32) -> (usize, i32) {
+
+        let mut best = (0, i32::min_value());
+
+        for (t, &(cos, sin)) in self.cos_sin.iter().enumerate() {
+            let rho = x as f32 * cos + y as f32 * sin;
+            let r = ((rho + self.max_rho) / self.rho_resolution).round() as usize;
+
+            let cell = &mut self.votes[t * self.rhos + r];
+
+            *cell += weight;
+
+            if *cell > best.1 {
+                best = (t, *cell);
+            }
+        }
+
+        best
+    }
+}
+
+/// The gradient-based Hough transform for circles
+///
+/// 1) Every edge pixel votes for the centres along its gradient direction (both ways), at
+///    distances in `[min_radius, max_radius]`.
+/// 2) The local maxima of the votes, with at least `threshold` votes, are candidate centres.
+///    Centres closer than `min_distance` to a stronger centre are suppressed.
+/// 3) The radius of each centre is the most frequent distance to the edge pixels. The circle is
+///    kept if the edge pixels cover at least `min_coverage` of its perimeter.
+///
+/// Only the direction of the gradient is used, so the circles can be brighter or darker than
+/// the background.
+///
+/// ## References
+///
+/// * Kimme, C., Ballard, D., & Sklansky, J. (1975). *Finding circles by an array of
+///   accumulators*. Communications of the ACM, 18(2), 120-122.
+#[derive(Clone, Copy, Debug)]
+pub struct HoughCircles {
+    min_radius: f32,
+    max_radius: f32,
+    threshold: u32,
+    min_distance: f32,
+    min_coverage: f32,
+}
+
+impl HoughCircles {
+
+    /// Constructs a transform detecting the circles with a radius in `[min_radius, max_radius]`
+    /// pixels, with a threshold of 20 votes for the centres, a minimum distance of
+    /// `min_radius` between centres and a minimum coverage of half the perimeter.
+    pub fn new(min_radius: f32, max_radius: f32) -> HoughCircles {
+
+        let min_radius = min_radius.max(1.0);
+
+        HoughCircles {
+            min_radius,
+            max_radius: max_radius.max(min_radius),
+            threshold: 20,
+            min_distance: min_radius,
+            min_coverage: 0.5,
+        }
+    }
+
+    /// Sets the minimum votes of a centre.
+    pub fn threshold(mut self, threshold: u32) -> Self {
+        self.threshold = cmp::max(1, threshold);
+        self
+    }
+
+    /// Sets the minimum distance between two centres, in pixels.
+    pub fn min_distance(mut self, min_distance: f32) -> Self {
+        self.min_distance = min_distance;
+        self
+    }
+
+    /// Sets the minimum fraction of the perimeter of a circle covered by edge pixels.
+    pub fn min_coverage(mut self, min_coverage: f32) -> Self {
+        self.min_coverage = min_coverage;
+        self
+    }
+
+    /// Detects the circles of an edge map (see `Canny::edge_map`).
+    ///
+    /// # Returns
+    ///
+    /// The circles, sorted by decreasing votes.
+    pub fn circles(&self, edge_map: &EdgeMap) -> Vec<Circle> {
+
+        let (width, height) = edge_map.edges.dimensions();
+
+        let edges: Vec<(u32, u32)> = {
+            edge_map.edges.enumerate_pixels().filter(|&(_, _, p)| p.data[0] != 0).map(|(x, y, _)| (x, y)).collect()
+        };
+
+        let mut votes = vec![0u32; (width * height) as usize];
+
+        for &(x, y) in &edges {
+            let angle = edge_map.orientation.get_pixel(x, y).data[0];
+            let (dx, dy) = (angle.cos(), angle.sin());
+
+            for &sign in &[1.0, -1.0] {
+                let mut r = self.min_radius;
+
+                while r <= self.max_radius {
+                    let cx = (x as f32 + sign * r * dx).round();
+                    let cy = (y as f32 + sign * r * dy).round();
+
+                    if cx >= 0.0 && cy >= 0.0 && cx < width as f32 && cy < height as f32 {
+                        votes[(cy as u32 * width + cx as u32) as usize] += 1;
+                    }
+
+                    r += 1.0;
+                }
+            }
+        }
+
+        let at = |x: i64, y: i64| {
+            if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 { 0 } else { votes[(y * width as i64 + x) as usize] }
+        };
+
+        // candidate centres: the local maxima of their 8 neighbours
+        let mut centres: Vec<(u32, u32, u32)> = Vec::new();
+
+        for y in 0..height as i64 {
+            for x in 0..width as i64 {
+                let v = at(x, y);
+
+                if v < self.threshold {
+                    continue;
+                }
+
+                let maximum = (-1..2).all(|dy| (-1..2).all(|dx| {
+                    let other = at(x + dx, y + dy);
+
+                    // ties are resolved in favour of the last pixel in raster order
+                    if (dy, dx) < (0, 0) { v >= other } else { (dy, dx) == (0, 0) || v > other }
+                }));
+
+                if maximum {
+                    centres.push((x as u32, y as u32, v));
+                }
+            }
+        }
+
+        centres.sort_by(|a, b| b.2.cmp(&a.2));
+
+        let mut circles: Vec<Circle> = Vec::new();
+        let bins = (self.max_radius - self.min_radius).floor() as usize + 1;
+
+        for &(cx, cy, _) in &centres {
+            let (cx, cy) = (cx as f32, cy as f32);
+
+            let isolated = circles.iter().all(|circle| {
+                let (x, y) = coordinates(&circle.centre);
+
+                (x - cx).hypot(y - cy) >= self.min_distance
+            });
+
+            if !isolated {
+                continue;
+            }
+
+            // the histogram of the distances from the centre to the edge pixels
+            let mut histogram = vec![0u32; bins];
+
+            for &(x, y) in &edges {
+                let distance = (x as f32 - cx).hypot(y as f32 - cy);
+                let bin = (distance - self.min_radius).round();
+
+                if bin >= 0.0 && (bin as usize) < bins {
+                    histogram[bin as usize] += 1;
+                }
+            }
+
+            let (bin, support) = histogram.iter().cloned().enumerate()
+                .fold((0, 0), |best, (bin, count)| if count > best.1 { (bin, count) } else { best });
+
+            let radius = self.min_radius + bin as f32;
+
+            if (support as f32) < self.min_coverage * 2.0 * f32::consts::PI * radius {
+                continue;
+            }
+
+            circles.push(Circle { centre: [cx, cy].into(), radius, votes: support });
+        }
+
+        circles.sort_by(|a, b| b.votes.cmp(&a.votes));
+
+        circles
+    }
+}
+
+fn coordinates(point: &Point<f32>) -> (f32, f32) {
+    let [x, y] = point.coordinates();
+
+    (x.into(), y.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use piston_image::{GrayImage, Luma};
+    use modules::feature::Canny;
+    use rand::{SeedableRng, XorShiftRng};
+    use super::{coordinates, HoughCircles, HoughLines};
+
+    #[test]
+    fn vertical_line() {
+        let edges = GrayImage::from_fn(64, 64, |x, y| Luma { data: [if x == 20 && y >= 10 && y < 50 { 255 } else { 0 }] });
+
+        let lines = HoughLines::new(30).lines(&edges);
+
+        assert_eq!(lines.len(), 1);
+        assert!((lines[0].rho - 20.0).abs() < 1.0 && lines[0].theta.abs() < 0.02);
+
+        let segments = HoughLines::new(20).segments_with_rng(&edges, &mut XorShiftRng::from_seed([1, 2, 3, 4]));
+
+        assert_eq!(segments.len(), 1);
+        assert!((segments[0].length() - 39.0).abs() <= 1.0);
+
+        let clipped = lines[0].clip(64, 64).unwrap();
+
+        assert!((clipped.length() - 63.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn circle() {
+        let image = GrayImage::from_fn(80, 80, |x, y| {
+            let inside = (x as f32 - 40.0).hypot(y as f32 - 36.0) < 15.0;
+
+            Luma { data: [if inside { 200 } else { 30 }] }
+        });
+
+        let circles = HoughCircles::new(8.0, 25.0).circles(&Canny::new(10.0, 30.0).edge_map(&image));
+
+        assert!(!circles.is_empty());
+
+        let (x, y) = coordinates(&circles[0].centre);
+
+        assert!((x - 40.0).abs() <= 1.5 && (y - 36.0).abs() <= 1.5);
+        assert!((circles[0].radius - 15.0).abs() <= 1.5);
+    }
+}
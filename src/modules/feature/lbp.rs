@@ -0,0 +1,228 @@
+use euclidean::Region2D as Region;
+use piston_image::{GrayImage, Luma};
+use std::cmp;
+
+/// The 8 neighbours of a pixel, clockwise from the top-left. Neighbour `k` sets bit `k`.
+const NEIGHBOURS: [(i32, i32); 8] = [(-1, -1), (0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0)];
+
+/// The mapping of the 8-bit patterns to the bins of the histograms.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Mapping {
+    /// Every pattern has its own bin (256 bins).
+    Basic,
+    /// Every uniform pattern (with at most 2 circular transitions between 0 and 1, i.e., an
+    /// edge, a corner, a spot or a flat area) has its own bin, and the non-uniform patterns
+    /// share the last bin (59 bins).
+    Uniform,
+    /// The patterns that are rotations of each other share a bin (36 bins).
+    RotationInvariant,
+    /// The uniform patterns are binned by their number of set bits, and the non-uniform
+    /// patterns share the last bin (10 bins). The usual texture descriptor.
+    UniformRotationInvariant,
+}
+
+/// Local binary patterns (LBP)
+///
+/// The pattern of a pixel is the 8-bit code of the comparisons of its 8 neighbours with it: bit
+/// `k` is set if neighbour `k` is at least as bright as the pixel. The patterns are invariant
+/// to monotonic changes of illumination. A texture is described by the histogram of the
+/// patterns of its pixels, after `Mapping` them to bins.
+///
+/// ## References
+///
+/// * Ojala, T., Pietikäinen, M., & Mäenpää, T. (2002). *Multiresolution gray-scale and rotation
+///   invariant texture classification with local binary patterns*. IEEE Transactions on
+///   Pattern Analysis and Machine Intelligence, 24(7), 971-987.
+#[derive(Clone, Debug)]
+pub struct Lbp {
+    mapping: Mapping,
+    /// The bin of every pattern.
+    table: Vec<u8>,
+    bins: usize,
+}
+
+impl Lbp {
+
+    /// Constructs an LBP operator with the `mapping`.
+    pub fn new(mapping: Mapping) -> Lbp {
+
+        let minimum_rotation = |code: u8| (0..8).map(|r| code.rotate_right(r)).min().unwrap();
+        let uniform = |code: u8| (code ^ code.rotate_right(1)).count_ones() <= 2;
+
+        let table: Vec<u8> = match mapping {
+            Mapping::Basic => (0..256).map(|code| code as u8).collect(),
+            Mapping::Uniform => {
+                let mut next = 0;
+
+                (0..256).map(|code| code as u8).map(|code| {
+                    if uniform(code) {
+                        next += 1;
+                        next - 1
+                    } else {
+                        58
+                    }
+                }).collect()
+            },
+            Mapping::RotationInvariant => {
+                let mut minima: Vec<u8> = (0..256).map(|code| minimum_rotation(code as u8)).collect();
+
+                minima.sort();
+                minima.dedup();
+
+                (0..256).map(|code| minima.binary_search(&minimum_rotation(code as u8)).unwrap() as u8).collect()
+            },
+            Mapping::UniformRotationInvariant => {
+                (0..256).map(|code| code as u8).map(|code| {
+                    if uniform(code) { code.count_ones() as u8 } else { 9 }
+                }).collect()
+            },
+        };
+
+        let bins = table.iter().cloned().max().unwrap() as usize + 1;
+
+        Lbp { mapping, table, bins }
+    }
+
+    /// Returns the mapping.
+    pub fn mapping(&self) -> Mapping {
+        self.mapping
+    }
+
+    /// Returns the number of bins of the histograms.
+    pub fn bins(&self) -> usize {
+        self.bins
+    }
+
+    /// Returns the bin of the pattern of the pixel at (`x`, `y`), or `None` if the pixel is on
+    /// the border of `image`.
+    pub fn bin(&self, image: &GrayImage, x: u32, y: u32) -> Option<u8> {
+
+        let (width, height) = image.dimensions();
+
+        if x == 0 || y == 0 || x + 1 >= width || y + 1 >= height {
+            return None;
+        }
+
+        Some(self.table[code(image, x, y) as usize])
+    }
+
+    /// Returns the image of the bins of the patterns of `image`. The border is 0.
+    pub fn image(&self, image: &GrayImage) -> GrayImage {
+        GrayImage::from_fn(image.width(), image.height(), |x, y| Luma { data: [self.bin(image, x, y).unwrap_or(0)] })
+    }
+
+    /// Returns the histogram of the patterns of the pixels of `region`, normalized to a sum of
+    /// 1.
+    ///
+    /// The pixels on the border of `image` are ignored. Returns `None` if `region` doesn't
+    /// contain any other pixel.
+    pub fn histogram(&self, image: &GrayImage, region: Region) -> Option<Vec<f32>> {
+
+        let (width, height) = image.dimensions();
+        let (x, y, w, h) = region.bounds();
+
+        let x0 = cmp::max(1, x.round() as i64);
+        let y0 = cmp::max(1, y.round() as i64);
+        let x1 = cmp::min(width as i64 - 1, (x + w).round() as i64);
+        let y1 = cmp::min(height as i64 - 1, (y + h).round() as i64);
+
+        if x0 >= x1 || y0 >= y1 {
+            return None;
+        }
+
+        let mut histogram = vec![0.0; self.bins];
+
+        for v in y0 as u32..y1 as u32 {
+            for u in x0 as u32..x1 as u32 {
+                histogram[self.table[code(image, u, v) as usize] as usize] += 1.0;
+            }
+        }
+
+        let total = ((x1 - x0) * (y1 - y0)) as f32;
+
+        for count in &mut histogram {
+            *count /= total;
+        }
+
+        Some(histogram)
+    }
+
+    /// Returns the concatenated histograms of a grid of `cells_x × cells_y` cells over
+    /// `region`.
+    ///
+    /// The histograms keep the layout of the texture, which a single histogram discards.
+    /// Returns `None` if a cell doesn't contain any pixel (see `histogram`).
+    pub fn pooled(&self, image: &GrayImage, region: Region, (cells_x, cells_y): (u32, u32)) -> Option<Vec<f32>> {
+
+        let (x, y, w, h) = region.bounds();
+        let (cells_x, cells_y) = (cmp::max(1, cells_x), cmp::max(1, cells_y));
+        let (cell_width, cell_height) = (w / cells_x as f64, h / cells_y as f64);
+
+        let mut descriptor = Vec::with_capacity(self.bins * (cells_x * cells_y) as usize);
+
+        for cy in 0..cells_y {
+            for cx in 0..cells_x {
+                let cell = [x + cx as f64 * cell_width, y + cy as f64 * cell_height, cell_width, cell_height];
+
+                match self.histogram(image, cell.into()) {
+                    Some(histogram) => descriptor.extend(histogram),
+                    None => return None,
+                }
+            }
+        }
+
+        Some(descriptor)
+    }
+}
+
+impl Default for Lbp {
+
+    /// Constructs an LBP operator with the `UniformRotationInvariant` mapping.
+    fn default() -> Lbp {
+        Lbp::new(Mapping::UniformRotationInvariant)
+    }
+}
+
+/// Returns the 8-bit pattern of the (inner) pixel at (`x`, `y`).
+fn code(image: &GrayImage, x: u32, y: u32) -> u8 {
+
+    let pixels: &[u8] = &**image;
+    let stride = image.width() as i32;
+    let at = |dx: i32, dy: i32| pixels[((y as i32 + dy) * stride + x as i32 + dx) as usize];
+
+    let centre = at(0, 0);
+
+    NEIGHBOURS.iter().enumerate().fold(0, |code, (k, &(dx, dy))| {
+        if at(dx, dy) >= centre { code | 1 << k } else { code }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use piston_image::{GrayImage, Luma};
+    use super::{Lbp, Mapping};
+
+    #[test]
+    fn bins() {
+        assert_eq!(Lbp::new(Mapping::Basic).bins(), 256);
+        assert_eq!(Lbp::new(Mapping::Uniform).bins(), 59);
+        assert_eq!(Lbp::new(Mapping::RotationInvariant).bins(), 36);
+        assert_eq!(Lbp::new(Mapping::UniformRotationInvariant).bins(), 10);
+    }
+
+    #[test]
+    fn illumination_invariance() {
+        let texture = |gain: u32, offset: u32| {
+            GrayImage::from_fn(32, 32, |x, y| Luma { data: [((x * 7 + y * 13) % 17 * gain + offset) as u8] })
+        };
+
+        let lbp = Lbp::default();
+        let region = [0.0, 0.0, 32.0, 32.0].into();
+
+        let a = lbp.pooled(&texture(2, 10), region, (2, 2)).unwrap();
+        let b = lbp.pooled(&texture(3, 50), region, (2, 2)).unwrap();
+
+        assert_eq!(a.len(), 40);
+        assert_eq!(a, b);
+    }
+}
@@ -0,0 +1,168 @@
+use core::feature::{Distance, Match};
+use std::cmp::Ordering;
+
+/// Brute-force descriptor matcher
+///
+/// Every query descriptor is compared to every train descriptor, with the `Distance` of the
+/// descriptors (Hamming for `BinaryDescriptor`, L2 for `Vec<f32>`). A match of `matches` is
+/// kept if it passes every enabled filter:
+///
+/// * the distance threshold: its distance is at most `max_distance`,
+/// * Lowe's ratio test: its distance is less than `ratio ×` the distance of the second nearest
+///   train descriptor (ambiguous matches, e.g., on repetitive textures, are rejected),
+/// * the cross-check: the query descriptor is also the nearest to the train descriptor.
+///
+/// ```rust,ignore
+/// use miro::modules::feature::{Matcher, Orb};
+///
+/// let orb = Orb::default();
+/// let (keypoints_i, descriptors_i): (Vec<_>, Vec<_>) = orb.detect_and_describe(&image_i).into_iter().unzip();
+/// let (keypoints_j, descriptors_j): (Vec<_>, Vec<_>) = orb.detect_and_describe(&image_j).into_iter().unzip();
+///
+/// let matches = Matcher::new().ratio(0.8).cross_check(true).matches(&descriptors_i, &descriptors_j);
+/// ```
+///
+/// ## References
+///
+/// * Lowe, D. G. (2004). *Distinctive image features from scale-invariant keypoints*.
+///   International Journal of Computer Vision, 60(2), 91-110.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Matcher {
+    ratio: Option<f32>,
+    cross_check: bool,
+    max_distance: Option<f32>,
+}
+
+impl Matcher {
+
+    /// Constructs a matcher without filters.
+    pub fn new() -> Matcher {
+        Matcher::default()
+    }
+
+    /// Enables (or disables, with `None`) the ratio test. Lowe recommends a ratio of 0.8.
+    pub fn ratio<R: Into<Option<f32>>>(mut self, ratio: R) -> Self {
+        self.ratio = ratio.into();
+        self
+    }
+
+    /// Enables or disables the cross-check.
+    pub fn cross_check(mut self, cross_check: bool) -> Self {
+        self.cross_check = cross_check;
+        self
+    }
+
+    /// Enables (or disables, with `None`) the distance threshold.
+    pub fn max_distance<D: Into<Option<f32>>>(mut self, max_distance: D) -> Self {
+        self.max_distance = max_distance.into();
+        self
+    }
+
+    /// Finds the `k` nearest train descriptors of every query descriptor.
+    ///
+    /// Only the distance threshold applies.
+    ///
+    /// # Returns
+    ///
+    /// For every query descriptor, in the same order, up to `k` matches sorted by increasing
+    /// distance.
+    pub fn knn<D>(&self, query: &[D], train: &[D], k: usize) -> Vec<Vec<Match>> where D: Distance {
+
+        query.iter().enumerate().map(|(q, descriptor)| {
+            let mut matches: Vec<Match> = train.iter().enumerate()
+                .map(|(t, other)| Match { query: q, train: t, distance: descriptor.distance(other) })
+                .filter(|m| self.max_distance.map_or(true, |max| m.distance <= max))
+                .collect();
+
+            matches.sort_by(by_distance);
+            matches.truncate(k);
+
+            matches
+        }).collect()
+    }
+
+    /// Finds the nearest train descriptor of every query descriptor, and filters the matches.
+    ///
+    /// # Returns
+    ///
+    /// At most one match per query descriptor, in the order of the query descriptors.
+    pub fn matches<D>(&self, query: &[D], train: &[D]) -> Vec<Match> where D: Distance {
+
+        // the distance threshold is applied to the best match only, so that the ratio test
+        // compares it to the true second nearest
+        let unfiltered = Matcher { max_distance: None, .. *self };
+
+        let backward = if self.cross_check {
+            unfiltered.knn(train, query, 1).into_iter().map(|m| m.first().map(|m| m.train)).collect()
+        } else {
+            Vec::new()
+        };
+
+        unfiltered.knn(query, train, 2).into_iter().filter_map(|nearest| {
+            let best = match nearest.first() {
+                Some(&best) => best,
+                None => return None,
+            };
+
+            if self.max_distance.map_or(false, |max| best.distance > max) {
+                return None;
+            }
+
+            if let (Some(ratio), Some(second)) = (self.ratio, nearest.get(1)) {
+                if best.distance >= ratio * second.distance {
+                    return None;
+                }
+            }
+
+            if self.cross_check && backward[best.train] != Some(best.query) {
+                return None;
+            }
+
+            Some(best)
+        }).collect()
+    }
+}
+
+/// Orders matches by increasing distance (ties by train index).
+fn by_distance(a: &Match, b: &Match) -> Ordering {
+    a.distance.partial_cmp(&b.distance).unwrap_or(Ordering::Equal).then(a.train.cmp(&b.train))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Matcher;
+
+    fn descriptors(values: &[[f32; 2]]) -> Vec<Vec<f32>> {
+        values.iter().map(|v| v.to_vec()).collect()
+    }
+
+    #[test]
+    fn nearest_neighbours() {
+        let query = descriptors(&[[0.0, 0.0], [10.0, 10.0]]);
+        let train = descriptors(&[[10.0, 9.0], [0.0, 1.0], [0.0, 3.0]]);
+
+        let knn = Matcher::new().knn(&query, &train, 2);
+
+        assert_eq!(knn[0].iter().map(|m| m.train).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(knn[1][0].train, 0);
+        assert!((knn[1][0].distance - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn filters() {
+        let query = descriptors(&[[0.0, 0.0], [5.0, 5.0], [20.0, 0.0], [5.0, 3.0]]);
+        let train = descriptors(&[[0.0, 1.0], [5.0, 6.0], [5.0, 4.0], [20.0, 5.0]]);
+
+        let pairs = |matcher: Matcher| {
+            matcher.matches(&query, &train).iter().map(|m| (m.query, m.train)).collect::<Vec<_>>()
+        };
+
+        // [5, 5] is as close to [5, 6] as to [5, 4]
+        assert_eq!(pairs(Matcher::new().ratio(0.8)), vec![(0, 0), (2, 3), (3, 2)]);
+
+        // [5, 4] is as close to [5, 3] as to [5, 5], which comes first
+        assert_eq!(pairs(Matcher::new().cross_check(true)), vec![(0, 0), (1, 1), (2, 3)]);
+
+        assert_eq!(pairs(Matcher::new().max_distance(2.0)), vec![(0, 0), (1, 1), (3, 2)]);
+    }
+}
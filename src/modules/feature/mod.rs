@@ -1 +1,22 @@
-//! Feature detection, matching, and description
\ No newline at end of file
+//! Feature detection, matching, and description
+pub use self::brief::Brief;
+pub use self::canny::{Canny, EdgeMap};
+pub use self::corners::{Corners, Score};
+pub use self::dog::Dog;
+pub use self::fast::Fast;
+pub use self::hog::{Hog, HogCells};
+pub use self::hough::{Circle, HoughCircles, HoughLines, Line, Segment};
+pub use self::lbp::{Lbp, Mapping};
+pub use self::matcher::Matcher;
+pub use self::orb::Orb;
+
+mod brief;
+mod canny;
+mod corners;
+mod dog;
+mod fast;
+mod hog;
+mod hough;
+mod lbp;
+mod matcher;
+mod orb;
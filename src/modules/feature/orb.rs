@@ -0,0 +1,250 @@
+use core::feature::{BinaryDescriptor, Describe, Detect, Keypoint};
+use error::Result;
+use image::GrayPyramid;
+use piston_image::GrayImage;
+use std::cmp;
+use super::{Brief, Fast};
+use super::brief::PATCH_RADIUS;
+
+/// ORB detector and descriptor (oriented FAST and rotated BRIEF)
+///
+/// 1) FAST-9 corners are detected on every level of an image pyramid, and the `max_keypoints`
+///    strongest are kept.
+/// 2) The orientation of each corner is the direction from the corner to the intensity
+///    centroid of the circular patch around it.
+/// 3) The corners are described by BRIEF, with the tests rotated by the orientation ("steered"
+///    BRIEF), which makes the descriptors invariant to in-plane rotation.
+///
+/// Unlike the original ORB, the tests are sampled at random (see `Brief`) rather than learned,
+/// and the pyramid is dyadic.
+///
+/// ## References
+///
+/// * Rublee, E., Rabaud, V., Konolige, K., & Bradski, G. (2011). *ORB: An efficient alternative
+///   to SIFT or SURF*. In IEEE International Conference on Computer Vision (pp. 2564-2571).
+///   IEEE.
+/// * Rosin, P. L. (1999). *Measuring corner properties*. Computer Vision and Image
+///   Understanding, 73(2), 291-307.
+#[derive(Clone, Debug)]
+pub struct Orb {
+    fast: Fast,
+    brief: Brief,
+    levels: usize,
+    max_keypoints: usize,
+}
+
+impl Orb {
+
+    /// Constructs an ORB detector keeping the `max_keypoints` strongest keypoints, with a FAST
+    /// threshold of 20, 256-bit descriptors and a pyramid of 4 levels.
+    pub fn new(max_keypoints: usize) -> Orb {
+        Orb { fast: Fast::new(20), brief: Brief::default(), levels: 4, max_keypoints }
+    }
+
+    /// Sets the corner detector.
+    pub fn fast(mut self, fast: Fast) -> Self {
+        self.fast = fast;
+        self
+    }
+
+    /// Sets the descriptor.
+    pub fn brief(mut self, brief: Brief) -> Self {
+        self.brief = brief;
+        self
+    }
+
+    /// Sets the number of levels of the pyramids built from images. If `levels` is 0, 1 is
+    /// chosen.
+    pub fn levels(mut self, levels: usize) -> Self {
+        self.levels = cmp::max(1, levels);
+        self
+    }
+
+    /// Detects and describes the keypoints of `image`.
+    ///
+    /// # Returns
+    ///
+    /// The keypoints (with their orientation) and their descriptors, sorted by decreasing
+    /// response.
+    pub fn detect_and_describe(&self, image: &GrayImage) -> Vec<(Keypoint, BinaryDescriptor)> {
+
+        let pyramid = GrayPyramid::build(image, self.levels);
+
+        self.oriented_keypoints(&pyramid).into_iter().filter_map(|keypoint| {
+            self.brief.describe_keypoint(&pyramid, &keypoint, keypoint.angle).map(|d| (keypoint, d))
+        }).collect()
+    }
+
+    fn oriented_keypoints(&self, pyramid: &GrayPyramid) -> Vec<Keypoint> {
+
+        let mut keypoints: Vec<Keypoint> = {
+            self.fast.detect(pyramid).unwrap_or_default().into_iter().filter_map(|mut keypoint| {
+                let image = &pyramid[keypoint.level];
+                let (x, y) = keypoint.coordinates();
+                let scale = (1 << keypoint.level) as f32;
+
+                orientation(image, (x / scale) as i32, (y / scale) as i32).map(|angle| {
+                    keypoint.angle = angle;
+                    keypoint
+                })
+            }).collect()
+        };
+
+        keypoints.sort_by(|a, b| b.response.partial_cmp(&a.response).unwrap());
+        keypoints.truncate(self.max_keypoints);
+
+        keypoints
+    }
+}
+
+impl Default for Orb {
+
+    /// Constructs an ORB detector keeping the 500 strongest keypoints.
+    fn default() -> Orb {
+        Orb::new(500)
+    }
+}
+
+/// Returns the angle of the intensity centroid of the disc of radius `PATCH_RADIUS` centred on
+/// (`x`, `y`), or `None` if the disc isn't inside `image`.
+fn orientation(image: &GrayImage, x: i32, y: i32) -> Option<f32> {
+
+    let (width, height) = image.dimensions();
+    let r = PATCH_RADIUS;
+
+    if x < r || y < r || x + r >= width as i32 || y + r >= height as i32 {
+        return None;
+    }
+
+    let pixels: &[u8] = &**image;
+    let stride = width as i32;
+
+    let (mut m10, mut m01) = (0i64, 0i64);
+
+    for v in -r..r + 1 {
+        // the half-width of the disc on row `v`
+        let half = ((r * r - v * v) as f32).sqrt() as i32;
+
+        for u in -half..half + 1 {
+            let intensity = pixels[((y + v) * stride + x + u) as usize] as i64;
+
+            m10 += u as i64 * intensity;
+            m01 += v as i64 * intensity;
+        }
+    }
+
+    Some((m01 as f32).atan2(m10 as f32))
+}
+
+impl Detect<GrayPyramid> for Orb {
+
+    /// Detects the oriented keypoints of `pyramid`, sorted by decreasing response.
+    fn detect(&self, pyramid: &GrayPyramid) -> Result<Vec<Keypoint>> {
+        Ok(self.oriented_keypoints(pyramid))
+    }
+}
+
+impl Detect<GrayImage> for Orb {
+
+    /// Detects the oriented keypoints of `image`, sorted by decreasing response.
+    fn detect(&self, image: &GrayImage) -> Result<Vec<Keypoint>> {
+        Ok(self.oriented_keypoints(&GrayPyramid::build(image, self.levels)))
+    }
+}
+
+impl Describe<GrayPyramid> for Orb {
+
+    type Descriptor = BinaryDescriptor;
+
+    /// Describes the keypoints on their level of `pyramid`, with steered BRIEF. The orientation
+    /// of the keypoints without one is computed.
+    fn describe(&self, pyramid: &GrayPyramid, keypoints: &[Keypoint]) -> Result<Vec<Option<BinaryDescriptor>>> {
+
+        Ok(keypoints.iter().map(|keypoint| {
+            let angle = if keypoint.has_angle() {
+                Some(keypoint.angle)
+            } else {
+                let (x, y) = keypoint.coordinates();
+                let scale = (1 << keypoint.level) as f32;
+
+                pyramid.get(keypoint.level)
+                    .and_then(|image| orientation(image, (x / scale) as i32, (y / scale) as i32))
+            };
+
+            angle.and_then(|angle| self.brief.describe_keypoint(pyramid, keypoint, angle))
+        }).collect())
+    }
+}
+
+impl Describe<GrayImage> for Orb {
+
+    type Descriptor = BinaryDescriptor;
+
+    /// Describes the keypoints with steered BRIEF, on a pyramid built from `image`.
+    fn describe(&self, image: &GrayImage, keypoints: &[Keypoint]) -> Result<Vec<Option<BinaryDescriptor>>> {
+        self.describe(&GrayPyramid::build(image, self.levels), keypoints)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::feature::{Describe, Keypoint};
+    use modules::feature::Brief;
+    use piston_image::{GrayImage, Luma};
+    use super::Orb;
+
+    fn texture(angle: f32) -> GrayImage {
+        let (sin, cos) = angle.sin_cos();
+
+        GrayImage::from_fn(128, 128, |x, y| {
+            // rotate about the centre
+            let (u, v) = (x as f32 - 64.0, y as f32 - 64.0);
+            let (u, v) = (cos * u + sin * v, -sin * u + cos * v);
+
+            let inside = (u > -20.0 && u < 10.0 && v > -20.0 && v < -5.0) || (u > 0.0 && u < 15.0 && v > 0.0 && v < 20.0);
+
+            Luma { data: [if inside { 220 } else { 30 }] }
+        })
+    }
+
+    #[test]
+    fn rotation_invariance() {
+        let (image_a, image_b) = (texture(0.0), texture(0.5));
+
+        let orb = Orb::new(50).levels(1);
+        let features = orb.detect_and_describe(&image_a);
+
+        assert!(!features.is_empty());
+
+        let (sin, cos) = 0.5f32.sin_cos();
+        let brief = Brief::default();
+
+        let (mut steered, mut unsteered) = (Vec::new(), Vec::new());
+
+        for &(ref keypoint, ref descriptor) in &features {
+            let (x, y) = keypoint.coordinates();
+
+            // the counterpart of the keypoint on the image rotated by 0.5 rad about (64, 64)
+            let (u, v) = (x - 64.0, y - 64.0);
+            let (x_b, y_b) = (64.0 + cos * u - sin * v, 64.0 + sin * u + cos * v);
+
+            let counterpart = orb.describe(&image_b, &[Keypoint::new(x_b, y_b, 0.0)]).unwrap().pop().unwrap();
+
+            steered.push(descriptor.hamming(&counterpart.unwrap()));
+
+            let (a, b) = (brief.describe_at(&image_a, x, y, 0.0), brief.describe_at(&image_b, x_b, y_b, 0.0));
+
+            unsteered.push(a.unwrap().hamming(&b.unwrap()));
+        }
+
+        // most counterparts are close (out of 256 bits)...
+        let close = steered.iter().filter(|&&distance| distance < 32).count();
+
+        assert!(4 * close >= 3 * steered.len(), "steered distances: {:?}", steered);
+
+        // ...much closer than without steering
+        let (sum_steered, sum_unsteered) = (steered.iter().sum::<u32>(), unsteered.iter().sum::<u32>());
+
+        assert!(sum_unsteered > 2 * sum_steered, "{:?} vs {:?}", steered, unsteered);
+    }
+}
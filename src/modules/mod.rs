@@ -1,5 +1,5 @@
 // mod classification;
-// mod detection;
-// pub mod feature;
+pub mod detection;
+pub mod feature;
 pub mod motion;
 pub mod tracking;
\ No newline at end of file
@@ -0,0 +1,408 @@
+use core::motion::{Flow, FlowField, FlowPoint, FlowStatus};
+use error::{Error, ErrorKind, Result};
+use image::{Intensity, LumaImage};
+use std::{cmp, f32};
+use utility::plane_euclidean::Point;
+
+/// The cost of matching two blocks.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Cost {
+    /// The sum of absolute differences.
+    Sad,
+    /// The sum of squared differences. Penalizes outliers more than `Sad`.
+    Ssd,
+}
+
+/// The strategy used to search the displacement of a block.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Search {
+    /// Every displacement within the search range is evaluated. Finds the global minimum of the
+    /// cost, at `(2 × range + 1)²` evaluations per block.
+    Exhaustive,
+    /// The three-step search: the 9 displacements on a square of half-side `step` around the
+    /// best displacement so far are evaluated, and `step` is halved, from `range / 2` down to 1.
+    ThreeStep,
+    /// The diamond search: a large diamond moves towards the best displacement until it is at
+    /// its centre, then a small diamond refines it. Usually the fastest.
+    Diamond,
+}
+
+/// Large diamond search pattern.
+const LARGE_DIAMOND: [(i32, i32); 8] = [(-2, 0), (-1, -1), (0, -2), (1, -1), (2, 0), (1, 1), (0, 2), (-1, 1)];
+
+/// Small diamond search pattern.
+const SMALL_DIAMOND: [(i32, i32); 4] = [(-1, 0), (0, -1), (1, 0), (0, 1)];
+
+/// Block-matching motion estimation
+///
+/// The motion of a block of pixels of the first image is the integer displacement that
+/// minimizes the matching cost with the second image, within a search range. Unlike the
+/// differential methods (e.g., `PyramLk`), block matching does not assume the motion to be
+/// small or the images to be smooth, but it is only accurate to the pixel.
+///
+/// `BlockMatching` computes a coarse motion field (one vector per block) with `field`, and
+/// implements `Flow` for the blocks centred on individual points.
+///
+/// ## References
+///
+/// * Koga, T., Iinuma, K., Hirano, A., Iijima, Y., & Ishiguro, T. (1981). *Motion-compensated
+///   interframe coding for video conferencing*. In Proceedings of the National
+///   Telecommunications Conference (pp. G5.3.1-G5.3.5).
+/// * Zhu, S., & Ma, K. K. (2000). *A new diamond search algorithm for fast block-matching
+///   motion estimation*. IEEE Transactions on Image Processing, 9(2), 287-290.
+#[derive(Clone, Copy, Debug)]
+pub struct BlockMatching {
+    block: u32,
+    range: u32,
+    cost: Cost,
+    search: Search,
+}
+
+impl BlockMatching {
+
+    /// Constructs a block matcher with the `Sad` cost and the `Diamond` search.
+    ///
+    /// # Arguments
+    ///
+    /// * `block` - The side of the blocks, in pixels. If `block` is 0, 1 is chosen.
+    /// * `range` - The largest displacement searched along each axis, in pixels.
+    pub fn new(block: u32, range: u32) -> BlockMatching {
+        BlockMatching { block: cmp::max(1, block), range, cost: Cost::Sad, search: Search::Diamond }
+    }
+
+    /// Sets the matching cost.
+    pub fn cost(mut self, cost: Cost) -> Self {
+        self.cost = cost;
+        self
+    }
+
+    /// Sets the search strategy.
+    pub fn search(mut self, search: Search) -> Self {
+        self.search = search;
+        self
+    }
+
+    /// Computes the motion field from `image_i` to `image_j`.
+    ///
+    /// # Returns
+    ///
+    /// A field of `⌊width / block⌋ × ⌊height / block⌋` vectors: the vector at `(x, y)` is the
+    /// displacement of the block whose top-left corner is at `(x × block, y × block)` on
+    /// `image_i`. Returns an error if the images don't have the same dimensions.
+    pub fn field<T>(&self, image_i: &LumaImage<T>, image_j: &LumaImage<T>) -> Result<FlowField>
+        where T: Intensity
+    {
+        same_dimensions(image_i, image_j)?;
+
+        let (width, height) = image_i.dimensions();
+        let mut field = FlowField::new(width / self.block, height / self.block);
+
+        for y in 0..height / self.block {
+            for x in 0..width / self.block {
+                let origin = ((x * self.block) as i32, (y * self.block) as i32);
+
+                if let Some((dx, dy, _)) = self.search_block(image_i, image_j, origin, (0, 0)) {
+                    field.set(x, y, [dx as f32, dy as f32]);
+                }
+            }
+        }
+
+        Ok(field)
+    }
+
+    /// Matches the block centred on `point_i`, starting the search from `guess_j`.
+    fn flow_point<T>(&self, image_i: &LumaImage<T>, point_i: &Point<f32>, guess_j: &Point<f32>,
+                     image_j: &LumaImage<T>) -> FlowPoint
+        where T: Intensity
+    {
+        let [x_i, y_i] = point_i.coordinates();
+        let (x_i, y_i): (f32, f32) = (x_i.into(), y_i.into());
+
+        let [x_g, y_g] = guess_j.coordinates();
+        let (x_g, y_g): (f32, f32) = (x_g.into(), y_g.into());
+
+        let half = (self.block / 2) as i32;
+        let origin = (x_i.round() as i32 - half, y_i.round() as i32 - half);
+        let start = ((x_g - x_i).round() as i32, (y_g - y_i).round() as i32);
+
+        let (width, height) = image_i.dimensions();
+        let block = self.block as i32;
+
+        if origin.0 < 0 || origin.1 < 0 || origin.0 + block > width as i32 || origin.1 + block > height as i32 {
+            debug!("The block around point {} is outside image bounds {}x{}", point_i, width, height);
+
+            return FlowPoint {
+                status: FlowStatus::OutOfBounds,
+                point: *point_i,
+                residual: f32::NAN,
+                min_eigenvalue: f32::NAN,
+            };
+        }
+
+        match self.search_block(image_i, image_j, origin, start) {
+            Some((dx, dy, _)) => {
+                let residual = mean_absolute_difference(image_i, image_j, origin, (dx, dy), self.block);
+
+                FlowPoint {
+                    status: FlowStatus::Tracked,
+                    point: [x_i + dx as f32, y_i + dy as f32].into(),
+                    residual,
+                    min_eigenvalue: f32::NAN,
+                }
+            },
+            None => {
+                debug!("No displacement of the block around point {} is inside the image", point_i);
+
+                FlowPoint {
+                    status: FlowStatus::Lost,
+                    point: [x_g, y_g].into(),
+                    residual: f32::NAN,
+                    min_eigenvalue: f32::NAN,
+                }
+            },
+        }
+    }
+
+    /// Searches the displacement of the block whose top-left corner is at `origin`, within
+    /// `range` of `start`.
+    ///
+    /// Returns the displacement and its cost, or `None` if no displacement within the range
+    /// keeps the block inside `image_j`.
+    fn search_block<T>(&self, image_i: &LumaImage<T>, image_j: &LumaImage<T>,
+                       origin: (i32, i32), start: (i32, i32)) -> Option<(i32, i32, f32)>
+        where T: Intensity
+    {
+        let range = self.range as i32;
+
+        let cost = |d: (i32, i32)| {
+            if (d.0 - start.0).abs() > range || (d.1 - start.1).abs() > range {
+                None
+            } else {
+                block_cost(image_i, image_j, origin, d, self.block, self.cost)
+            }
+        };
+
+        // replaces `best` if `d` is strictly better; ties keep the earlier displacement
+        let consider = |best: &mut Option<(i32, i32, f32)>, d: (i32, i32)| {
+            if let Some(c) = cost(d) {
+                if best.map_or(true, |(_, _, b)| c < b) {
+                    *best = Some((d.0, d.1, c));
+                    return true;
+                }
+            }
+
+            false
+        };
+
+        let mut best = None;
+
+        match self.search {
+            Search::Exhaustive => {
+                consider(&mut best, start);
+
+                for dy in -range..range + 1 {
+                    for dx in -range..range + 1 {
+                        consider(&mut best, (start.0 + dx, start.1 + dy));
+                    }
+                }
+            },
+            Search::ThreeStep => {
+                consider(&mut best, start);
+
+                let mut step = cmp::max(1, (self.range + 1).next_power_of_two() as i32 / 2);
+
+                while let Some((cx, cy, _)) = best {
+                    for &(sx, sy) in &[(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)] {
+                        consider(&mut best, (cx + sx * step, cy + sy * step));
+                    }
+
+                    if step == 1 {
+                        break;
+                    }
+
+                    step /= 2;
+                }
+            },
+            Search::Diamond => {
+                consider(&mut best, start);
+
+                // the search moves by at least one pixel per iteration, so it terminates
+                while let Some((cx, cy, _)) = best {
+                    let moved = LARGE_DIAMOND.iter()
+                        .fold(false, |moved, &(sx, sy)| consider(&mut best, (cx + sx, cy + sy)) || moved);
+
+                    if !moved {
+                        break;
+                    }
+                }
+
+                if let Some((cx, cy, _)) = best {
+                    for &(sx, sy) in &SMALL_DIAMOND {
+                        consider(&mut best, (cx + sx, cy + sy));
+                    }
+                }
+            },
+        }
+
+        best
+    }
+}
+
+impl Default for BlockMatching {
+
+    /// Constructs a block matcher with 16×16 blocks and a search range of 16 pixels.
+    fn default() -> BlockMatching {
+        BlockMatching::new(16, 16)
+    }
+}
+
+/// Returns an error if `image_i` and `image_j` don't have the same dimensions (the blocks of
+/// both are read with the same stride).
+fn same_dimensions<T>(image_i: &LumaImage<T>, image_j: &LumaImage<T>) -> Result where T: Intensity {
+
+    if image_i.dimensions() != image_j.dimensions() {
+        let message = format!("expected images of the same dimensions, found {:?} and {:?}",
+            image_i.dimensions(), image_j.dimensions());
+
+        return Err(Error::new(ErrorKind::Motion, message));
+    }
+
+    Ok(())
+}
+
+/// Returns the cost of displacing the block at `origin` by `d`, or `None` if the displaced
+/// block isn't inside `image_j`.
+fn block_cost<T>(image_i: &LumaImage<T>, image_j: &LumaImage<T>, origin: (i32, i32), d: (i32, i32),
+                 block: u32, cost: Cost) -> Option<f32>
+    where T: Intensity
+{
+    let (width, height) = image_j.dimensions();
+    let (x_j, y_j) = (origin.0 + d.0, origin.1 + d.1);
+
+    if x_j < 0 || y_j < 0 || x_j + block as i32 > width as i32 || y_j + block as i32 > height as i32 {
+        return None;
+    }
+
+    let (pixels_i, pixels_j) = (&**image_i, &**image_j);
+    let stride = width as usize;
+    let block = block as usize;
+
+    let mut sum = 0.0;
+
+    for row in 0..block {
+        let start_i = (origin.1 as usize + row) * stride + origin.0 as usize;
+        let start_j = (y_j as usize + row) * stride + x_j as usize;
+
+        let row_i = &pixels_i[start_i..start_i + block];
+        let row_j = &pixels_j[start_j..start_j + block];
+
+        for (&p_i, &p_j) in row_i.iter().zip(row_j) {
+            let e = p_i.to_intensity() - p_j.to_intensity();
+
+            sum += match cost {
+                Cost::Sad => e.abs(),
+                Cost::Ssd => e * e,
+            };
+        }
+    }
+
+    Some(sum)
+}
+
+fn mean_absolute_difference<T>(image_i: &LumaImage<T>, image_j: &LumaImage<T>, origin: (i32, i32),
+                               d: (i32, i32), block: u32) -> f32
+    where T: Intensity
+{
+    block_cost(image_i, image_j, origin, d, block, Cost::Sad).unwrap_or(f32::NAN) / (block * block) as f32
+}
+
+impl<T> Flow<LumaImage<T>> for BlockMatching where T: Intensity {
+
+    /// Matches the block centred on every point.
+    ///
+    /// The flow is rounded to the pixel. The `min_eigenvalue` of the points is not computed
+    /// (`NaN`); points whose block is partially outside `i` are `OutOfBounds`. Returns an error
+    /// if `i` and `j` don't have the same dimensions.
+    fn flow(&self, i: &LumaImage<T>, points_i: &[Point<f32>], j: &LumaImage<T>) -> Result<Vec<FlowPoint>> {
+        same_dimensions(i, j)?;
+
+        Ok(points_i.iter().map(|point_i| self.flow_point(i, point_i, point_i, j)).collect())
+    }
+
+    /// Matches the block centred on every point, searching around the guesses.
+    fn flow_with_guess(&self, i: &LumaImage<T>, points_i: &[Point<f32>], guesses_j: &[Point<f32>],
+                       j: &LumaImage<T>) -> Result<Vec<FlowPoint>>
+    {
+        same_dimensions(i, j)?;
+
+        if guesses_j.len() != points_i.len() {
+            let message = format!("expected {} guesses, found {}", points_i.len(), guesses_j.len());
+
+            return Err(Error::new(ErrorKind::Motion, message));
+        }
+
+        Ok(points_i.iter().zip(guesses_j).map(|(point_i, guess_j)| self.flow_point(i, point_i, guess_j, j)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::motion::{Flow, FlowPoint};
+    use modules::motion::PyramLk;
+    use utility::fixtures;
+    use super::{BlockMatching, Cost, Search};
+
+    #[test]
+    fn searches_find_the_translation() {
+        let (image_i, image_j) = (fixtures::texture(96, 96, 0.0, 0.0), fixtures::texture(96, 96, 3.0, -2.0));
+
+        for &search in &[Search::Exhaustive, Search::ThreeStep, Search::Diamond] {
+            for &cost in &[Cost::Sad, Cost::Ssd] {
+                let field = BlockMatching::new(16, 7).search(search).cost(cost).field(&image_i, &image_j).unwrap();
+
+                // inner blocks
+                assert_eq!(field.get(2, 2), [3.0, -2.0], "{:?} {:?}", search, cost);
+            }
+        }
+    }
+
+    #[test]
+    fn endpoint_error_against_pyramidal_lk() {
+        let (image_i, image_j) = (fixtures::texture(96, 96, 0.0, 0.0), fixtures::texture(96, 96, 2.5, -1.5));
+        let points = fixtures::grid((32.0, 32.0), (32.0, 32.0), 4);
+
+        // the mean endpoint error
+        let error = |flow: Vec<FlowPoint>| {
+            let sum: f32 = flow.iter().zip(&points).map(|(flow_point, point_i)| {
+                assert!(flow_point.is_tracked());
+
+                let ([x_i, y_i], [x_j, y_j]) = (point_i.coordinates(), flow_point.point.coordinates());
+                let (dx, dy): (f32, f32) = ((x_j - x_i).into(), (y_j - y_i).into());
+
+                (dx - 2.5).hypot(dy + 1.5)
+            }).sum();
+
+            sum / points.len() as f32
+        };
+
+        let bm = error(BlockMatching::new(8, 16).flow(&image_i, &points, &image_j).unwrap());
+        let lk = error(PyramLk::default().flow(&image_i, &points, &image_j).unwrap());
+
+        // block matching is accurate to the pixel only, off by at least (0.5, 0.5) here
+        assert!(bm >= 0.5, "{}", bm);
+        assert!(lk < 0.1, "{}", lk);
+    }
+
+    #[test]
+    fn mismatched_frames() {
+        let (image_i, image_j) = (fixtures::texture(96, 96, 0.0, 0.0), fixtures::texture(64, 48, 0.0, 0.0));
+        let points = fixtures::grid((32.0, 32.0), (32.0, 32.0), 4);
+
+        let bm = BlockMatching::new(8, 16);
+
+        assert!(bm.field(&image_i, &image_j).is_err());
+        assert!(bm.flow(&image_i, &points, &image_j).is_err());
+        assert!(bm.flow_with_guess(&image_i, &points, &points, &image_j).is_err());
+        assert!(bm.flow(&image_j, &points, &image_i).is_err());
+    }
+}
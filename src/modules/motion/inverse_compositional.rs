@@ -0,0 +1,551 @@
+use core::tracking::{Track, TrackMut};
+use error::{Error, ErrorKind, Result};
+use euclidean::Region2D as Region;
+use nalgebra::{DMat, DVec, Inv};
+use piston_image::GrayImage;
+use std::{cmp, f64};
+use super::lucas_kanade::subpx;
+
+/// The number of parameters of an affine warp.
+const NPARAMS: usize = 6;
+
+/// An affine warp `W(x; p)` with the parametrization used by Baker and Matthews:
+///
+/// ```text
+/// W(x; p) = | 1 + p₁   p₃     p₅ |   | x |
+///           | p₂     1 + p₄   p₆ | × | y |
+///                                    | 1 |
+/// ```
+///
+/// The identity warp is `p = 0`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AffineWarp {
+    /// The parameters `[p₁, p₂, p₃, p₄, p₅, p₆]`.
+    pub p: [f64; NPARAMS],
+}
+
+impl AffineWarp {
+
+    /// Returns the identity warp.
+    pub fn identity() -> AffineWarp {
+        AffineWarp { p: [0.0; NPARAMS] }
+    }
+
+    /// Returns a pure translation.
+    pub fn translation(tx: f64, ty: f64) -> AffineWarp {
+        AffineWarp { p: [0.0, 0.0, 0.0, 0.0, tx, ty] }
+    }
+
+    /// Warps the point (`x`, `y`).
+    pub fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        let [[a, b, c], [d, e, f]] = self.matrix();
+
+        (a * x + b * y + c, d * x + e * y + f)
+    }
+
+    /// Returns the composition `self ∘ other`, i.e., the warp `x ↦ self(other(x))`.
+    pub fn compose(&self, other: &AffineWarp) -> AffineWarp {
+        let [[a0, b0, c0], [d0, e0, f0]] = self.matrix();
+        let [[a1, b1, c1], [d1, e1, f1]] = other.matrix();
+
+        AffineWarp::from_matrix([
+            [a0 * a1 + b0 * d1, a0 * b1 + b0 * e1, a0 * c1 + b0 * f1 + c0],
+            [d0 * a1 + e0 * d1, d0 * b1 + e0 * e1, d0 * c1 + e0 * f1 + f0],
+        ])
+    }
+
+    /// Returns the inverse warp, or `None` if the warp is degenerate.
+    pub fn inverse(&self) -> Option<AffineWarp> {
+        let [[a, b, c], [d, e, f]] = self.matrix();
+
+        let det = a * e - b * d;
+
+        if det.abs() < f64::EPSILON {
+            return None;
+        }
+
+        Some(AffineWarp::from_matrix([
+            [ e / det, -b / det, (b * f - c * e) / det],
+            [-d / det,  a / det, (c * d - a * f) / det],
+        ]))
+    }
+
+    /// Returns the top two rows of the 3×3 warp matrix.
+    pub fn matrix(&self) -> [[f64; 3]; 2] {
+        let p = &self.p;
+
+        [[1.0 + p[0], p[2], p[4]], [p[1], 1.0 + p[3], p[5]]]
+    }
+
+    /// Constructs a warp from the top two rows of a 3×3 warp matrix.
+    pub fn from_matrix(m: [[f64; 3]; 2]) -> AffineWarp {
+        AffineWarp { p: [m[0][0] - 1.0, m[1][0], m[0][1], m[1][1] - 1.0, m[0][2], m[1][2]] }
+    }
+}
+
+/// Inverse-compositional [Lucas-Kanade] template aligner
+///
+/// Estimates the affine warp that aligns a template (a region of image `I`) with image `J`.
+/// Unlike `PyramLk`, which estimates a translation per point, the warp of the whole patch can
+/// represent rotation, scale and shear.
+///
+/// ## The inverse compositional algorithm
+///
+/// The roles of the template and the image are switched: the update `Δp` is computed on the
+/// template and the current warp is updated with the inverse of the incremental warp,
+/// `W(x; p) ← W(x; p) ∘ W(x; Δp)⁻¹`. As a result, the steepest-descent images `∇T ∂W/∂p` and
+/// the Hessian are computed once per template instead of once per iteration.
+///
+/// The template coordinates are centred on the region, so that the linear part of the warp is
+/// expressed about the centre of the target.
+///
+/// ## References/Resources
+///
+/// * [Simon Baker and Iain Matthews. Lucas-Kanade 20 Years On: A Unifying Framework. 2004][1]
+///
+/// [Lucas-Kanade]: https://en.wikipedia.org/wiki/Lucas%E2%80%93Kanade_method
+/// [1]: https://www.ri.cmu.edu/pub_files/pub3/baker_simon_2004_1/baker_simon_2004_1.pdf
+#[derive(Debug)]
+pub struct InverseCompositional {
+    /// The maximum number of iterations.
+    max_iterations: usize,
+    /// The iterations stop once the norm of the update `‖Δp‖` falls below `epsilon`.
+    epsilon: f64,
+}
+
+impl InverseCompositional {
+
+    /// Constructs an `InverseCompositional` aligner.
+    ///
+    /// If `max_iterations` is 0, 1 is chosen.
+    pub fn new(max_iterations: usize, epsilon: f64) -> InverseCompositional {
+
+        InverseCompositional { max_iterations: cmp::max(1, max_iterations), epsilon }
+    }
+
+    /// Aligns the template `region` of `image_i` with `image_j`.
+    ///
+    /// # Arguments
+    ///
+    /// * `image_i` - The image containing the template.
+    /// * `region` - The template (a bounding box on `image_i`).
+    /// * `image_j` - The image to align the template with.
+    /// * `initial` - The initial estimate of the warp, in template coordinates relative to the
+    ///   centre of `region` (see `AffineWarp::identity` for no motion).
+    ///
+    /// # Returns
+    ///
+    /// Returns the warp `W` such that the template point at offset `x` from the centre of
+    /// `region` is found at offset `W(x)` from the same centre on `image_j`.
+    pub fn align(&self,
+                 image_i: &GrayImage,
+                 region: Region,
+                 image_j: &GrayImage,
+                 initial: AffineWarp) -> Result<AffineWarp>
+    {
+        self.align_template(&Template::new(image_i, region)?, image_j, initial)
+    }
+
+    /// Aligns a precomputed `template` with `image_j` (see `align`).
+    fn align_template(&self, template: &Template, image_j: &GrayImage, initial: AffineWarp)
+        -> Result<AffineWarp>
+    {
+        let (cx, cy) = template.centre;
+
+        // W(x; p) in image coordinates
+        let mut warp = AffineWarp::translation(cx, cy).compose(&initial);
+
+        for _ in 0..self.max_iterations {
+            // b = Σ [∇T ∂W/∂p]ᵀ [I(W(x; p)) − T(x)]
+            let mut b = DVec::new_zeros(NPARAMS);
+
+            for (k, &(x, y)) in template.coordinates.iter().enumerate() {
+                let (x_j, y_j) = warp.apply(x, y);
+
+                let error = subpx(image_j, x_j as f32, y_j as f32) - template.intensities[k];
+
+                for n in 0..NPARAMS {
+                    b[n] += template.steepest_descent[k][n] * error as f64;
+                }
+            }
+
+            let δp = template.hessian_inv.clone() * b;
+
+            let update = AffineWarp { p: [δp[0], δp[1], δp[2], δp[3], δp[4], δp[5]] };
+
+            // W(x; p) ← W(x; p) ∘ W(x; Δp)⁻¹
+            let update_inv = update.inverse().ok_or_else(|| {
+                Error::new(ErrorKind::Motion, "the incremental warp is not invertible")
+            })?;
+
+            warp = warp.compose(&update_inv);
+
+            let norm = update.p.iter().fold(0.0, |sum, δ| sum + δ * δ).sqrt();
+
+            if !norm.is_finite() {
+                return Err(Error::new(ErrorKind::Motion, "the alignment diverged"));
+            }
+
+            if norm < self.epsilon {
+                break;
+            }
+        }
+
+        Ok(AffineWarp::translation(-cx, -cy).compose(&warp))
+    }
+}
+
+impl Default for InverseCompositional {
+
+    fn default() -> Self {
+        InverseCompositional { max_iterations: 50, epsilon: 1e-3 }
+    }
+}
+
+impl Track<GrayImage> for InverseCompositional {
+
+    /// Inverse-compositional template tracker
+    ///
+    /// # Arguments
+    ///
+    /// * `image_i` - Image at `t`.
+    /// * `region` - A bounding box.
+    /// * `image_j` - Image at `t + 1`.
+    ///
+    /// # Returns
+    ///
+    /// Returns the bounding box of the warped region at `t + 1`. Use `align` to get the warp
+    /// itself (including rotation and shear). The warp is lost between calls, so the box of a
+    /// rotating target grows if the calls are chained; use `TemplateTracker` to follow a target
+    /// over a sequence.
+    fn track(&self, image_i: &GrayImage, region: Region, image_j: &GrayImage) -> Result<Region> {
+
+        let warp = {
+            self.align(image_i, region, image_j, AffineWarp::identity())
+                .map_err(|err| Error::new(ErrorKind::Tracking, err))?
+        };
+
+        warped_bounds(region, &warp, image_j.dimensions())
+    }
+}
+
+/// A template tracker that keeps the affine warp across frames
+///
+/// The template is taken once, from the first frame, and every following frame is aligned with
+/// it by `InverseCompositional`, starting from the warp found on the previous frame. The warp
+/// (rotation and shear included) is accumulated rather than re-estimated from a bounding box,
+/// so the target doesn't drift or take in the background.
+///
+/// ```rust,ignore
+/// use miro::core::tracking::TrackMut;
+/// use miro::modules::motion::TemplateTracker;
+///
+/// let mut tracker = TemplateTracker::default();
+///
+/// region = tracker.track_mut(&image_i, region, &image_j)?;
+/// let warp = tracker.warp();
+/// ```
+pub struct TemplateTracker {
+    aligner: InverseCompositional,
+    /// The template and its bounding box on the first frame.
+    template: Option<(Template, Region)>,
+    warp: AffineWarp,
+}
+
+impl TemplateTracker {
+
+    /// Constructs a tracker that aligns the frames with `aligner`.
+    pub fn new(aligner: InverseCompositional) -> TemplateTracker {
+
+        TemplateTracker { aligner, template: None, warp: AffineWarp::identity() }
+    }
+
+    /// Returns the bounding box of the template on the first frame, if a target is being
+    /// tracked.
+    pub fn template(&self) -> Option<Region> {
+        self.template.as_ref().map(|&(_, region)| region)
+    }
+
+    /// Returns the warp of the template on the last frame, relative to the centre of the
+    /// template (see `InverseCompositional::align`).
+    pub fn warp(&self) -> AffineWarp {
+        self.warp
+    }
+
+    /// Forgets the current target. The next call to `track_mut` takes a new template.
+    pub fn reset(&mut self) {
+        self.template = None;
+        self.warp = AffineWarp::identity();
+    }
+}
+
+impl Default for TemplateTracker {
+
+    fn default() -> Self {
+        TemplateTracker::new(InverseCompositional::default())
+    }
+}
+
+impl TrackMut<GrayImage> for TemplateTracker {
+
+    /// Aligns the template with `image_j`.
+    ///
+    /// The template is the `region` of `image_i` on the first call (or after `reset`); both are
+    /// ignored by the following calls.
+    ///
+    /// # Returns
+    ///
+    /// Returns the bounding box of the warped template on `image_j`, a value derived from
+    /// `warp`. On failure, the warp of the previous frame is kept.
+    fn track_mut(&mut self, image_i: &GrayImage, region: Region, image_j: &GrayImage) -> Result<Region> {
+
+        if self.template.is_none() {
+            let template = {
+                Template::new(image_i, region).map_err(|err| Error::new(ErrorKind::Tracking, err))?
+            };
+
+            self.template = Some((template, region));
+            self.warp = AffineWarp::identity();
+        }
+
+        let (warp, bounds) = {
+            let &(ref template, region) = self.template.as_ref().unwrap();
+
+            let warp = {
+                self.aligner.align_template(template, image_j, self.warp)
+                    .map_err(|err| Error::new(ErrorKind::Tracking, err))?
+            };
+
+            (warp, warped_bounds(region, &warp, image_j.dimensions())?)
+        };
+
+        self.warp = warp;
+
+        Ok(bounds)
+    }
+}
+
+/// Returns the bounding box of `region` warped by `warp` (relative to the centre of `region`),
+/// or an error if the box is outside an image of `dimensions`.
+fn warped_bounds(region: Region, warp: &AffineWarp, (w_j, h_j): (u32, u32)) -> Result<Region> {
+
+    let (x, y, width, height) = region.bounds();
+    let (cx, cy) = (x + width / 2.0, y + height / 2.0);
+    let (hw, hh) = (width / 2.0, height / 2.0);
+
+    let corners = [(-hw, -hh), (hw, -hh), (-hw, hh), (hw, hh)];
+
+    let (mut min_x, mut min_y) = (f64::INFINITY, f64::INFINITY);
+    let (mut max_x, mut max_y) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+
+    for &(u, v) in &corners {
+        let (u, v) = warp.apply(u, v);
+
+        min_x = min_x.min(u + cx);
+        min_y = min_y.min(v + cy);
+        max_x = max_x.max(u + cx);
+        max_y = max_y.max(v + cy);
+    }
+
+    if max_x < 0.0 || max_y < 0.0 || min_x >= w_j as f64 || min_y >= h_j as f64 {
+        return Err(Error::new(ErrorKind::Tracking, "the target left the image"));
+    }
+
+    Ok([min_x, min_y, max_x - min_x, max_y - min_y].into())
+}
+
+/// The precomputed quantities of the inverse compositional algorithm.
+struct Template {
+    /// The centre of the region on the image, in image coordinates.
+    centre: (f64, f64),
+    /// The template coordinates `x` (relative to `centre`) of every template pixel.
+    coordinates: Vec<(f64, f64)>,
+    /// `T(x)`
+    intensities: Vec<f32>,
+    /// The steepest-descent images `∇T ∂W/∂p`, evaluated at `p = 0`.
+    steepest_descent: Vec<[f64; NPARAMS]>,
+    /// The inverse of the Hessian `Σ [∇T ∂W/∂p]ᵀ [∇T ∂W/∂p]`.
+    hessian_inv: DMat<f64>,
+}
+
+impl Template {
+
+    fn new(image: &GrayImage, region: Region) -> Result<Template> {
+
+        let (x0, y0, width, height) = region.bounds();
+
+        if width < 2.0 || height < 2.0 {
+            return Err(Error::new(ErrorKind::Motion, "the template must be at least 2×2 pixels"));
+        }
+
+        let centre = (x0 + width / 2.0, y0 + height / 2.0);
+
+        let (ncols, nrows) = (width as usize, height as usize);
+
+        let mut coordinates = Vec::with_capacity(ncols * nrows);
+        let mut intensities = Vec::with_capacity(ncols * nrows);
+        let mut steepest_descent = Vec::with_capacity(ncols * nrows);
+
+        let mut hessian = DMat::new_zeros(NPARAMS, NPARAMS);
+
+        for row in 0..nrows {
+            for col in 0..ncols {
+                let x = (x0 + col as f64) as f32;
+                let y = (y0 + row as f64) as f32;
+
+                // ∇T
+                let tx = ((subpx(image, x + 1.0, y) - subpx(image, x - 1.0, y)) / 2.0) as f64;
+                let ty = ((subpx(image, x, y + 1.0) - subpx(image, x, y - 1.0)) / 2.0) as f64;
+
+                let (u, v) = (x as f64 - centre.0, y as f64 - centre.1);
+
+                // ∇T ∂W/∂p, where ∂W/∂p = | u 0 v 0 1 0 |
+                //                         | 0 u 0 v 0 1 |
+                let sd = [tx * u, ty * u, tx * v, ty * v, tx, ty];
+
+                for r in 0..NPARAMS {
+                    for c in 0..NPARAMS {
+                        hessian[(r, c)] += sd[r] * sd[c];
+                    }
+                }
+
+                coordinates.push((u, v));
+                intensities.push(subpx(image, x, y));
+                steepest_descent.push(sd);
+            }
+        }
+
+        let hessian_inv = hessian.inv().ok_or_else(|| {
+            Error::new(ErrorKind::Motion, "the Hessian of the template is singular")
+        })?;
+
+        Ok(Template { centre, coordinates, intensities, steepest_descent, hessian_inv })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::tracking::{Track, TrackMut};
+    use piston_image::{GrayImage, Luma};
+    use super::{warped_bounds, AffineWarp, InverseCompositional, TemplateTracker};
+
+    /// The texture of `fixtures::texture`, warped by `warp` about (48, 48).
+    fn warped(warp: &AffineWarp) -> GrayImage {
+        let inverse = warp.inverse().unwrap();
+
+        GrayImage::from_fn(96, 96, |x, y| {
+            let (x, y) = inverse.apply(x as f64 - 48.0, y as f64 - 48.0);
+            let (x, y) = (x + 48.0, y + 48.0);
+            let value = 127.5 + 63.0 * (x / 7.0).sin() * (y / 11.0).cos() + 63.0 * ((x + y) / 17.0).sin();
+
+            Luma { data: [value as u8] }
+        })
+    }
+
+    /// A rotation by `angle` and a scaling by `scale`, followed by a translation.
+    fn similarity(scale: f64, angle: f64, tx: f64, ty: f64) -> AffineWarp {
+        let (sin, cos) = angle.sin_cos();
+
+        AffineWarp::from_matrix([[scale * cos, -scale * sin, tx], [scale * sin, scale * cos, ty]])
+    }
+
+    fn assert_close(a: &AffineWarp, b: &AffineWarp) {
+        for (n, (p_a, p_b)) in a.p.iter().zip(b.p.iter()).enumerate() {
+            // the linear part, then the translation
+            let tolerance = if n < 4 { 5e-3 } else { 5e-2 };
+
+            assert!((p_a - p_b).abs() < tolerance, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn compose_with_inverse() {
+        let warp = AffineWarp { p: [0.1, -0.2, 0.05, 0.3, 4.0, -2.0] };
+        let identity = warp.compose(&warp.inverse().unwrap());
+
+        for (a, b) in identity.p.iter().zip(AffineWarp::identity().p.iter()) {
+            assert!((a - b).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn translation() {
+        let (x, y) = AffineWarp::translation(2.0, -1.0).apply(1.0, 1.0);
+
+        assert_eq!((x, y), (3.0, 0.0));
+    }
+
+    #[test]
+    fn align_recovers_the_warp() {
+        let image_i = warped(&AffineWarp::identity());
+
+        // rotated by 0.05 rad, scaled by 1.03 and sheared
+        let mut warp = similarity(1.03, 0.05, 1.5, -1.0);
+        warp.p[2] += 0.02;
+
+        let aligned = InverseCompositional::default()
+            .align(&image_i, [32.0, 32.0, 32.0, 32.0].into(), &warped(&warp), AffineWarp::identity())
+            .unwrap();
+
+        assert_close(&aligned, &warp);
+    }
+
+    #[test]
+    fn track_a_translation() {
+        let (image_i, image_j) = (warped(&AffineWarp::identity()), warped(&AffineWarp::translation(2.0, 1.0)));
+
+        let region = InverseCompositional::default().track(&image_i, [32.0, 32.0, 32.0, 32.0].into(), &image_j).unwrap();
+        let (x, y, width, height) = region.bounds();
+
+        assert!((x - 34.0).abs() < 0.05 && (y - 33.0).abs() < 0.05, "{:?}", region.bounds());
+        assert!((width - 32.0).abs() < 0.05 && (height - 32.0).abs() < 0.05, "{:?}", region.bounds());
+    }
+
+    #[test]
+    fn template_tracker_keeps_the_warp() {
+        let image_i = warped(&AffineWarp::identity());
+
+        let mut tracker = TemplateTracker::default();
+        let mut region = [32.0, 32.0, 32.0, 32.0].into();
+
+        // the target turns by 0.04 rad and moves by (1, 0.5) per frame
+        for k in 1..7 {
+            let warp = similarity(1.0, 0.04 * k as f64, k as f64, 0.5 * k as f64);
+
+            region = tracker.track_mut(&image_i, region, &warped(&warp)).unwrap();
+
+            assert_close(&tracker.warp(), &warp);
+        }
+
+        // the box of the square turned by 0.24 rad, not a box that grows with every frame
+        let (_, _, width, height) = region.bounds();
+        let expected = 32.0 * (0.24f64.cos() + 0.24f64.sin());
+
+        assert!((width - expected).abs() < 0.2 && (height - expected).abs() < 0.2, "{:?}", region.bounds());
+
+        tracker.reset();
+
+        assert!(tracker.template().is_none());
+    }
+
+    #[test]
+    fn errors() {
+        let image = warped(&AffineWarp::identity());
+        let uniform = GrayImage::from_pixel(96, 96, Luma { data: [120] });
+
+        let aligner = InverseCompositional::default();
+
+        // a template smaller than 2×2
+        assert!(aligner.align(&image, [32.0, 32.0, 1.0, 8.0].into(), &image, AffineWarp::identity()).is_err());
+        // a singular Hessian
+        assert!(aligner.align(&uniform, [32.0, 32.0, 32.0, 32.0].into(), &uniform, AffineWarp::identity()).is_err());
+        assert!(aligner.track(&uniform, [32.0, 32.0, 32.0, 32.0].into(), &uniform).is_err());
+        assert!(TemplateTracker::default().track_mut(&uniform, [32.0, 32.0, 32.0, 32.0].into(), &uniform).is_err());
+
+        // a target that left the image
+        let left = AffineWarp::translation(-100.0, 0.0);
+
+        assert!(warped_bounds([32.0, 32.0, 32.0, 32.0].into(), &left, (96, 96)).is_err());
+        assert!(warped_bounds([32.0, 32.0, 32.0, 32.0].into(), &AffineWarp::identity(), (96, 96)).is_ok());
+    }
+}
@@ -1,2 +1,2 @@
-pub use self::pyramidal::PyramLk;
+pub use self::pyramidal::{subpx, PyramLk, PyramLkBuilder};
 mod pyramidal;
\ No newline at end of file
@@ -1,14 +1,14 @@
-use core::motion::Flow;
-use error::Result;
+use core::motion::{Flow, FlowPoint, FlowStatus};
+use error::{Error, ErrorKind, Result};
 use euclidean::Size2D;
 use float::FloatGuard;
-use image::GrayPyramid;
+use image::{Intensity, LumaImage, LumaPyramid};
 use nalgebra::{Inv, Mat2, Vec2};
 use num;
-use piston_image::{GrayImage, Luma};
-use piston_imageproc::filter;
-use std::cmp;
-use utility::plane_euclidean::{OptPoint, Point};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::{cmp, f32};
+use utility::plane_euclidean::Point;
 
 /// Pyramidal Implementation of the Lucas Kanade Feature Tracker
 ///
@@ -30,6 +30,38 @@ use utility::plane_euclidean::{OptPoint, Point};
 /// 2) The image patch around the tracked point varies too much between image `I` and 
 ///    image `J` (the point disappears due to occlusion).
 ///
+/// The first case is reported through the status of the returned `FlowPoint`s. The second case 
+/// is left to the caller, who can reject points based on the `residual` of each `FlowPoint`.
+///
+/// Points are also rejected when the spatial gradient matrix is too weak to be inverted reliably 
+/// (see `PyramLkBuilder::min_eigenvalue`), or when the iterative L-K diverges, i.e., the flow 
+/// computed at a level leaves the integration window.
+///
+/// ## Pixel types
+///
+/// `PyramLk` tracks points on 8-bit (`GrayImage`), 16-bit (`Gray16Image`) and floating point 
+/// (`Gray32FImage`) images, and on pyramids of those images, without quantizing them. Intensities 
+/// are used in the range of the channel type, so the minimum eigenvalue threshold and the 
+/// residuals scale with it (e.g., by 257² and 257 respectively from 8-bit to 16-bit images).
+///
+/// ## Parallelism
+///
+/// Every point is tracked independently. With the `parallel` feature enabled, the points are 
+/// distributed over a thread pool. The results are returned in the same order either way.
+///
+/// ## Construction
+///
+/// ```rust,ignore
+/// use miro::modules::motion::PyramLk;
+///
+/// let lk = PyramLk::builder()
+///     .height(3)
+///     .window(5, 5)
+///     .max_iterations(30)
+///     .epsilon(0.01)
+///     .build();
+/// ```
+///
 /// ## References/Resources
 ///
 /// * [Jean-Yves Bouguet. Pyramidal Impl. of the Lucas-Kanade Feature Tracker. Intel Corp., 2001][1]
@@ -63,287 +95,465 @@ pub struct PyramLk {
     /// integration window. Typical values for ωₓ₁ and ωₓ₂ are 2,3,4,5,6,7 pixels.
     win: Size2D<u16>,
     
-    k: usize,
+    /// The maximum number of L-K iterations per level.
+    max_iterations: usize,
+
+    /// The iterations at a level stop early once the norm of the flow update ‖η‖ falls below 
+    /// `epsilon`.
+    epsilon: Option<f32>,
+
+    /// Points whose normalized minimum eigenvalue of the spatial gradient matrix (see 
+    /// `FlowPoint::min_eigenvalue`) is below this threshold are rejected.
+    min_eigenvalue: f32,
 }
 
 impl PyramLk {
 
+    /// Constructs a `PyramLk` with a pyramid of `height` levels, a square integration window 
+    /// `win` and exactly `k` iterations per level.
+    ///
+    /// The iterations don't stop early (no `epsilon`), and only the points whose spatial 
+    /// gradient matrix is singular are rejected (a `min_eigenvalue` of 0). See 
+    /// `PyramLk::builder` for finer control.
     pub fn new(height: usize, win: u16, k: usize) -> PyramLk {
 
-        PyramLk {
-            height,
-            win: [win, win].into(),
-            k,
-        }
+        PyramLk::builder()
+            .height(height)
+            .window(win, win)
+            .max_iterations(k)
+            .epsilon(None)
+            .min_eigenvalue(0.0)
+            .build()
+    }
+
+    /// Returns a builder initialized with the default parameters.
+    pub fn builder() -> PyramLkBuilder {
+
+        PyramLkBuilder::default()
     }
 }
 
 impl Default for PyramLk {
     
-    /// Constructs a new `PyramLk` using the default `height` and `win` parameters.
+    /// Constructs a new `PyramLk` using the default parameters.
     fn default() -> Self {
+        PyramLk::builder().build()
+    }
+}
+
+/// A builder for `PyramLk`.
+///
+/// The termination criteria of the iterative L-K follow Bouguet's paper: at each level, the 
+/// iterations stop after `max_iterations` or once ‖η‖ < `epsilon`, whichever comes first.
+#[derive(Clone, Debug)]
+pub struct PyramLkBuilder {
+    height: usize,
+    win: [u16; 2],
+    max_iterations: usize,
+    epsilon: Option<f32>,
+    min_eigenvalue: f32,
+}
+
+impl PyramLkBuilder {
+
+    /// Sets the height (# of layers) of the image pyramid. If `height` is 0, 1 is chosen.
+    pub fn height(mut self, height: usize) -> Self {
+        self.height = cmp::max(1, height);
+        self
+    }
+
+    /// Sets the integration window size (ωₓ₁, ωₓ₂). A size of 0 is replaced by 1.
+    pub fn window(mut self, width: u16, height: u16) -> Self {
+        self.win = [cmp::max(1, width), cmp::max(1, height)];
+        self
+    }
+
+    /// Sets the maximum number of iterations per level. If `max_iterations` is 0, 1 is chosen.
+    pub fn max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = cmp::max(1, max_iterations);
+        self
+    }
+
+    /// Stops the iterations at a level once ‖η‖ < `epsilon` (in pixels).
+    pub fn epsilon<E>(mut self, epsilon: E) -> Self where E: Into<Option<f32>> {
+        self.epsilon = epsilon.into();
+        self
+    }
+
+    /// Sets the minimum eigenvalue threshold for rejecting weak features.
+    pub fn min_eigenvalue(mut self, min_eigenvalue: f32) -> Self {
+        self.min_eigenvalue = min_eigenvalue;
+        self
+    }
+
+    /// Constructs the `PyramLk`.
+    pub fn build(self) -> PyramLk {
+
         PyramLk {
+            height: self.height,
+            win: self.win.into(),
+            max_iterations: self.max_iterations,
+            epsilon: self.epsilon,
+            min_eigenvalue: self.min_eigenvalue,
+        }
+    }
+}
+
+impl Default for PyramLkBuilder {
+
+    fn default() -> Self {
+        PyramLkBuilder {
             height: 4,
-            win: [7, 7].into(),
-            k: 4,
+            win: [7, 7],
+            max_iterations: 20,
+            epsilon: Some(0.03),
+            min_eigenvalue: 1e-4,
         }
     }
 }
 
-impl Flow<GrayImage> for PyramLk {
+impl<T> Flow<LumaImage<T>> for PyramLk where T: Intensity {
 
     /// Computes the optical flow.
-    fn flow(&self, image_i: &GrayImage, points_i: &[Point<f32>], image_j: &GrayImage) 
-        -> Result<Vec<OptPoint<f32>>> {
+    fn flow(&self, image_i: &LumaImage<T>, points_i: &[Point<f32>], image_j: &LumaImage<T>) 
+        -> Result<Vec<FlowPoint>> {
 
-        let pyramid_i = build_pyramid(image_i, self.height);
+        let pyramid_i = LumaPyramid::build(image_i, self.height);
             
-        let pyramid_j = build_pyramid(image_j, self.height);
+        let pyramid_j = LumaPyramid::build(image_j, self.height);
         
         self.flow(&pyramid_i, points_i, &pyramid_j)
     }
+
+    /// Computes the optical flow, starting from predicted locations on image `J`.
+    fn flow_with_guess(&self, 
+                       image_i: &LumaImage<T>, 
+                       points_i: &[Point<f32>], 
+                       guesses_j: &[Point<f32>], 
+                       image_j: &LumaImage<T>) -> Result<Vec<FlowPoint>> 
+    {
+        let pyramid_i = LumaPyramid::build(image_i, self.height);
+            
+        let pyramid_j = LumaPyramid::build(image_j, self.height);
+        
+        self.flow_with_guess(&pyramid_i, points_i, guesses_j, &pyramid_j)
+    }
 }
 
-impl Flow<GrayPyramid> for PyramLk {
+impl<T> Flow<LumaPyramid<T>> for PyramLk where T: Intensity {
 
     /// Computes the optical flow using pre-computed image pyramids.
-    fn flow(&self, pyramid_i: &GrayPyramid, points_i: &[Point<f32>], pyramid_j: &GrayPyramid)
-        -> Result<Vec<OptPoint<f32>>> 
+    fn flow(&self, pyramid_i: &LumaPyramid<T>, points_i: &[Point<f32>], pyramid_j: &LumaPyramid<T>)
+        -> Result<Vec<FlowPoint>> 
     {
+        self.flow_pyramids(pyramid_i, points_i, None, pyramid_j)
+    }
+
+    /// Computes the optical flow using pre-computed image pyramids, starting from predicted 
+    /// locations on image `J`.
+    fn flow_with_guess(&self, 
+                       pyramid_i: &LumaPyramid<T>, 
+                       points_i: &[Point<f32>], 
+                       guesses_j: &[Point<f32>], 
+                       pyramid_j: &LumaPyramid<T>) -> Result<Vec<FlowPoint>> 
+    {
+        if guesses_j.len() != points_i.len() {
+            let message = format!("expected {} guesses, found {}", points_i.len(), guesses_j.len());
+
+            return Err(Error::new(ErrorKind::Motion, message));
+        }
+
+        self.flow_pyramids(pyramid_i, points_i, Some(guesses_j), pyramid_j)
+    }
+}
+
+impl PyramLk {
+
+    fn flow_pyramids<T>(&self, 
+                        pyramid_i: &LumaPyramid<T>, 
+                        points_i: &[Point<f32>], 
+                        guesses_j: Option<&[Point<f32>]>,
+                        pyramid_j: &LumaPyramid<T>) -> Result<Vec<FlowPoint>> 
+        where T: Intensity
+    {
+        let guess_j = |index: usize| guesses_j.map(|guesses_j| &guesses_j[index]);
+
+        // Every point is tracked independently, so the points can be processed in any order. 
+        // The output order is always the order of `points_i`.
+        #[cfg(not(feature = "parallel"))]
+        let corresponding_points = {
+            points_i.iter()
+                .enumerate()
+                .map(|(index, point_i)| {
+                    self.flow_point(pyramid_i, point_i, guess_j(index), pyramid_j)
+                })
+                .collect()
+        };
+
+        #[cfg(feature = "parallel")]
+        let corresponding_points = {
+            points_i.par_iter()
+                .enumerate()
+                .map(|(index, point_i)| {
+                    self.flow_point(pyramid_i, point_i, guess_j(index), pyramid_j)
+                })
+                .collect()
+        };
+            
+        Ok(corresponding_points)
+    }
 
+    /// Tracks a single point.
+    fn flow_point<T>(&self, 
+                     pyramid_i: &LumaPyramid<T>, 
+                     point_i: &Point<f32>, 
+                     guess_j: Option<&Point<f32>>,
+                     pyramid_j: &LumaPyramid<T>) -> FlowPoint 
+        where T: Intensity
+    {
         let [window_width, window_height] = self.win.dimensions();
         let (w_j, h_j) = pyramid_j[0].dimensions();
+
+        // The top level `Lₘ` of the pyramids
+        let top = cmp::min(pyramid_i.len(), pyramid_j.len()) - 1;
+
+        let [x_i, y_i] = point_i.coordinates();
+        
+        if x_i < 0.0 || y_i < 0.0 || x_i >= (w_j as f32) || y_i >= (h_j as f32) {
+            debug!("Point {} is outside image bounds {}x{}", point_i, w_j, h_j);
+
+            return FlowPoint {
+                status: FlowStatus::OutOfBounds,
+                point: *point_i,
+                residual: f32::NAN,
+                min_eigenvalue: f32::NAN,
+            };
+        }
+
+        let zero: FloatGuard<f32> = num::zero();
+        
+        // The optical flow vector at level `L` or the flow at the bottom of the pyramid.
+        let mut ground_flow = Vec2 {x: zero, y: zero};
+        
+        // Initialization of the pyramidal guess: `gᴸᵐ = (v̂ - u)/2ᴸᵐ`, where `v̂` is the 
+        // predicted location of the point on `J`, or zero if there is no prediction
+        let mut pyramidal_guess = match guess_j {
+            Some(guess_j) => {
+                let [x_guess, y_guess] = guess_j.coordinates();
+                let scale = (2.0f32).powi(top as i32);
+
+                Vec2 {x: (x_guess - x_i) / scale, y: (y_guess - y_i) / scale}
+            },
+            None => Vec2 {x: zero, y: zero},
+        };
+
+        // Quality measures of the match (see `FlowPoint`)
+        let mut residual = f32::NAN;
+        let mut min_eigenvalue = f32::NAN;
         
-        let mut corresponding_points = Vec::with_capacity(points_i.len());
+        // for L=L_m down to 0 with a step of -1
+        for (level, (i, j)) in pyramid_i.iter().zip(pyramid_j.iter()).enumerate().rev() {
+            // Spatial gradient matrix
+            let mut h_i = Mat2::new(zero, zero, zero, zero);
             
-        'points: for point_i in points_i {
-            let [x_i, y_i] = point_i.coordinates();
+            // Location of point `scaled_u` on the image `currI`: `uᶫ = u/2ᶫ`
+            // Observe that in particular, `u⁰ = u/2⁰ = u`
+            let scaled_x_i = x_i / (2.0f32).powi(level as i32);
+            let scaled_y_i = y_i / (2.0f32).powi(level as i32);
             
-            if x_i < 0.0 || y_i < 0.0 || x_i >= (w_j as f32) || y_i >= (h_j as f32) {
-                // TODO return error instead?
-                debug!("Point {} is outside image bounds {}x{}", point_i, w_j, h_j);
+            // farthest left x
+            let min_x_i = scaled_x_i - window_width as f32;
+            
+            // farthest bottom y
+            let min_y_i = scaled_y_i - window_height as f32;
+            
+            // farthest right x
+            let max_x_i = scaled_x_i + window_width as f32;
+            
+            // farthest top y
+            let max_y_i = scaled_y_i + window_height as f32;
+            
+            // Derivatives
+            let capacity = {max_x_i - min_x_i}.ceil() * {max_y_i - min_y_i}.ceil();
 
-                corresponding_points.push(None);
+            let mut derivatives_i = Vec::with_capacity(num::cast(capacity).unwrap());
+            
+            // Area of the neighborhood (integration window)
+            for x in min_x_i..max_x_i {
+                for y in min_y_i..max_y_i {
+                    
+                    let x: f32 = x.into();
+                    let y: f32 = y.into();
 
-                continue;
+                    // Derivative of `i` wrt `x`
+                    let fx = (
+                        subpx(i, x + 1., y) - subpx(i, x - 1., y)
+                    ) / 2.0;
+                    
+                    // Derivative of `i` wrt `y`
+                    let fy = (
+                        subpx(i, x, y + 1.) - subpx(i, x, y - 1.)
+                    ) / 2.0;
+                    
+                    derivatives_i.push((fx, fy));
+                    
+                    h_i.m11 += fx * fx;
+                    h_i.m12 += fx * fy;
+                    h_i.m21 += fx * fy;
+                    h_i.m22 += fy * fy;
+                }
             }
-
-            let zero: FloatGuard<f32> = num::zero();
             
-            // The optical flow vector at level `L` or the flow at the bottom of the pyramid.
-            let mut ground_flow = Vec2 {x: zero, y: zero};
+            // Minimum eigenvalue of the spatial gradient matrix, normalized by the area of 
+            // the integration window
+            min_eigenvalue = {
+                let (a, b, c) = (h_i.m11.into(): f32, h_i.m12.into(): f32, h_i.m22.into(): f32);
+                let area = derivatives_i.len() as f32;
+
+                ((a + c) / 2.0 - (((a - c) / 2.0).powi(2) + b * b).sqrt()) / area
+            };
+
+            // The inverse spatial gradient matrix
+            let h_i_inv = {
+                match h_i.inv() {
+                    Some(inv) if min_eigenvalue >= self.min_eigenvalue => inv,
+                    _ => {
+                        debug!("The spatial gradient matrix at point {} is singular", point_i);
+
+                        return FlowPoint {
+                            status: FlowStatus::Singular,
+                            point: *point_i,
+                            residual: f32::NAN,
+                            min_eigenvalue,
+                        };
+                    }
+                }
+            };
             
-            // Initialization of the pyramidal guess
-            let mut pyramidal_guess = Vec2 {x: zero, y: zero};
+            // Initialization of iterative L-K
+            let mut flowlk = Vec2::new(zero, zero);
             
-            // for L=L_m down to 0 with a step of -1
-            for (level, (i, j)) in pyramid_i.iter().zip(pyramid_j.iter()).enumerate().rev() {
-                // Spatial gradient matrix
-                let mut h_i = Mat2::new(zero, zero, zero, zero);
-                
-                // Location of point `scaled_u` on the image `currI`: `uᶫ = u/2ᶫ`
-                // Observe that in particular, `u⁰ = u/2⁰ = u`
-                let scaled_x_i = x_i / (2.0f32).powi(level as i32);
-                let scaled_y_i = y_i / (2.0f32).powi(level as i32);
-                
-                // farthest left x
-                let min_x_i = scaled_x_i - window_width as f32;
-                
-                // farthest bottom y
-                let min_y_i = scaled_y_i - window_height as f32;
+            for _ in 0..self.max_iterations {
+                // Image mismatch vector
+                let mut mismatch = Vec2::new(zero, zero);
                 
-                // farthest right x
-                let max_x_i = scaled_x_i + window_width as f32;
+                let mut it = derivatives_i.iter();
                 
-                // farthest top y
-                let max_y_i = scaled_y_i + window_height as f32;
-                
-                // Derivatives
-                let capacity = {max_x_i - min_x_i}.ceil() * {max_y_i - min_y_i}.ceil();
-
-                let mut derivatives_i = Vec::with_capacity(num::cast(capacity).unwrap());
-                
-                // Area of the neighborhood (integration window)
-                for x in min_x_i..max_x_i {
-                    for y in min_y_i..max_y_i {
+                for x_i_win in min_x_i..max_x_i {
+                    for y_i_win in min_y_i..max_y_i {
+                    
+                        let x_j_win: f32 = (x_i_win + pyramidal_guess[0] + flowlk[0]).into();
+                        let y_j_win: f32 = (y_i_win + pyramidal_guess[1] + flowlk[1]).into();
                         
-                        let x: f32 = x.into();
-                        let y: f32 = y.into();
+                        let x_i_win: f32 = x_i_win.into();
+                        let y_i_win: f32 = y_i_win.into();
 
-                        // Derivative of `i` wrt `x`
-                        let fx = (
-                            subpx(i, x + 1., y) - subpx(i, x - 1., y)
-                        ) / 2.0;
-                        
-                        // Derivative of `i` wrt `y`
-                        let fy = (
-                            subpx(i, x, y + 1.) - subpx(i, x, y - 1.)
-                        ) / 2.0;
-                        
-                        derivatives_i.push((fx, fy));
-                        
-                        h_i.m11 += fx * fx;
-                        h_i.m12 += fx * fy;
-                        h_i.m21 += fx * fy;
-                        h_i.m22 += fy * fy;
+                        // Image difference
+                        let delta = subpx(i, x_i_win, y_i_win) - subpx(j, x_j_win, y_j_win);
+                    
+                        let &(fx, fy) = it.next().unwrap();
+                        mismatch[0] += delta * fx;
+                        mismatch[1] += delta * fy;
                     }
                 }
                 
-                // The inverse spatial gradient matrix
-                let h_i_inv = {
-                    match h_i.inv() {
-                        Some(inv) => inv,
-                        None => {
-                            debug!("An error occurred while inverting a matrix");
-                            // TODO return an error for the current point instead?
-                            corresponding_points.push(None);
-                            continue 'points;
-                        }
+                // Optical flow (Lucas-Kanade)
+                let ηk = h_i_inv * mismatch;
+                
+                // Guess for next iteration
+                flowlk = flowlk + ηk;
+
+                if let Some(epsilon) = self.epsilon {
+                    let norm = (ηk.x.into(): f32).hypot(ηk.y.into(): f32);
+
+                    if norm < epsilon {
+                        break;
                     }
+                }
+            }
+
+            // The flow computed at this level should stay within the integration window
+            let (flow_x, flow_y) = (flowlk.x.into(): f32, flowlk.y.into(): f32);
+
+            if flow_x.abs() > window_width as f32 || flow_y.abs() > window_height as f32 {
+                debug!("The iterative L-K diverged at point {} (level {})", point_i, level);
+
+                return FlowPoint {
+                    status: FlowStatus::Diverged,
+                    point: *point_i,
+                    residual: f32::NAN,
+                    min_eigenvalue,
                 };
-                
-                // Initialization of iterative L-K
-                let mut flowlk = Vec2::new(zero, zero);
-                
-                for _ in 0..self.k {
-                    // Image mismatch vector
-                    let mut mismatch = Vec2::new(zero, zero);
-                    
-                    let mut it = derivatives_i.iter();
-                    
+            }
+            
+            if level == 0 {
+                ground_flow = flowlk;
+
+                residual = {
+                    let mut sum = 0.0;
+                    let mut area = 0.0;
+
                     for x_i_win in min_x_i..max_x_i {
                         for y_i_win in min_y_i..max_y_i {
-                        
+
                             let x_j_win: f32 = (x_i_win + pyramidal_guess[0] + flowlk[0]).into();
                             let y_j_win: f32 = (y_i_win + pyramidal_guess[1] + flowlk[1]).into();
-                            
+
                             let x_i_win: f32 = x_i_win.into();
                             let y_i_win: f32 = y_i_win.into();
 
-                            // Image difference
-                            let delta = subpx(i, x_i_win, y_i_win) - subpx(j, x_j_win, y_j_win);
-                        
-                            let &(fx, fy) = it.next().unwrap();
-                            mismatch[0] += delta * fx;
-                            mismatch[1] += delta * fy;
+                            sum += (subpx(i, x_i_win, y_i_win) - subpx(j, x_j_win, y_j_win)).abs();
+                            area += 1.0;
                         }
                     }
-                    
-                    // Optical flow (Lucas-Kanade)
-                    let ηk = h_i_inv * mismatch;
-                    
-                    // Guess for next iteration
-                    flowlk = flowlk + ηk;
-                }
-                
-                if level == 0 {
-                    ground_flow = flowlk;
-                } else {
-                    let two = unsafe { FloatGuard::from_unchecked(2.0) };
-                    // Guess for next iteration
-                    pyramidal_guess[0] = (pyramidal_guess[0] + flowlk[0]) * two;
-                    pyramidal_guess[1] = (pyramidal_guess[1] + flowlk[1]) * two;
-                }
-            }
-            
-            // The final optical flow vector
-            let resultant_flow = pyramidal_guess + ground_flow;
-            
-            // Location of point on image `J` (translation).
-            let x_j = x_i + resultant_flow.x;
-            let y_j = y_i + resultant_flow.y;
-            
-            if cmp::min(x_j, y_j) < 0.0 || x_j >= (w_j as f32) || y_j >= (h_j as f32) {
-                
-                corresponding_points.push(None);
-                continue;
+
+                    sum / area
+                };
+            } else {
+                let two = unsafe { FloatGuard::from_unchecked(2.0) };
+                // Guess for next iteration
+                pyramidal_guess[0] = (pyramidal_guess[0] + flowlk[0]) * two;
+                pyramidal_guess[1] = (pyramidal_guess[1] + flowlk[1]) * two;
             }
-            
-            // Location of point on `J` (v = u + final optical flow vector)
-            let corresponding_point = Some([x_j, y_j].into());
-            
-            corresponding_points.push(corresponding_point);
         }
-            
-        Ok(corresponding_points)
-    }
-}
-
-fn build_pyramid(im: &GrayImage, height: usize) -> GrayPyramid {
-    
-    /// Anti-aliasing filter kernel is used for pyramid construction (used for image anti-aliasing 
-    /// before image sub-sampling).
-    const KERNEL: [f64; 9] = [
-        // [1/16 1/4 3/8 1/4 1/16] × [1/16 1/4 3/8 1/4 1/16]T
-        1.0/16.0,   1.0/8.0,    1.0/16.0,
-        1.0/8.0,    1.0/4.0,    1.0/8.0,
-        1.0/16.0,   1.0/8.0,    1.0/16.0
-    ];
-
-    GrayPyramid::new(im.clone(), height, |prev| {
-
-        //  TODO : `filter3x3` creates a new image
-        let filtered_prev = filter::filter3x3(prev, &KERNEL);
         
-        let (prev_w, prev_h) = prev.dimensions();
+        // The final optical flow vector
+        let resultant_flow = pyramidal_guess + ground_flow;
         
-        let width = (prev_w + 1) / 2;
-        let height = (prev_h + 1) / 2;
-
-        let mut buffer = GrayImage::new(width, height);
+        // Location of point on image `J` (translation).
+        let x_j = x_i + resultant_flow.x;
+        let y_j = y_i + resultant_flow.y;
         
-        for x in 0..width {
-            for y in 0..height {
-                let prev_x = 2.0 * x as f32;
-                let prev_y = 2.0 * y as f32;
-
-                let sum1 = safepx(&filtered_prev, prev_x, prev_y) / 4.0;
-
-                let sum2 = {
-                    let mut sum = 0.0;
-                    sum += safepx(&filtered_prev, prev_x - 1.0, prev_y);
-                    sum += safepx(&filtered_prev, prev_x + 1.0, prev_y);
-                    sum += safepx(&filtered_prev, prev_x, prev_y - 1.0);
-                    sum += safepx(&filtered_prev, prev_x, prev_y + 1.0);
-                    sum /= 8.0;
-                    sum
-                };
-
-                let sum3 = {
-                    let mut sum = 0.0;
-                    sum += safepx(&filtered_prev, prev_x - 1.0, prev_y - 1.0);
-                    sum += safepx(&filtered_prev, prev_x + 1.0, prev_y - 1.0);
-                    sum += safepx(&filtered_prev, prev_x - 1.0, prev_y + 1.0);
-                    sum += safepx(&filtered_prev, prev_x + 1.0, prev_y + 1.0);
-                    sum /= 16.0;
-                    sum
-                };
-
-                let sum = sum1 + sum2 + sum3;
-
-                let data = [sum as u8];
-                let luma = Luma { data: data };
-
-                buffer.put_pixel(x, y, luma);
+        let status = {
+            if cmp::min(x_j, y_j) < 0.0 || x_j >= (w_j as f32) || y_j >= (h_j as f32) {
+                FlowStatus::Lost
+            } else {
+                FlowStatus::Tracked
             }
+        };
+        
+        // Location of point on `J` (v = u + final optical flow vector)
+        FlowPoint {
+            status,
+            point: [x_j, y_j].into(),
+            residual,
+            min_eigenvalue,
         }
-
-        buffer
-    })
+    }
 }
 
-fn safepx(im: &GrayImage, x: f32, y: f32) -> f32 {
+fn safepx<T>(im: &LumaImage<T>, x: f32, y: f32) -> f32 where T: Intensity {
     let (w, h) = im.dimensions();
     let (w, h) = (w as f32, h as f32);
 
     let x = x.min(w - 1.0).max(0.0) as u32;
     let y = y.min(h - 1.0).max(0.0) as u32;
 
-    im[(x, y)].data[0] as f32
+    im[(x, y)].data[0].to_intensity()
 }
 
 /// Subpixel computation
-pub fn subpx(im: &GrayImage, x: f32, y: f32) -> f32 {
+pub fn subpx<T>(im: &LumaImage<T>, x: f32, y: f32) -> f32 where T: Intensity {
     let x_0 = x.floor();
     let y_0 = y.floor();
         
@@ -365,16 +575,261 @@ pub fn subpx(im: &GrayImage, x: f32, y: f32) -> f32 {
 
 #[cfg(test)]
 mod tests {
+    use core::motion::{Flow, FlowPoint, FlowStatus};
+    use image::{Gray16Image, Gray32FImage};
+    use piston_image::{GrayImage, Luma};
+    use utility::fixtures;
+    use super::{safepx, subpx, PyramLk};
+
+    fn ramp() -> GrayImage {
+        GrayImage::from_fn(4, 3, |x, y| Luma { data: [(10 * y + x) as u8] })
+    }
 
     #[test]
     fn safepx_computation() {
+        let image = ramp();
+
+        assert_eq!(safepx(&image, 2.0, 1.0), 12.0);
+        assert_eq!(safepx(&image, 2.7, 1.2), 12.0);
 
-        unimplemented!()
+        // the coordinates are clamped to the image
+        assert_eq!(safepx(&image, -3.0, 1.0), 10.0);
+        assert_eq!(safepx(&image, 9.0, 5.0), 23.0);
     }
 
     #[test]
     fn subpx_computation() {
+        let image = ramp();
+
+        assert!((subpx(&image, 2.0, 1.0) - 12.0).abs() < 1e-6);
+
+        // the bilinear interpolation of a linear ramp is exact
+        assert!((subpx(&image, 1.25, 0.5) - 6.25).abs() < 1e-6);
+
+        // the border is replicated
+        assert!((subpx(&image, 3.5, 1.0) - 13.0).abs() < 1e-6);
+        assert!((subpx(&image, -0.5, 2.5) - 20.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn tracks_a_translation() {
+        let (image_i, image_j) = (fixtures::texture(96, 96, 0.0, 0.0), fixtures::texture(96, 96, 2.5, -1.5));
+        let points = fixtures::grid((32.0, 32.0), (32.0, 32.0), 4);
+
+        let flow = PyramLk::default().flow(&image_i, &points, &image_j).unwrap();
+
+        assert_eq!(flow.len(), points.len());
+
+        for (flow_point, point_i) in flow.iter().zip(&points) {
+            assert_eq!(flow_point.status, FlowStatus::Tracked);
+
+            let ([x_i, y_i], [x_j, y_j]) = (point_i.coordinates(), flow_point.point.coordinates());
+            let (dx, dy): (f32, f32) = ((x_j - x_i).into(), (y_j - y_i).into());
+
+            assert!((dx - 2.5).abs() < 0.1 && (dy + 1.5).abs() < 0.1, "{} {}", dx, dy);
+            assert!(flow_point.min_eigenvalue > 0.0);
+        }
+    }
+
+    #[test]
+    fn points_outside_the_image() {
+        let image = fixtures::texture(96, 96, 0.0, 0.0);
+        let points = [fixtures::point(-1.0, 10.0), fixtures::point(96.0, 10.0)];
+
+        for (flow_point, point_i) in PyramLk::default().flow(&image, &points, &image).unwrap().iter().zip(&points) {
+            assert_eq!(flow_point.status, FlowStatus::OutOfBounds);
+
+            let ([x_i, y_i], [x_j, y_j]) = (point_i.coordinates(), flow_point.point.coordinates());
+
+            assert!(x_i == x_j && y_i == y_j);
+        }
+    }
+
+    #[test]
+    fn uniform_patch() {
+        let image = GrayImage::from_pixel(96, 96, Luma { data: [128] });
+
+        let flow = PyramLk::default().flow(&image, &[fixtures::point(48.0, 48.0)], &image).unwrap();
+
+        assert_eq!(flow[0].status, FlowStatus::Singular);
+    }
+
+    #[test]
+    fn mismatched_frames() {
+        let lk = PyramLk::builder().height(3).build();
+        let image_i = fixtures::texture(96, 96, 0.0, 0.0);
+
+        // an unrelated, rough texture
+        let image_j = GrayImage::from_fn(96, 96, |x, y| Luma { data: [((x * 37 + y * 91 + x * y * 13) % 251) as u8] });
+        let points: Vec<_> = [(20.0, 20.0), (30.0, 60.0), (48.0, 48.0), (60.0, 30.0)].iter()
+            .map(|&(x, y)| fixtures::point(x, y))
+            .collect();
+
+        for flow_point in lk.flow(&image_i, &points, &image_j).unwrap() {
+            assert_eq!(flow_point.status, FlowStatus::Diverged);
+        }
+
+        // the texture moves to the left, past the border
+        let image_j = fixtures::texture(96, 96, -2.0, 0.0);
+
+        let flow = lk.flow(&image_i, &[fixtures::point(0.5, 48.0)], &image_j).unwrap();
 
-        unimplemented!()
+        assert_eq!(flow[0].status, FlowStatus::Lost);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn epsilon_stops_the_iterations() {
+        let (image_i, image_j) = (fixtures::texture(96, 96, 0.0, 0.0), fixtures::texture(96, 96, 2.5, -1.5));
+        let points = [fixtures::point(48.0, 48.0)];
+
+        let flow = |lk: PyramLk| -> (f32, f32) {
+            let points_j: Vec<FlowPoint> = lk.flow(&image_i, &points, &image_j).unwrap();
+            let [x, y] = points_j[0].point.coordinates();
+
+            (x.into(), y.into())
+        };
+
+        let builder = PyramLk::builder().height(1);
+
+        // every update is smaller than `epsilon`: a single iteration
+        let early = flow(builder.clone().max_iterations(20).epsilon(1000.0).build());
+
+        assert_eq!(early, flow(builder.clone().max_iterations(1).epsilon(None).build()));
+        assert_eq!(early, flow(PyramLk::new(1, 7, 1)));
+
+        // a single iteration doesn't converge from this far
+        let (x, y) = flow(PyramLk::new(1, 7, 20));
+
+        assert!((x - 50.5).abs() < 0.1 && (y - 46.5).abs() < 0.1);
+        assert!((early.0 - x).abs() + (early.1 - y).abs() > 0.1);
+    }
+
+    #[test]
+    fn min_eigenvalue_rejects_weak_features() {
+        // a low-contrast copy of the texture, in `[0, 0.1]`
+        let faint = |dx, dy| {
+            let texture = fixtures::texture(96, 96, dx, dy);
+
+            Gray32FImage::from_fn(96, 96, |x, y| Luma { data: [texture[(x, y)].data[0] as f32 / 2550.0] })
+        };
+
+        let (image_i, image_j) = (faint(0.0, 0.0), faint(2.5, -1.5));
+        let points = fixtures::grid((32.0, 32.0), (32.0, 32.0), 2);
+
+        for flow_point in PyramLk::builder().height(3).build().flow(&image_i, &points, &image_j).unwrap() {
+            assert_eq!(flow_point.status, FlowStatus::Singular);
+            assert!(flow_point.min_eigenvalue < 1e-4);
+        }
+
+        for (flow_point, point_i) in PyramLk::new(3, 7, 20).flow(&image_i, &points, &image_j).unwrap().iter().zip(&points) {
+            assert_eq!(flow_point.status, FlowStatus::Tracked);
+
+            let ([x_i, y_i], [x_j, y_j]) = (point_i.coordinates(), flow_point.point.coordinates());
+            let (dx, dy): (f32, f32) = ((x_j - x_i).into(), (y_j - y_i).into());
+
+            assert!((dx - 2.5).abs() < 0.1 && (dy + 1.5).abs() < 0.1, "{} {}", dx, dy);
+        }
+    }
+
+    #[test]
+    fn guesses_beyond_the_reach_of_the_pyramid() {
+        let (image_i, image_j) = (fixtures::texture(160, 120, 0.0, 0.0), fixtures::texture(160, 120, 14.0, 9.0));
+        let points: Vec<_> = [(60.0, 50.0), (80.0, 60.0), (100.0, 70.0)].iter()
+            .map(|&(x, y)| fixtures::point(x, y))
+            .collect();
+
+        // 2 levels of a 3-pixel window reach about 9 pixels
+        let lk = PyramLk::builder().height(2).window(3, 3).build();
+
+        for flow_point in lk.flow(&image_i, &points, &image_j).unwrap() {
+            assert!(!flow_point.is_tracked());
+        }
+
+        // a prediction a pixel off along each axis
+        let guesses: Vec<_> = points.iter().map(|point_i| {
+            let [x, y] = point_i.coordinates();
+            let (x, y): (f32, f32) = (x.into(), y.into());
+
+            fixtures::point(x + 13.0, y + 8.0)
+        }).collect();
+
+        let flow = lk.flow_with_guess(&image_i, &points, &guesses, &image_j).unwrap();
+
+        for (flow_point, point_i) in flow.iter().zip(&points) {
+            assert_eq!(flow_point.status, FlowStatus::Tracked);
+
+            let ([x_i, y_i], [x_j, y_j]) = (point_i.coordinates(), flow_point.point.coordinates());
+            let (dx, dy): (f32, f32) = ((x_j - x_i).into(), (y_j - y_i).into());
+
+            assert!((dx - 14.0).abs() < 0.1 && (dy - 9.0).abs() < 0.1, "{} {}", dx, dy);
+        }
+
+        assert!(lk.flow_with_guess(&image_i, &points, &guesses[..2], &image_j).is_err());
+    }
+
+    #[test]
+    fn tracks_16_bit_and_floating_point_images() {
+        let (texture_i, texture_j) = (fixtures::texture(96, 96, 0.0, 0.0), fixtures::texture(96, 96, 2.5, -1.5));
+        let points = fixtures::grid((32.0, 32.0), (32.0, 32.0), 4);
+
+        let gray16 = |texture: &GrayImage| {
+            Gray16Image::from_fn(96, 96, |x, y| Luma { data: [texture[(x, y)].data[0] as u16 * 257] })
+        };
+
+        let gray32f = |texture: &GrayImage| {
+            Gray32FImage::from_fn(96, 96, |x, y| Luma { data: [texture[(x, y)].data[0] as f32 / 255.0] })
+        };
+
+        // the threshold scales with the square of the range of the intensities
+        let lk16 = PyramLk::builder().min_eigenvalue(1e-4 * 257.0 * 257.0).build();
+        let lk32f = PyramLk::builder().min_eigenvalue(1e-4 / (255.0 * 255.0)).build();
+
+        let flows = [
+            PyramLk::default().flow(&texture_i, &points, &texture_j).unwrap(),
+            lk16.flow(&gray16(&texture_i), &points, &gray16(&texture_j)).unwrap(),
+            lk32f.flow(&gray32f(&texture_i), &points, &gray32f(&texture_j)).unwrap(),
+        ];
+
+        for flow in &flows {
+            for (flow_point, point_i) in flow.iter().zip(&points) {
+                assert_eq!(flow_point.status, FlowStatus::Tracked);
+
+                let ([x_i, y_i], [x_j, y_j]) = (point_i.coordinates(), flow_point.point.coordinates());
+                let (dx, dy): (f32, f32) = ((x_j - x_i).into(), (y_j - y_i).into());
+
+                assert!((dx - 2.5).abs() < 0.1 && (dy + 1.5).abs() < 0.1, "{} {}", dx, dy);
+            }
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_flow_keeps_the_order() {
+        use image::LumaPyramid;
+
+        let lk = PyramLk::default();
+
+        let pyramid_i = LumaPyramid::build(&fixtures::texture(160, 120, 0.0, 0.0), 4);
+        let pyramid_j = LumaPyramid::build(&fixtures::texture(160, 120, 2.5, -1.5), 4);
+
+        // a shuffled grid, with points outside the image
+        let mut points = fixtures::grid((-20.0, -10.0), (200.0, 140.0), 10);
+
+        points.reverse();
+        points.swap(3, 71);
+
+        let flow = lk.flow(&pyramid_i, &points, &pyramid_j).unwrap();
+
+        assert_eq!(flow.len(), points.len());
+
+        // every point tracked on its own
+        for (flow_point, point_i) in flow.iter().zip(&points) {
+            let alone = lk.flow(&pyramid_i, &[*point_i], &pyramid_j).unwrap()[0];
+
+            let ([x, y], [x_alone, y_alone]) = (flow_point.point.coordinates(), alone.point.coordinates());
+
+            assert_eq!(flow_point.status, alone.status);
+            assert!(x == x_alone && y == y_alone);
+        }
+    }
+}
@@ -1,3 +1,9 @@
-pub use self::lucas_kanade::PyramLk;
+pub use self::block_matching::{BlockMatching, Cost, Search};
+pub use self::inverse_compositional::{AffineWarp, InverseCompositional, TemplateTracker};
+pub use self::lucas_kanade::{PyramLk, PyramLkBuilder};
+pub use self::stabilization::Stabilizer;
 
-mod lucas_kanade;
\ No newline at end of file
+mod block_matching;
+mod inverse_compositional;
+mod lucas_kanade;
+mod stabilization;
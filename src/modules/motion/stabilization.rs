@@ -0,0 +1,374 @@
+use core::io::FrameSource;
+use core::motion::{Estimator, Flow, Method, MotionModel, Transform};
+use error::{Error, ErrorKind, Result};
+use float::FloatGuard;
+use image::{GrayPyramid, Intensity};
+use piston_image::{GrayImage, Luma};
+use std::collections::VecDeque;
+use std::{cmp, mem};
+use super::PyramLk;
+use super::lucas_kanade::subpx;
+use utility::plane_euclidean::Point;
+
+/// The height of the pyramids used to track the features between consecutive frames.
+const PYRAMID_HEIGHT: usize = 4;
+
+/// The camera pose (relative to the first frame), as the accumulated similarity motion of the
+/// frame content about the centre of the frame.
+#[derive(Clone, Copy, Debug, Default)]
+struct Pose {
+    x: f64,
+    y: f64,
+    angle: f64,
+    /// The logarithm of the scale.
+    scale: f64,
+}
+
+impl Pose {
+
+    fn add(&self, other: &Pose) -> Pose {
+        Pose {
+            x: self.x + other.x,
+            y: self.y + other.y,
+            angle: self.angle + other.angle,
+            scale: self.scale + other.scale,
+        }
+    }
+
+    fn sub(&self, other: &Pose) -> Pose {
+        Pose {
+            x: self.x - other.x,
+            y: self.y - other.y,
+            angle: self.angle - other.angle,
+            scale: self.scale - other.scale,
+        }
+    }
+}
+
+/// Video stabilization
+///
+/// A processing stage that consumes a `FrameSource` of grayscale frames and yields stabilized
+/// frames.
+///
+/// 1) Features on a regular grid are tracked between consecutive frames with `PyramLk`. Features
+///    on flat regions are rejected by the tracker.
+/// 2) A global similarity transform (translation, rotation and scale) is estimated from the
+///    tracked features with RANSAC. It is the motion of the camera between the two frames.
+/// 3) The camera trajectory (the accumulated motion) is smoothed with a centred moving average
+///    over `2 × radius + 1` frames. Smoothing requires `radius` frames of look-ahead, so the
+///    output lags the input by `radius` frames.
+/// 4) Each frame is warped by the difference between the smoothed and the actual trajectory,
+///    which cancels the jitter while keeping the intended motion (e.g., panning).
+/// 5) A border of `crop` × the frame size is cropped from every side to hide the regions
+///    uncovered by the warp.
+///
+/// The frames must all have the dimensions of the first one; `next_frame` returns an error
+/// when the source yields a frame of other dimensions.
+///
+/// ```rust,ignore
+/// use miro::core::io::{FrameSource, IterSource};
+/// use miro::modules::motion::Stabilizer;
+///
+/// let mut stabilizer = Stabilizer::new(IterSource::new(frames)).radius(15).crop(0.1);
+///
+/// while let Some(frame) = stabilizer.next_frame()? {
+///     // ..
+/// }
+/// ```
+pub struct Stabilizer<S> {
+    source: S,
+    lk: PyramLk,
+    estimator: Estimator,
+
+    /// The smoothing radius, in frames.
+    radius: usize,
+    /// The fraction of the frame size cropped from each side.
+    crop: f32,
+    /// The spacing of the grid of tracked features, in pixels.
+    spacing: u32,
+
+    /// The features tracked from every frame.
+    points: Vec<Point<f32>>,
+    /// The pyramid of the last frame read from the source.
+    pyramid: Option<GrayPyramid>,
+    /// A pyramid whose buffers are reused for the next frame.
+    spare: Option<GrayPyramid>,
+    /// The pose of the last frame read from the source.
+    pose: Pose,
+
+    /// The frames read from the source but not yet returned.
+    frames: VecDeque<GrayImage>,
+    /// The poses of the frames in `frames`, preceded by the poses of up to `radius` frames
+    /// that have already been returned.
+    poses: VecDeque<Pose>,
+    /// The number of poses in `poses` preceding the pose of the next frame to return.
+    past: usize,
+    exhausted: bool,
+}
+
+impl<S> Stabilizer<S> where S: FrameSource<Frame = GrayImage> {
+
+    /// Constructs a stabilizer with a smoothing radius of 15 frames, a crop of 10% and a grid
+    /// spacing of 20 pixels.
+    pub fn new(source: S) -> Stabilizer<S> {
+
+        Stabilizer {
+            source,
+            lk: PyramLk::default(),
+            estimator: Estimator::new(MotionModel::Similarity, Method::Ransac { threshold: 1.0 }),
+            radius: 15,
+            crop: 0.1,
+            spacing: 20,
+            points: Vec::new(),
+            pyramid: None,
+            spare: None,
+            pose: Pose::default(),
+            frames: VecDeque::new(),
+            poses: VecDeque::new(),
+            past: 0,
+            exhausted: false,
+        }
+    }
+
+    /// Sets the smoothing radius, in frames.
+    pub fn radius(mut self, radius: usize) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    /// Sets the fraction of the frame size cropped from each side, clamped to `[0, 0.45]`.
+    pub fn crop(mut self, crop: f32) -> Self {
+        self.crop = crop.max(0.0).min(0.45);
+        self
+    }
+
+    /// Sets the spacing of the grid of tracked features. If `spacing` is 0, 1 is chosen.
+    pub fn spacing(mut self, spacing: u32) -> Self {
+        self.spacing = cmp::max(1, spacing);
+        self
+    }
+
+    /// Sets the tracker used between consecutive frames.
+    pub fn tracker(mut self, lk: PyramLk) -> Self {
+        self.lk = lk;
+        self
+    }
+
+    /// Reads a frame from the source and computes its pose.
+    fn read(&mut self) -> Result<bool> {
+
+        let frame = match self.source.next_frame()? {
+            Some(frame) => frame,
+            None => return Ok(false),
+        };
+
+        // the grid of tracked points and the trajectory hold for the first frame's dimensions
+        if let Some(ref prev) = self.pyramid {
+            if prev[0].dimensions() != frame.dimensions() {
+                let message = format!("expected a frame of {:?}, found {:?}",
+                    prev[0].dimensions(), frame.dimensions());
+
+                return Err(Error::new(ErrorKind::Motion, message));
+            }
+        }
+
+        let pyramid = match self.spare.take() {
+            Some(mut pyramid) => {
+                pyramid.rebuild_from(&frame);
+                pyramid
+            },
+            None => GrayPyramid::build(&frame, PYRAMID_HEIGHT),
+        };
+
+        let motion = self.pyramid.as_ref().map(|prev| {
+            self.motion(prev, &pyramid).unwrap_or_else(|| {
+                debug!("Failed to estimate the camera motion; assuming a static camera");
+
+                Pose::default()
+            })
+        });
+
+        match motion {
+            Some(motion) => self.pose = self.pose.add(&motion),
+            None => self.points = grid(frame.dimensions(), self.spacing),
+        }
+
+        self.spare = mem::replace(&mut self.pyramid, Some(pyramid));
+
+        self.frames.push_back(frame);
+        self.poses.push_back(self.pose);
+
+        Ok(true)
+    }
+
+    /// Estimates the motion of the content between two consecutive frames.
+    fn motion(&self, prev: &GrayPyramid, curr: &GrayPyramid) -> Option<Pose> {
+
+        let estimate = {
+            self.lk.flow(prev, &self.points, curr)
+                .and_then(|flow| self.estimator.estimate_flow(&self.points, &flow))
+        };
+
+        let estimate = match estimate {
+            Ok(estimate) => estimate,
+            Err(_) => return None,
+        };
+
+        let m = &estimate.transform.matrix;
+
+        // the translation is measured at the centre of the frame, so that it doesn't depend on
+        // the rotation
+        let (width, height) = prev[0].dimensions();
+        let (cx, cy) = (width as f64 / 2.0, height as f64 / 2.0);
+        let (x, y) = estimate.transform.apply(cx, cy);
+
+        Some(Pose {
+            x: x - cx,
+            y: y - cy,
+            angle: m[1][0].atan2(m[0][0]),
+            scale: m[1][0].hypot(m[0][0]).ln(),
+        })
+    }
+}
+
+impl<S> FrameSource for Stabilizer<S> where S: FrameSource<Frame = GrayImage> {
+
+    type Frame = GrayImage;
+
+    /// Returns the next stabilized frame.
+    fn next_frame(&mut self) -> Result<Option<GrayImage>> {
+
+        while !self.exhausted && self.frames.len() <= self.radius {
+            self.exhausted = !self.read()?;
+        }
+
+        let frame = match self.frames.pop_front() {
+            Some(frame) => frame,
+            None => return Ok(None),
+        };
+
+        // centred moving average (truncated at both ends of the video)
+        let window = cmp::min(self.past + self.radius + 1, self.poses.len());
+
+        let smoothed = {
+            let sum = self.poses.iter().take(window).fold(Pose::default(), |sum, pose| sum.add(pose));
+            let n = window as f64;
+
+            Pose { x: sum.x / n, y: sum.y / n, angle: sum.angle / n, scale: sum.scale / n }
+        };
+
+        let correction = smoothed.sub(&self.poses[self.past]);
+
+        self.past += 1;
+
+        if self.past > self.radius {
+            self.poses.pop_front();
+            self.past -= 1;
+        }
+
+        Ok(Some(warp(&frame, &correction, self.crop)))
+    }
+}
+
+/// A regular grid of points, `spacing` pixels apart.
+fn grid((width, height): (u32, u32), spacing: u32) -> Vec<Point<f32>> {
+
+    let mut points = Vec::new();
+
+    for y in (0..height / spacing).map(|n| (n * spacing + spacing / 2) as f32) {
+        for x in (0..width / spacing).map(|n| (n * spacing + spacing / 2) as f32) {
+            points.push(unsafe { [FloatGuard::from_unchecked(x), FloatGuard::from_unchecked(y)] }.into());
+        }
+    }
+
+    points
+}
+
+/// Moves the content of `frame` by `correction` (about the centre of the frame) and crops it.
+fn warp(frame: &GrayImage, correction: &Pose, crop: f32) -> GrayImage {
+
+    let (width, height) = frame.dimensions();
+    let (cx, cy) = (width as f64 / 2.0, height as f64 / 2.0);
+
+    let (scale, angle) = (correction.scale.exp(), correction.angle);
+
+    // K(p) = RS(p − c) + c + d
+    let (rs_cx, rs_cy) = Transform::similarity(scale, angle, 0.0, 0.0).apply(cx, cy);
+    let correction = Transform::similarity(
+        scale, angle, cx + correction.x - rs_cx, cy + correction.y - rs_cy);
+
+    let inverse = correction.inverse().unwrap_or_else(Transform::identity);
+
+    let margin_x = (width as f32 * crop).round() as u32;
+    let margin_y = (height as f32 * crop).round() as u32;
+
+    GrayImage::from_fn(width - 2 * margin_x, height - 2 * margin_y, |u, v| {
+        let (x, y) = inverse.apply((u + margin_x) as f64, (v + margin_y) as f64);
+
+        let inside = x >= 0.0 && y >= 0.0 && x <= (width - 1) as f64 && y <= (height - 1) as f64;
+
+        let intensity = if inside { subpx(frame, x as f32, y as f32) } else { 0.0 };
+
+        Luma { data: [u8::from_intensity(intensity)] }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use core::io::{FrameSource, IterSource};
+    use piston_image::GrayImage;
+    use utility::fixtures;
+    use super::Stabilizer;
+
+    /// The mean absolute difference of two images of the same size.
+    fn difference(a: &GrayImage, b: &GrayImage) -> f32 {
+        let sum: f32 = a.iter().zip(b.iter()).map(|(&p, &q)| (p as f32 - q as f32).abs()).sum();
+
+        sum / a.len() as f32
+    }
+
+    #[test]
+    fn cancels_the_jitter() {
+        // a static camera, shaken with a period of 3 frames
+        let jitter = [(0.0, 0.0), (2.0, -1.0), (-2.0, 1.0)];
+
+        let frames: Vec<GrayImage> = (0..9).map(|n| {
+            let (dx, dy) = jitter[n % 3];
+
+            fixtures::texture(160, 120, dx, dy)
+        }).collect();
+
+        let mut stabilizer = Stabilizer::new(IterSource::new(frames.clone())).radius(1).crop(0.1);
+        let mut stabilized = Vec::new();
+
+        while let Some(frame) = stabilizer.next_frame().unwrap() {
+            stabilized.push(frame);
+        }
+
+        assert_eq!(stabilized.len(), frames.len());
+        assert_eq!(stabilized[0].dimensions(), (128, 96));
+
+        // the frames, cropped as the stabilized frames
+        let crop = |frame: &GrayImage| GrayImage::from_fn(128, 96, |x, y| *frame.get_pixel(x + 16, y + 12));
+        let reference = crop(&frames[0]);
+
+        // a window of 3 frames averages the jitter out, except at both ends of the video
+        for n in 1..frames.len() - 1 {
+            assert!(difference(&stabilized[n], &reference) < 2.0, "frame {}", n);
+
+            if n % 3 != 0 {
+                assert!(difference(&crop(&frames[n]), &reference) > 5.0);
+            }
+        }
+    }
+
+    #[test]
+    fn frames_of_other_dimensions() {
+        let frames = vec![fixtures::texture(160, 120, 0.0, 0.0), fixtures::texture(120, 160, 0.0, 0.0)];
+
+        let mut stabilizer = Stabilizer::new(IterSource::new(frames)).radius(0);
+
+        assert!(stabilizer.next_frame().unwrap().is_some());
+        assert!(stabilizer.next_frame().is_err());
+    }
+}
@@ -0,0 +1,499 @@
+use core::tracking::{Track, TrackMut};
+use error::{Error, ErrorKind, Result};
+use euclidean::Region2D as Region;
+use nalgebra::{DMat, DVec, Inv, Transpose};
+
+/// The motion model of a `KalmanFilter` built by `KalmanFilter::with_dynamics`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Dynamics {
+    /// The state holds a position and a velocity per dimension; the acceleration is modelled
+    /// as white noise.
+    ConstantVelocity,
+    /// The state holds a position, a velocity and an acceleration per dimension; the jerk is
+    /// modelled as white noise.
+    ConstantAcceleration,
+}
+
+impl Dynamics {
+
+    /// The number of state variables per observed dimension.
+    fn order(&self) -> usize {
+        match *self {
+            Dynamics::ConstantVelocity => 2,
+            Dynamics::ConstantAcceleration => 3,
+        }
+    }
+}
+
+/// A linear [Kalman filter]
+///
+/// Estimates the state `x` of a linear system from noisy measurements `z`:
+///
+/// ```text
+/// xₖ = F xₖ₋₁ + wₖ,  wₖ ~ N(0, Q)
+/// zₖ = H xₖ + vₖ,    vₖ ~ N(0, R)
+/// ```
+///
+/// [Kalman filter]: https://en.wikipedia.org/wiki/Kalman_filter
+#[derive(Clone, Debug)]
+pub struct KalmanFilter {
+    /// The state estimate.
+    x: DVec<f64>,
+    /// The covariance of the state estimate.
+    p: DMat<f64>,
+    /// The state transition model.
+    f: DMat<f64>,
+    /// The observation model.
+    h: DMat<f64>,
+    /// The covariance of the process noise.
+    q: DMat<f64>,
+    /// The covariance of the observation noise.
+    r: DMat<f64>,
+}
+
+impl KalmanFilter {
+
+    /// Constructs a Kalman filter from its models and its initial state.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - The state transition model (n × n).
+    /// * `h` - The observation model (m × n).
+    /// * `q` - The covariance of the process noise (n × n).
+    /// * `r` - The covariance of the observation noise (m × m).
+    /// * `x` - The initial state (n).
+    /// * `p` - The covariance of the initial state (n × n).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the dimensions of the matrices don't agree.
+    pub fn new(f: DMat<f64>, h: DMat<f64>, q: DMat<f64>, r: DMat<f64>, x: DVec<f64>, p: DMat<f64>)
+        -> KalmanFilter
+    {
+        let (n, m) = (x.len(), h.nrows());
+
+        assert!(f.nrows() == n && f.ncols() == n, "`f` must be {}×{}", n, n);
+        assert!(h.ncols() == n, "`h` must be {}×{}", m, n);
+        assert!(q.nrows() == n && q.ncols() == n, "`q` must be {}×{}", n, n);
+        assert!(r.nrows() == m && r.ncols() == m, "`r` must be {}×{}", m, m);
+        assert!(p.nrows() == n && p.ncols() == n, "`p` must be {}×{}", n, n);
+
+        KalmanFilter { x, p, f, h, q, r }
+    }
+
+    /// Constructs a Kalman filter that observes the positions of a point in `initial.len()`
+    /// dimensions, with the chosen `dynamics`.
+    ///
+    /// The state is ordered by derivative: all positions, then all velocities (then all
+    /// accelerations). The derivatives start at zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `dynamics` - The motion model.
+    /// * `initial` - The initial position.
+    /// * `dt` - The time step between two predictions.
+    /// * `process_noise` - The standard deviation of the highest derivative noise (the
+    ///   acceleration for `ConstantVelocity`, the jerk for `ConstantAcceleration`).
+    /// * `measurement_noise` - The standard deviation of the position measurements.
+    pub fn with_dynamics(dynamics: Dynamics,
+                         initial: &[f64],
+                         dt: f64,
+                         process_noise: f64,
+                         measurement_noise: f64) -> KalmanFilter
+    {
+        let dims = initial.len();
+        let order = dynamics.order();
+        let n = dims * order;
+
+        // Taylor coefficients: x(t + dt) = x + dt·x' + dt²/2·x''
+        let coefficient = |k: usize| (1..k + 1).fold(1.0, |c, i| c * dt / i as f64);
+
+        // the noise enters through the highest derivative: G = [dtᵏ/k!, .., dt, 1]ᵀ
+        let g: Vec<f64> = (0..order).map(|d| coefficient(order - 1 - d)).collect();
+
+        let mut f = DMat::new_zeros(n, n);
+        let mut q = DMat::new_zeros(n, n);
+        let mut h = DMat::new_zeros(dims, n);
+        let mut x = DVec::new_zeros(n);
+        let mut p = DMat::new_zeros(n, n);
+
+        for dim in 0..dims {
+            for row in 0..order {
+                for col in row..order {
+                    f[(row * dims + dim, col * dims + dim)] = coefficient(col - row);
+                }
+
+                for col in 0..order {
+                    q[(row * dims + dim, col * dims + dim)] = g[row] * g[col] * process_noise.powi(2);
+                }
+            }
+
+            h[(dim, dim)] = 1.0;
+            x[dim] = initial[dim];
+
+            // the position is known up to the measurement noise; the derivatives are unknown
+            p[(dim, dim)] = measurement_noise.powi(2);
+
+            for derivative in 1..order {
+                p[(derivative * dims + dim, derivative * dims + dim)] = 1e4;
+            }
+        }
+
+        let mut r = DMat::new_zeros(dims, dims);
+
+        for dim in 0..dims {
+            r[(dim, dim)] = measurement_noise.powi(2);
+        }
+
+        KalmanFilter { x, p, f, h, q, r }
+    }
+
+    /// Returns the state estimate.
+    pub fn state(&self) -> &[f64] {
+        self.x.as_ref()
+    }
+
+    /// Returns the covariance of the state estimate.
+    pub fn covariance(&self) -> &DMat<f64> {
+        &self.p
+    }
+
+    /// Returns the predicted measurement `H x` of the current state.
+    pub fn measurement(&self) -> DVec<f64> {
+        self.h.clone() * self.x.clone()
+    }
+
+    /// Advances the state by one time step.
+    pub fn predict(&mut self) {
+        // x = F x
+        self.x = self.f.clone() * self.x.clone();
+
+        // P = F P Fᵀ + Q
+        self.p = self.f.clone() * self.p.clone() * self.f.transpose() + self.q.clone();
+    }
+
+    /// Updates the state with the measurement `z`.
+    ///
+    /// Returns an error if `z` doesn't have the dimension of the observation model, or if the
+    /// covariance of the innovation is singular.
+    pub fn correct(&mut self, z: &[f64]) -> Result {
+
+        if z.len() != self.h.nrows() {
+            let message = format!("expected a measurement of dimension {}, found {}",
+                self.h.nrows(), z.len());
+
+            return Err(Error::new(ErrorKind::Tracking, message));
+        }
+
+        let n = self.x.len();
+
+        // innovation: y = z − H x
+        let y = DVec::from_slice(z.len(), z) - self.measurement();
+
+        // innovation covariance: S = H P Hᵀ + R
+        let h_t = self.h.transpose();
+        let s = self.h.clone() * self.p.clone() * h_t.clone() + self.r.clone();
+
+        let s_inv = s.inv().ok_or_else(|| {
+            Error::new(ErrorKind::Tracking, "the innovation covariance is singular")
+        })?;
+
+        // optimal gain: K = P Hᵀ S⁻¹
+        let k = self.p.clone() * h_t * s_inv;
+
+        // x = x + K y
+        self.x = self.x.clone() + k.clone() * y;
+
+        // P = (I − K H) P
+        self.p = (DMat::new_identity(n) - k * self.h.clone()) * self.p.clone();
+
+        Ok(())
+    }
+}
+
+/// A Kalman filter over a bounding box.
+///
+/// The observed variables are the centre and the size of the box, each with its own
+/// derivatives (see `Dynamics`).
+#[derive(Clone, Debug)]
+pub struct RegionFilter {
+    filter: KalmanFilter,
+}
+
+impl RegionFilter {
+
+    /// Constructs a filter initialized with `region`.
+    ///
+    /// # Arguments
+    ///
+    /// * `region` - The initial bounding box.
+    /// * `dynamics` - The motion model.
+    /// * `process_noise` - See `KalmanFilter::with_dynamics` (in pixels per time step).
+    /// * `measurement_noise` - The standard deviation of the measured boxes (in pixels).
+    pub fn new(region: Region, dynamics: Dynamics, process_noise: f64, measurement_noise: f64)
+        -> RegionFilter
+    {
+        let filter = {
+            KalmanFilter::with_dynamics(dynamics, &observe(region), 1.0, process_noise, measurement_noise)
+        };
+
+        RegionFilter { filter }
+    }
+
+    /// Returns the estimated bounding box.
+    pub fn region(&self) -> Region {
+        let state = self.filter.state();
+
+        let (cx, cy) = (state[0], state[1]);
+        let (width, height) = (state[2].max(1.0), state[3].max(1.0));
+
+        [cx - width / 2.0, cy - height / 2.0, width, height].into()
+    }
+
+    /// Returns the estimated velocity of the centre of the box, in pixels per time step.
+    pub fn velocity(&self) -> (f64, f64) {
+        let state = self.filter.state();
+
+        (state[4], state[5])
+    }
+
+    /// Advances the filter by one time step and returns the predicted bounding box.
+    pub fn predict(&mut self) -> Region {
+        self.filter.predict();
+        self.region()
+    }
+
+    /// Updates the filter with a measured bounding box and returns the estimated bounding box.
+    pub fn correct(&mut self, region: Region) -> Result<Region> {
+        self.filter.correct(&observe(region))?;
+
+        Ok(self.region())
+    }
+}
+
+fn observe(region: Region) -> [f64; 4] {
+    let (x, y, width, height) = region.bounds();
+
+    [x + width / 2.0, y + height / 2.0, width, height]
+}
+
+/// A tracker whose output is filtered by a `RegionFilter`.
+///
+/// The output of the wrapped tracker is smoothed by the filter. When the wrapped tracker fails,
+/// the predicted bounding box is returned instead, for up to `max_misses` consecutive frames.
+///
+/// ```rust,ignore
+/// use miro::core::tracking::TrackMut;
+/// use miro::modules::motion::PyramLk;
+/// use miro::modules::tracking::{MedianFlow, Predictive};
+///
+/// let mut tracker = Predictive::new(MedianFlow::<PyramLk>::default());
+///
+/// region = tracker.track_mut(&image_i, region, &image_j)?;
+/// ```
+pub struct Predictive<T> {
+    tracker: T,
+    filter: Option<RegionFilter>,
+    misses: usize,
+
+    max_misses: usize,
+    dynamics: Dynamics,
+    process_noise: f64,
+    measurement_noise: f64,
+}
+
+impl<T> Predictive<T> {
+
+    /// Wraps `tracker` with a constant-velocity filter that predicts the box for up to 10
+    /// consecutive failures.
+    pub fn new(tracker: T) -> Predictive<T> {
+
+        Predictive {
+            tracker,
+            filter: None,
+            misses: 0,
+            max_misses: 10,
+            dynamics: Dynamics::ConstantVelocity,
+            process_noise: 1.0,
+            measurement_noise: 2.0,
+        }
+    }
+
+    /// Sets the number of consecutive failures of the wrapped tracker that are replaced by
+    /// predictions.
+    pub fn max_misses(mut self, max_misses: usize) -> Self {
+        self.max_misses = max_misses;
+        self
+    }
+
+    /// Sets the motion model of the filter.
+    pub fn dynamics(mut self, dynamics: Dynamics) -> Self {
+        self.dynamics = dynamics;
+        self
+    }
+
+    /// Sets the noise of the filter (see `RegionFilter::new`).
+    pub fn noise(mut self, process_noise: f64, measurement_noise: f64) -> Self {
+        self.process_noise = process_noise;
+        self.measurement_noise = measurement_noise;
+        self
+    }
+
+    /// Returns the filter, if a target is being tracked.
+    pub fn filter(&self) -> Option<&RegionFilter> {
+        self.filter.as_ref()
+    }
+
+    /// Forgets the current target. The next call to `track_mut` starts a new filter.
+    pub fn reset(&mut self) {
+        self.filter = None;
+        self.misses = 0;
+    }
+}
+
+impl<T, I> TrackMut<I> for Predictive<T> where T: Track<I> {
+
+    /// Tracks `region` with the wrapped tracker and filters the result.
+    fn track_mut(&mut self, image_i: &I, region: Region, image_j: &I) -> Result<Region> {
+
+        if self.filter.is_none() {
+            self.filter = Some(RegionFilter::new(
+                region, self.dynamics, self.process_noise, self.measurement_noise));
+        }
+
+        let result = self.tracker.track(image_i, region, image_j);
+
+        let outcome = {
+            let filter = self.filter.as_mut().unwrap();
+
+            let predicted = filter.predict();
+
+            match result {
+                Ok(measured) => filter.correct(measured).map_err(|err| (err, None)),
+                Err(err) => Err((err, Some(predicted))),
+            }
+        };
+
+        match outcome {
+            Ok(region) => {
+                self.misses = 0;
+
+                Ok(region)
+            },
+            Err((_, Some(predicted))) if self.misses < self.max_misses => {
+                self.misses += 1;
+
+                debug!("Tracking failed, predicting the target ({} consecutive misses)", self.misses);
+
+                Ok(predicted)
+            },
+            Err((err, _)) => {
+                self.reset();
+
+                Err(err)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::tracking::{Track, TrackMut};
+    use error::{Error, ErrorKind, Result};
+    use euclidean::Region2D as Region;
+    use std::cell::Cell;
+    use super::{Dynamics, KalmanFilter, Predictive};
+
+    /// A tracker that replays its measurements, one per frame (`None` is a failure).
+    struct Script {
+        measurements: Vec<Option<Region>>,
+        frame: Cell<usize>,
+    }
+
+    impl Track<()> for Script {
+
+        fn track(&self, _: &(), _: Region, _: &()) -> Result<Region> {
+            let frame = self.frame.get();
+
+            self.frame.set(frame + 1);
+
+            self.measurements[frame].ok_or_else(|| Error::new(ErrorKind::Tracking, "lost"))
+        }
+    }
+
+    /// A 20×20 box at (`x`, 20).
+    fn square(x: f64) -> Region {
+        [x, 20.0, 20.0, 20.0].into()
+    }
+
+    fn assert_near(region: Region, x: f64) {
+        let (x_r, y_r, width, height) = region.bounds();
+
+        assert!((x_r - x).abs() < 0.1 && (y_r - 20.0).abs() < 0.1, "{:?} != ({}, 20)", (x_r, y_r), x);
+        assert!((width - 20.0).abs() < 0.1 && (height - 20.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn constant_velocity_converges() {
+        let mut filter = KalmanFilter::with_dynamics(Dynamics::ConstantVelocity, &[0.0], 1.0, 0.01, 0.1);
+
+        for t in 1..50 {
+            filter.predict();
+            filter.correct(&[2.0 * t as f64]).unwrap();
+        }
+
+        assert!((filter.state()[1] - 2.0).abs() < 1e-2);
+
+        filter.predict();
+
+        assert!((filter.state()[0] - 100.0).abs() < 1e-1);
+    }
+
+    #[test]
+    fn measurement_dimension() {
+        let mut filter = KalmanFilter::with_dynamics(Dynamics::ConstantAcceleration, &[0.0, 0.0], 1.0, 1.0, 1.0);
+
+        assert!(filter.correct(&[1.0]).is_err());
+        assert_eq!(filter.state().len(), 6);
+    }
+
+    #[test]
+    fn coasts_on_the_prediction() {
+        // the box moves by 2 px per frame; the tracker misses the frames 21 to 23
+        let measurements = (1..25).map(|t| {
+            if t > 20 && t < 24 { None } else { Some(square(10.0 + 2.0 * t as f64)) }
+        }).collect();
+
+        let mut tracker = Predictive::new(Script { measurements, frame: Cell::new(0) });
+        let mut region = square(10.0);
+
+        for t in 1..25 {
+            region = tracker.track_mut(&(), region, &()).unwrap();
+
+            assert_near(region, 10.0 + 2.0 * t as f64);
+            assert_eq!(tracker.misses, if t > 20 && t < 24 { t - 20 } else { 0 });
+        }
+    }
+
+    #[test]
+    fn resets_after_a_redetection() {
+        let measurements = vec![Some(square(12.0)), Some(square(14.0)), None, None, None, Some(square(80.0))];
+
+        let mut tracker = Predictive::new(Script { measurements, frame: Cell::new(0) }).max_misses(2);
+        let mut region = square(10.0);
+
+        for _ in 0..4 {
+            region = tracker.track_mut(&(), region, &()).unwrap();
+        }
+
+        // the third consecutive miss drops the target
+        assert!(tracker.track_mut(&(), region, &()).is_err());
+        assert!(tracker.filter().is_none());
+
+        // the re-detected target starts a new filter, without the velocity of the old one
+        let region = tracker.track_mut(&(), square(80.0), &()).unwrap();
+
+        assert_near(region, 80.0);
+        assert_eq!(tracker.filter().unwrap().velocity(), (0.0, 0.0));
+    }
+}
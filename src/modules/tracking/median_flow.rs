@@ -3,6 +3,7 @@ use core::tracking::Track;
 use error::{Error, ErrorKind, Result};
 use euclidean::{Point2D as Point, Region2D as Region};
 use float::FloatGuard;
+use modules::feature::Corners;
 use piston_image::GrayImage;
 use std::cmp;
 use utility::statistics;
@@ -20,7 +21,7 @@ use utility::statistics;
 ///
 /// [1]: http://personal.ee.surrey.ac.uk/Personal/Z.Kalal/Publications/2010_icpr.pdf
 /// [2]: https://en.wikipedia.org/wiki/Optical_flow
-pub struct MedianFlow<F> { algorithm: F, λ: usize }
+pub struct MedianFlow<F, S = Grid> { algorithm: F, λ: usize, seeds: S }
 
 impl<F> MedianFlow<F> {
     
@@ -29,7 +30,22 @@ impl<F> MedianFlow<F> {
     /// If `density` is 0, 1 is chosen as the density. 
     pub fn new(algorithm: F, density: usize) -> Self {
 
-        MedianFlow { algorithm, λ: cmp::max(1, density) }
+        MedianFlow { algorithm, λ: cmp::max(1, density), seeds: Grid }
+    }
+}
+
+impl<F, S> MedianFlow<F, S> {
+
+    /// Chooses the tracked points with `seeds` rather than on the uniform grid.
+    ///
+    /// ```rust,ignore
+    /// use miro::modules::feature::Corners;
+    ///
+    /// let tracker = MedianFlow::<PyramLk>::default().seed_with(Corners::shi_tomasi().min_distance(5.0));
+    /// ```
+    pub fn seed_with<T>(self, seeds: T) -> MedianFlow<F, T> {
+
+        MedianFlow { algorithm: self.algorithm, λ: self.λ, seeds }
     }
 }
 
@@ -37,11 +53,69 @@ impl<F> Default for MedianFlow<F> where F: Default {
     
     fn default() -> Self {
         
-        MedianFlow { algorithm: Default::default(), λ: 10 }
+        MedianFlow { algorithm: Default::default(), λ: 10, seeds: Grid }
     }
 }
 
-impl<F> Track<GrayImage> for MedianFlow<F> where F: Flow<GrayImage> {
+/// The choice of the points tracked by `MedianFlow` inside the bounding box.
+pub trait Seed<I> {
+
+    /// Returns at most `count` points inside `region`.
+    fn seed(&self, image: &I, region: Region, count: usize) -> Vec<Point<FloatGuard<f32>>>;
+}
+
+/// Seeds the points on a `λ × λ` rectangular grid, where `λ² = count`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Grid;
+
+impl<I> Seed<I> for Grid {
+
+    fn seed(&self, _: &I, region: Region, count: usize) -> Vec<Point<FloatGuard<f32>>> {
+
+        let λ = cmp::max(1, (count as f64).sqrt() as usize);
+
+        let mut points = Vec::with_capacity(λ * λ);
+
+        let (xregion, yregion, wregion, hregion) = region.bounds();
+
+        let λ_x = wregion / λ as f64;
+        let λ_y = hregion / λ as f64;
+        
+        for x in (0..λ).map(|x| unsafe {
+            FloatGuard::from_unchecked((xregion + (x + 1) as f64 * λ_x) as f32)
+        }) {
+            for y in (0..λ).map(|y| unsafe { 
+                FloatGuard::from_unchecked((yregion + (y + 1) as f64 * λ_y) as f32) 
+            }) {
+            
+                points.push(Point::from([x, y]));
+            }
+        }
+
+        points
+    }
+}
+
+/// Seeds the points on the strongest corners inside the bounding box, so that they avoid the
+/// flat regions where the flow is ill-conditioned. Falls back to the grid if fewer than `λ²`
+/// corners are found, so that the medians are always taken over as many points.
+impl Seed<GrayImage> for Corners {
+
+    fn seed(&self, image: &GrayImage, region: Region, count: usize) -> Vec<Point<FloatGuard<f32>>> {
+
+        let corners = self.max_corners(count).detect_region(image, region);
+
+        if corners.len() < count {
+            debug!("Found {} corners in {:?}; seeding on a grid", corners.len(), region.bounds());
+
+            return Grid.seed(image, region, count);
+        }
+
+        corners.iter().map(|corner| corner.point).collect()
+    }
+}
+
+impl<F, S, I> Track<I> for MedianFlow<F, S> where F: Flow<I>, S: Seed<I> {
 
     /// Median Flow tracker
     ///
@@ -55,33 +129,20 @@ impl<F> Track<GrayImage> for MedianFlow<F> where F: Flow<GrayImage> {
     ///
     /// Returns the bounding box at `t + 1`.
     #[allow(non_snake_case)]
-    fn track(&self, image_i: &GrayImage, region: Region, image_j: &GrayImage) -> Result<Region> {
+    fn track(&self, image_i: &I, region: Region, image_j: &I) -> Result<Region> {
         // TODO forward-backwards tracking
 
-        let ρ = self.λ * self.λ;
-
-        let mut points_i = Vec::with_capacity(ρ);
-
         let (xregion, yregion, wregion, hregion) = region.bounds();
 
-        // Generate a set of points on a `λ x λ` rectangular grid within the `region`.
+        // Choose a set of points within the `region` (by default, on a `λ x λ` rectangular 
+        // grid).
         //
         // These points are then tracked by Lucas-Kanade tracker which generates a sparse motion 
         // flow between `imI` and `imJ`.
-        
-        let λ_x = wregion / self.λ as f64;
-        let λ_y = hregion / self.λ as f64;
-        
-        for x in (0..self.λ).map(|x| unsafe {
-            FloatGuard::from_unchecked((xregion + (x + 1) as f64 * λ_x) as f32)
-        }) {
-            for y in (0..self.λ).map(|y| unsafe { 
-                FloatGuard::from_unchecked((yregion + (y + 1) as f64 * λ_y) as f32) 
-            }) {
-            
-                points_i.push(Point::from([x, y]));
-            }
-        }
+
+        let points_i = self.seeds.seed(image_i, region, self.λ * self.λ);
+
+        let ρ = points_i.len();
         
         let points_j = {
             self.algorithm
@@ -100,7 +161,7 @@ impl<F> Track<GrayImage> for MedianFlow<F> where F: Flow<GrayImage> {
             let mut yscales = Vec::with_capacity(capacity);
             
             for m in 0..ρ {
-                if let Some(point_j_m) = points_j[m] {
+                if let Some(point_j_m) = points_j[m].tracked() {
 
                     let [point_i_m_x, point_i_m_y]: [FloatGuard<f32>; 2] = points_i[m].into();
                     let [point_j_m_x, point_j_m_y]: [FloatGuard<f32>; 2] = point_j_m.into();
@@ -112,7 +173,7 @@ impl<F> Track<GrayImage> for MedianFlow<F> where F: Flow<GrayImage> {
                     δys.push(δy);
 
                     for n in (m + 1)..ρ {
-                        if let Some(point_j_n) = points_j[n] {
+                        if let Some(point_j_n) = points_j[n].tracked() {
                             // scale
                             let δxI = points_i[n].x() - point_i_m_x;
                             let δyI = points_i[n].y() - point_i_m_y;
@@ -150,4 +211,71 @@ impl<F> Track<GrayImage> for MedianFlow<F> where F: Flow<GrayImage> {
 
         Ok([x, y, width, height].into())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use core::tracking::Track;
+    use euclidean::Point2D as Point;
+    use float::FloatGuard;
+    use modules::feature::Corners;
+    use modules::motion::PyramLk;
+    use piston_image::{GrayImage, Luma};
+    use super::{Grid, MedianFlow, Seed};
+    use utility::fixtures;
+
+    fn coordinates(points: &[Point<FloatGuard<f32>>]) -> Vec<(f32, f32)> {
+        points.iter().map(|&point| {
+            let [x, y]: [FloatGuard<f32>; 2] = point.into();
+
+            (x.into(), y.into())
+        }).collect()
+    }
+
+    fn squares() -> GrayImage {
+        GrayImage::from_fn(64, 64, |x, y| {
+            let inside = (x >= 8 && x < 24 && y >= 8 && y < 24) || (x >= 40 && x < 56 && y >= 40 && y < 56);
+
+            Luma { data: [if inside { 200 } else { 50 }] }
+        })
+    }
+
+    #[test]
+    fn tracks_a_textured_region() {
+        let image_i = fixtures::texture(96, 96, 0.0, 0.0);
+        let image_j = fixtures::texture(96, 96, 2.0, 1.0);
+
+        let tracker = MedianFlow::new(PyramLk::default(), 3).seed_with(Corners::shi_tomasi().min_distance(5.0));
+
+        let seeds = tracker.seeds.seed(&image_i, [24.0, 24.0, 48.0, 48.0].into(), 9);
+
+        assert_eq!(seeds.len(), 9);
+        assert!(coordinates(&Grid.seed(&image_i, [24.0, 24.0, 48.0, 48.0].into(), 9)) != coordinates(&seeds));
+
+        let region = tracker.track(&image_i, [24.0, 24.0, 48.0, 48.0].into(), &image_j).unwrap();
+        let (x, y, width, height) = region.bounds();
+
+        assert!((x - 26.0).abs() < 0.1 && (y - 25.0).abs() < 0.1, "{:?}", region.bounds());
+        assert!((width - 48.0).abs() < 0.1 && (height - 48.0).abs() < 0.1, "{:?}", region.bounds());
+    }
+
+    #[test]
+    fn falls_back_to_the_grid() {
+        let corners = Corners::shi_tomasi().min_distance(5.0);
+        let image = squares();
+
+        // the region holds the 4 corners of a square: enough for 4 points, not for 9
+        let seeds = corners.seed(&image, [0.0, 0.0, 32.0, 32.0].into(), 4);
+
+        assert_eq!(seeds.len(), 4);
+        assert!(coordinates(&seeds) != coordinates(&Grid.seed(&image, [0.0, 0.0, 32.0, 32.0].into(), 4)));
+
+        assert_eq!(coordinates(&corners.seed(&image, [0.0, 0.0, 32.0, 32.0].into(), 9)),
+                   coordinates(&Grid.seed(&image, [0.0, 0.0, 32.0, 32.0].into(), 9)));
+
+        // no corners at all on a uniform image
+        let uniform = GrayImage::from_pixel(64, 64, Luma { data: [120] });
+
+        assert_eq!(coordinates(&corners.seed(&uniform, [8.0, 8.0, 32.0, 32.0].into(), 9)),
+                   coordinates(&Grid.seed(&uniform, [8.0, 8.0, 32.0, 32.0].into(), 9)));
+    }
+}
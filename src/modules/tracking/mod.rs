@@ -1,3 +1,5 @@
-pub use self::median_flow::MedianFlow;
+pub use self::kalman::{Dynamics, KalmanFilter, Predictive, RegionFilter};
+pub use self::median_flow::{Grid, MedianFlow, Seed};
 
-mod median_flow;
\ No newline at end of file
+mod kalman;
+mod median_flow;
@@ -0,0 +1,35 @@
+//! Synthetic inputs shared by the tests (the benches have a copy of `texture` and `grid`).
+
+use piston_image::{GrayImage, Luma};
+use utility::plane_euclidean::Point;
+
+/// A smooth `width × height` texture, shifted by (`dx`, `dy`).
+///
+/// The texture has strong gradients in every direction, so that every point away from the
+/// borders can be tracked.
+pub fn texture(width: u32, height: u32, dx: f32, dy: f32) -> GrayImage {
+    GrayImage::from_fn(width, height, |x, y| {
+        let (x, y) = (x as f32 - dx, y as f32 - dy);
+        let value = 127.5 + 63.0 * (x / 7.0).sin() * (y / 11.0).cos() + 63.0 * ((x + y) / 17.0).sin();
+
+        Luma { data: [value as u8] }
+    })
+}
+
+/// A `λ × λ` grid of points spanning the `width × height` rectangle at (`x`, `y`).
+pub fn grid((x, y): (f32, f32), (width, height): (f32, f32), λ: usize) -> Vec<Point<f32>> {
+    let mut points = Vec::with_capacity(λ * λ);
+
+    for m in 0..λ {
+        for n in 0..λ {
+            points.push(point(x + m as f32 * width / λ as f32, y + n as f32 * height / λ as f32));
+        }
+    }
+
+    points
+}
+
+/// Constructs the point (`x`, `y`), through the checked conversion of `Point`.
+pub fn point(x: f32, y: f32) -> Point<f32> {
+    [x, y].into()
+}
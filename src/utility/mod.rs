@@ -1,2 +1,5 @@
 pub mod plane_euclidean;
-pub mod statistics;
\ No newline at end of file
+pub mod statistics;
+
+#[cfg(test)]
+pub mod fixtures;
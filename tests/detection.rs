@@ -0,0 +1,54 @@
+#![feature(plugin)]
+#![plugin(speculate)]
+
+extern crate image;
+extern crate miro;
+
+speculate! {
+
+    describe "a Haar cascade" {
+        use image::{GrayImage, Luma};
+        use miro::modules::detection::HaarCascade;
+
+        const PATH: &'static str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/haarcascade_blob.xml");
+
+        /// A 48×36 image of a blob centred at (30, 14), brighter (`sign` = 1) or darker
+        /// (`sign` = -1) than the background.
+        fn blob(sign: f64) -> GrayImage {
+            GrayImage::from_fn(48, 36, |x, y| {
+                let d2 = (x as f64 - 30.0).powi(2) + (y as f64 - 14.0).powi(2);
+
+                Luma { data: [(120.0 + sign * 100.0 * (-d2 / 18.0).exp()) as u8] }
+            })
+        }
+
+        it "reads an OpenCV cascade from a file" {
+            let cascade = HaarCascade::open(PATH).unwrap();
+
+            assert_eq!(cascade.window_size(), (12, 12));
+        }
+
+        it "fails to read a missing file" {
+            assert!(HaarCascade::open("tests/data/missing.xml").is_err());
+        }
+
+        it "detects a bright blob" {
+            let cascade = HaarCascade::open(PATH).unwrap();
+            let detections = cascade.detect(&blob(1.0));
+
+            assert_eq!(detections.len(), 1);
+
+            let (x, y, w, h) = detections[0].0.bounds();
+
+            assert!((x + w / 2.0 - 30.0).abs() < 2.0 && (y + h / 2.0 - 14.0).abs() < 2.0);
+        }
+
+        it "rejects a dark blob and a uniform image" {
+            let cascade = HaarCascade::open(PATH).unwrap();
+
+            // the ring around a dark blob passes the upright stage, not the tilted one
+            assert!(cascade.hits(&blob(-1.0)).is_empty());
+            assert!(cascade.hits(&GrayImage::from_pixel(48, 36, Luma { data: [120] })).is_empty());
+        }
+    }
+}
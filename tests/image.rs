@@ -14,8 +14,8 @@ speculate! {
     }
 
     describe "a pyramid" {
-        use image::GrayImage;
-        use miro::image::Pyramid;
+        use image::{GrayImage, Luma};
+        use miro::image::{GrayPyramid, Pyramid};
 
         it "contains the correct number of layers" {
             let gray_image = GrayImage::new(1, 1);
@@ -31,5 +31,30 @@ speculate! {
 
             assert!(w == 1, h == 1);
         }
+
+        it "halves the dimensions at every level" {
+            let gray_image = GrayImage::new(640, 361);
+            let pyramid = GrayPyramid::build(&gray_image, 3);
+
+            assert_eq!(pyramid[1].dimensions(), (320, 181));
+            assert_eq!(pyramid[2].dimensions(), (160, 91));
+        }
+
+        it "preserves a constant image" {
+            let gray_image = GrayImage::from_pixel(9, 7, Luma { data: [42] });
+            let pyramid = GrayPyramid::build(&gray_image, 3);
+
+            assert!(pyramid.iter().all(|level| level.pixels().all(|p| p.data[0] == 42)));
+        }
+
+        it "reuses its buffers when rebuilt from an image of the same size" {
+            let mut pyramid = GrayPyramid::build(&GrayImage::new(64, 48), 3);
+            let buffers: Vec<_> = pyramid.iter().map(|level| level.as_ptr()).collect();
+
+            pyramid.rebuild_from(&GrayImage::from_pixel(64, 48, Luma { data: [7] }));
+
+            assert_eq!(pyramid.iter().map(|level| level.as_ptr()).collect::<Vec<_>>(), buffers);
+            assert_eq!(pyramid[2].get_pixel(0, 0).data[0], 7);
+        }
     }
 }
\ No newline at end of file